@@ -1,40 +1,338 @@
 use std::collections::HashMap;
 
-pub fn parse(args: &[String]) -> HashMap<&str, &str> {
-    let mut parsed_args = HashMap::new();
-    args.iter().for_each(|input| {
-        if input == "--debug" {
-            parsed_args.insert("debug", "true");
-        }
+/// What the application was started to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// The terminal user interface, the default.
+    Tui,
+    /// Chat printed line by line to stdout.
+    Tail,
+    /// Plays back a recorded session.
+    Replay,
+    /// Writes every line the server sends to a file.
+    Record,
+}
 
-        if let Some(value) = input.strip_prefix("--nick=") {
-            parsed_args.insert("nick", value);
+impl Command {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tui" => Some(Command::Tui),
+            "tail" => Some(Command::Tail),
+            "replay" => Some(Command::Replay),
+            "record" => Some(Command::Record),
+            _ => None,
         }
+    }
 
-        if let Some(value) = input.strip_prefix("--channel=") {
-            parsed_args.insert("channel", value);
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Tui => "tui",
+            Command::Tail => "tail",
+            Command::Replay => "replay",
+            Command::Record => "record",
         }
+    }
 
-        if input == "--debug" {
-            parsed_args.insert("log_level", "debug");
-        }
+    /// Replay reads a recording and record writes one, both need its path.
+    fn takes_file(&self) -> bool {
+        matches!(self, Command::Replay | Command::Record)
+    }
+}
 
-        if input == "--info" {
-            parsed_args.insert("log_level", "info");
-        }
+/// How an option gets its value.
+enum Value {
+    /// A flag, it always stands for the value given.
+    Fixed(&'static str),
+    /// `--key=value` or `--key value`.
+    Required,
+    /// `--key=value`, or the value given when the option stands alone. One
+    /// of the known values may also follow as the next argument.
+    Optional(&'static str, &'static [&'static str]),
+}
 
-        if input == "--warn" {
-            parsed_args.insert("log_level", "warn");
-        }
+/// Every option there is, the name it is given as, the key it is stored
+/// under and how it gets its value.
+const OPTIONS: [(&str, &str, Value); 20] = [
+    ("help", "help", Value::Fixed("true")),
+    ("version", "version", Value::Fixed("true")),
+    ("config", "config", Value::Required),
+    ("nick", "nick", Value::Required),
+    ("channel", "channel", Value::Required),
+    ("url", "url", Value::Required),
+    ("irc", "irc", Value::Fixed("true")),
+    ("emotes", "emotes", Value::Required),
+    ("highlight", "highlight", Value::Required),
+    ("highlight-regex", "highlight_regex", Value::Required),
+    (
+        "images",
+        "images",
+        Value::Optional("auto", &["auto", "kitty", "iterm", "sixel"]),
+    ),
+    ("log", "logging", Value::Fixed("true")),
+    ("trace", "log_level", Value::Fixed("trace")),
+    ("debug", "log_level", Value::Fixed("debug")),
+    ("info", "log_level", Value::Fixed("info")),
+    ("warn", "log_level", Value::Fixed("warn")),
+    ("error", "log_level", Value::Fixed("error")),
+    (
+        "color",
+        "color",
+        Value::Optional("always", &["auto", "always", "never"]),
+    ),
+    ("format", "format", Value::Required),
+    ("speed", "speed", Value::Required),
+];
+
+/// The parsed command line, options are stored under their key.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Args<'a> {
+    pub command: Command,
+    /// The recording `replay` and `record` work with.
+    pub file: Option<&'a str>,
+    pub options: HashMap<&'static str, &'a str>,
+}
+
+/// Parses the arguments following the program name. An optional command
+/// comes first, options can be given before or after it.
+pub fn parse(args: &[String]) -> Result<Args<'_>, String> {
+    let mut command = None;
+    let mut file = None;
+    let mut options = HashMap::new();
+
+    let mut args = args.iter().peekable();
+    // an option that took no value right before, so that a value it does not
+    // know is not mistaken for a command
+    let mut bare: Option<(&str, &[&str])> = None;
+    while let Some(arg) = args.next() {
+        let name = match arg.strip_prefix("--") {
+            Some(name) => name,
+            None if arg == "-h" => "help",
+            None if arg == "-V" => "version",
+            None if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option `{}`", arg))
+            }
+            None if command.is_none() && file.is_none() && Command::parse(arg).is_some() => {
+                command = Command::parse(arg);
+                bare = None;
+                continue;
+            }
+            None if command.is_some_and(|command| command.takes_file()) && file.is_none() => {
+                file = Some(arg.as_str());
+                bare = None;
+                continue;
+            }
+            None => {
+                return Err(match bare {
+                    Some((name, values)) => unknown_value(arg, name, values),
+                    None if command.is_none() && file.is_none() => {
+                        format!("unknown command `{}`", arg)
+                    }
+                    None => format!("unexpected argument `{}`", arg),
+                })
+            }
+        };
+        bare = None;
+        let (name, inline) = match name.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (name, None),
+        };
+        let (key, value) = OPTIONS
+            .iter()
+            .find(|(option, _, _)| *option == name)
+            .map(|(_, key, value)| (*key, value))
+            .ok_or_else(|| format!("unknown option `--{}`", name))?;
+        let value = match (value, inline) {
+            (Value::Fixed(_), Some(_)) => {
+                return Err(format!("`--{}` does not take a value", name))
+            }
+            (Value::Optional(_, values), None)
+                if args
+                    .peek()
+                    .is_some_and(|next| values.contains(&next.as_str())) =>
+            {
+                args.next().unwrap().as_str()
+            }
+            (Value::Optional(value, values), None) => {
+                bare = Some((name, values));
+                value
+            }
+            (Value::Fixed(value), None) => value,
+            (Value::Optional(_, values), Some(value)) if !values.contains(&value) => {
+                return Err(unknown_value(value, name, values))
+            }
+            (_, Some(value)) => value,
+            (Value::Required, None) => args
+                .next()
+                .map(String::as_str)
+                .filter(|value| !value.starts_with("--"))
+                .ok_or_else(|| format!("`--{}` needs a value", name))?,
+        };
+        options.insert(key, value);
+    }
 
-        if input == "--error" {
-            parsed_args.insert("log_level", "error");
+    let command = command.unwrap_or(Command::Tui);
+    if !options.contains_key("help") {
+        if command.takes_file() && file.is_none() {
+            return Err(format!("`{}` needs a file", command.name()));
         }
+        check_applies(command, &options)?;
+    }
+    Ok(Args {
+        command,
+        file,
+        options,
+    })
+}
+
+fn unknown_value(value: &str, name: &str, values: &[&str]) -> String {
+    format!(
+        "unknown value `{}` for `--{}`, expected one of {}",
+        value,
+        name,
+        values.join(", ")
+    )
+}
 
-        if input == "--log" {
-            parsed_args.insert("logging", "true");
+/// Refuses options the command would ignore. A replay given a format is
+/// printed like tail, without one it is shown like the terminal user
+/// interface.
+fn check_applies(command: Command, options: &HashMap<&'static str, &str>) -> Result<(), String> {
+    let printed = match command {
+        Command::Tail => true,
+        Command::Replay => options.contains_key("format"),
+        Command::Tui | Command::Record => false,
+    };
+    for (option, applies) in [
+        ("format", matches!(command, Command::Tail | Command::Replay)),
+        ("color", printed),
+        ("images", !printed),
+        ("speed", command == Command::Replay),
+    ] {
+        match (options.contains_key(option), applies) {
+            (true, false) if command == Command::Replay => {
+                return Err(format!(
+                    "`--{}` does not apply to `replay` {} `--format`",
+                    option,
+                    if printed { "with" } else { "without" }
+                ))
+            }
+            (true, false) => {
+                return Err(format!(
+                    "`--{}` does not apply to `{}`",
+                    option,
+                    command.name()
+                ))
+            }
+            _ => (),
         }
-    });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn should_read_both_value_forms() {
+        let line = args("--nick=foobar --channel dallas,forsen --log --trace --images");
+        let parsed = parse(&line).unwrap();
+        assert_eq!(parsed.command, Command::Tui);
+        assert_eq!(
+            parsed.options,
+            HashMap::from([
+                ("nick", "foobar"),
+                ("channel", "dallas,forsen"),
+                ("logging", "true"),
+                ("log_level", "trace"),
+                ("images", "auto"),
+            ])
+        );
+        let line = args("tail --debug --color=never");
+        let parsed = parse(&line).unwrap();
+        assert_eq!(parsed.options.len(), 2);
+        assert_eq!(parsed.options["color"], "never");
+        let line = args("tail --color never");
+        let parsed = parse(&line).unwrap();
+        assert_eq!(parsed.command, Command::Tail);
+        assert_eq!(parsed.options["color"], "never");
+        let line = args("--images kitty");
+        let parsed = parse(&line).unwrap();
+        assert_eq!(parsed.options["images"], "kitty");
+        let line = args("--color tail");
+        let parsed = parse(&line).unwrap();
+        assert_eq!(parsed.command, Command::Tail);
+        assert_eq!(parsed.options["color"], "always");
+    }
+
+    #[test]
+    fn should_read_commands() {
+        let line = args("--nick foobar tail --channel dallas");
+        let parsed = parse(&line).unwrap();
+        assert_eq!(parsed.command, Command::Tail);
+        assert_eq!(parsed.options["channel"], "dallas");
+
+        let line = args("replay session.log");
+        let parsed = parse(&line).unwrap();
+        assert_eq!(parsed.command, Command::Replay);
+        assert_eq!(parsed.file, Some("session.log"));
+        assert_eq!(parse(&args("-h")).unwrap().options["help"], "true");
+    }
+
+    #[test]
+    fn should_reject_what_it_does_not_know() {
+        let error = |line: &str| parse(&args(line)).unwrap_err();
+        assert_eq!(error("--nik=foo"), "unknown option `--nik`");
+        assert_eq!(error("-n foo"), "unknown option `-n`");
+        assert_eq!(error("chat"), "unknown command `chat`");
+        assert_eq!(error("tail dallas"), "unexpected argument `dallas`");
+        assert_eq!(error("--nick"), "`--nick` needs a value");
+        assert_eq!(error("--nick --log"), "`--nick` needs a value");
+        assert_eq!(error("--log=yes"), "`--log` does not take a value");
+        assert_eq!(error("record"), "`record` needs a file");
+        assert_eq!(
+            error("--color blue"),
+            "unknown value `blue` for `--color`, expected one of auto, always, never"
+        );
+        assert_eq!(
+            error("--images png"),
+            "unknown value `png` for `--images`, expected one of auto, kitty, iterm, sixel"
+        );
+        assert_eq!(
+            error("tail --color=blue"),
+            "unknown value `blue` for `--color`, expected one of auto, always, never"
+        );
+    }
 
-    parsed_args
+    #[test]
+    fn should_reject_options_the_command_ignores() {
+        let error = |line: &str| parse(&args(line)).unwrap_err();
+        assert_eq!(error("--format=json"), "`--format` does not apply to `tui`");
+        assert_eq!(
+            error("record session.log --color"),
+            "`--color` does not apply to `record`"
+        );
+        assert_eq!(
+            error("tail --speed 2"),
+            "`--speed` does not apply to `tail`"
+        );
+        assert_eq!(
+            error("tail --images"),
+            "`--images` does not apply to `tail`"
+        );
+        assert_eq!(
+            error("replay session.log --color"),
+            "`--color` does not apply to `replay` without `--format`"
+        );
+        assert_eq!(
+            error("replay session.log --format json --images"),
+            "`--images` does not apply to `replay` with `--format`"
+        );
+        assert!(parse(&args("replay session.log --speed 2 --format text --color")).is_ok());
+        assert!(parse(&args("tail --speed 2 --help")).is_ok());
+    }
 }
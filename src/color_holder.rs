@@ -13,6 +13,17 @@ impl ColorCache {
         }
     }
 
+    /// The colour a user picked, or one generated for them.
+    pub fn user_color(&mut self, display_name: &str, color: Option<(u8, u8, u8)>) -> (u8, u8, u8) {
+        color.unwrap_or_else(|| {
+            get_logger().debug(
+                format!("no color information found for user: {display_name}"),
+                type_name::<ColorCache>(),
+            );
+            self.get(display_name)
+        })
+    }
+
     pub fn get(&mut self, username: &str) -> (u8, u8, u8) {
         let log = get_logger();
         log.debug(
@@ -0,0 +1,552 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crossterm::style::{Attribute, Attributes, Color};
+use serde::Deserialize;
+
+use crate::{
+    gui::{
+        badges::{BadgeGlyph, BadgeTheme},
+        buffer::Style,
+        chat_widget::valid_timestamp_format,
+        event_handler::KeyMap,
+        highlight::Highlights,
+    },
+    log::LogLevel,
+    parser::Badges,
+};
+
+/// Settings read from `config.toml`, anything left out keeps its default
+/// and flags given on the command line win over the file.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub nick: Option<String>,
+    pub channels: Vec<String>,
+    pub url: Option<String>,
+    pub irc: bool,
+    pub token: TokenSource,
+    pub log: LogConfig,
+    pub timestamp_format: Option<String>,
+    pub theme: ThemeConfig,
+    /// Action name to the key, or keys, that trigger it.
+    pub keys: HashMap<String, Keys>,
+}
+
+/// Where the OAuth token is read from.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "TokenTable")]
+pub enum TokenSource {
+    /// An environment variable.
+    Env(String),
+    /// A file holding nothing but the token.
+    File(PathBuf),
+    /// A shell command printing the token, for password managers.
+    Command(String),
+}
+
+impl Default for TokenSource {
+    fn default() -> Self {
+        TokenSource::Env("TWITCH_BOT_TOKEN".to_string())
+    }
+}
+
+/// The `[token]` table as written, toml can not read a table of its own
+/// into an enum.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TokenTable {
+    env: Option<String>,
+    file: Option<PathBuf>,
+    command: Option<String>,
+}
+
+impl TryFrom<TokenTable> for TokenSource {
+    type Error = String;
+
+    fn try_from(table: TokenTable) -> Result<Self, Self::Error> {
+        match (table.env, table.file, table.command) {
+            (Some(var), None, None) => Ok(TokenSource::Env(var)),
+            (None, Some(path), None) => Ok(TokenSource::File(path)),
+            (None, None, Some(command)) => Ok(TokenSource::Command(command)),
+            _ => Err("token: expected exactly one of `env`, `file` or `command`".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub enabled: bool,
+    pub level: Option<String>,
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// A file of emote glyphs, like the one `--emotes` takes.
+    pub emotes: Option<PathBuf>,
+    /// `auto`, `kitty`, `iterm` or `sixel`, like `--images`.
+    pub images: Option<String>,
+    pub badges: HashMap<String, BadgeSetting>,
+    /// Whether the months subscribed are shown behind the subscriber and
+    /// founder badges.
+    pub tenure: Option<bool>,
+    pub highlights: HighlightConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BadgeSetting {
+    /// `false` hides the badge.
+    Shown(bool),
+    Glyph(GlyphConfig),
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GlyphConfig {
+    pub glyph: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightConfig {
+    pub mention: Option<HighlightSetting>,
+    pub broadcaster_reply: Option<HighlightSetting>,
+    pub first_msg: Option<HighlightSetting>,
+    pub rules: Vec<HighlightRule>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum HighlightSetting {
+    /// `false` turns the highlight off.
+    Enabled(bool),
+    Style(StyleConfig),
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleConfig {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+/// A keyword or a regex, with the style of the highlight. Without colours
+/// the default rule style is used.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightRule {
+    pub keyword: Option<String>,
+    pub regex: Option<String>,
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/twitch-chat/config.toml`, falling back to
+    /// `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join("twitch-chat").join("config.toml"))
+    }
+
+    /// Reads the file at `path`. A missing file is only an error when
+    /// `required`, otherwise the defaults are used.
+    pub fn load(path: &Path, required: bool) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default())
+            }
+            Err(err) => return Err(format!("could not read {}: {}", path.display(), err)),
+        };
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Parses the file and checks the values serde can not, so that a bad
+    /// entry is reported before anything starts.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(text).map_err(|err| err.to_string())?;
+        config.log_level()?;
+        config.badge_theme()?;
+        config.highlights()?;
+        config.key_map()?;
+        if let Some(format) = &config.timestamp_format {
+            if !valid_timestamp_format(format) {
+                return Err(format!("timestamp_format: invalid format `{}`", format));
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn log_level(&self) -> Result<Option<LogLevel>, String> {
+        self.log
+            .level
+            .as_deref()
+            .map(|level| {
+                parse_log_level(level).ok_or_else(|| {
+                    format!(
+                        "log.level: unknown level `{}`, expected one of trace, debug, info, warn, error",
+                        level
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    pub fn token(&self) -> Result<String, String> {
+        let token = match &self.token {
+            TokenSource::Env(var) => {
+                env::var(var).map_err(|_| format!("{} env variable not set", var))?
+            }
+            TokenSource::File(path) => fs::read_to_string(path)
+                .map_err(|err| format!("could not read token from {}: {}", path.display(), err))?,
+            TokenSource::Command(command) => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .map_err(|err| format!("could not run `{}`: {}", command, err))?;
+                if !output.status.success() {
+                    return Err(format!("`{}` failed with {}", command, output.status));
+                }
+                String::from_utf8_lossy(&output.stdout).to_string()
+            }
+        };
+        let token = token.trim();
+        if token.is_empty() {
+            return Err("the token is empty".to_string());
+        }
+        Ok(token.to_string())
+    }
+
+    pub fn badge_theme(&self) -> Result<BadgeTheme, String> {
+        let mut theme = BadgeTheme::default();
+        if let Some(tenure) = self.theme.tenure {
+            theme.tenure = tenure;
+        }
+        for (name, setting) in &self.theme.badges {
+            let badge = Badges::from(name.as_str());
+            if badge == Badges::Unimplemented {
+                return Err(format!("theme.badges: unknown badge `{}`", name));
+            }
+            let glyph = match setting {
+                BadgeSetting::Shown(false) => None,
+                // keeps the default glyph
+                BadgeSetting::Shown(true) => continue,
+                BadgeSetting::Glyph(GlyphConfig { glyph, color }) => {
+                    let color = color
+                        .as_deref()
+                        .map(parse_color)
+                        .transpose()
+                        .map_err(|err| format!("theme.badges.{}: {}", name, err))?;
+                    Some(BadgeGlyph::new(glyph, color))
+                }
+            };
+            theme.set(badge, glyph);
+        }
+        Ok(theme)
+    }
+
+    pub fn highlights(&self) -> Result<Highlights, String> {
+        let config = &self.theme.highlights;
+        let mut highlights = Highlights::default();
+        let builtin = [
+            ("mention", &config.mention, &mut highlights.mention),
+            (
+                "broadcaster_reply",
+                &config.broadcaster_reply,
+                &mut highlights.broadcaster_reply,
+            ),
+            ("first_msg", &config.first_msg, &mut highlights.first_msg),
+        ];
+        for (name, setting, style) in builtin {
+            match setting {
+                Some(HighlightSetting::Enabled(false)) => *style = None,
+                Some(HighlightSetting::Enabled(true)) | None => (),
+                Some(HighlightSetting::Style(config)) => {
+                    *style = Some(
+                        to_style(
+                            &config.fg,
+                            &config.bg,
+                            [config.bold, config.italic, config.underline],
+                        )
+                        .map_err(|err| format!("theme.highlights.{}: {}", name, err))?,
+                    );
+                }
+            }
+        }
+
+        for (i, rule) in config.rules.iter().enumerate() {
+            let context = |err: String| format!("theme.highlights.rules[{}]: {}", i, err);
+            let attributes = [rule.bold, rule.italic, rule.underline];
+            let style = match (&rule.fg, &rule.bg) {
+                (None, None) => Highlights::rule_style().attributes(to_attributes(attributes)),
+                _ => to_style(&rule.fg, &rule.bg, attributes).map_err(context)?,
+            };
+            match (&rule.keyword, &rule.regex) {
+                (Some(keyword), None) => highlights.add_keyword(keyword, style),
+                (None, Some(regex)) => highlights.add_regex(regex, style).map_err(context)?,
+                _ => return Err(context("expected either `keyword` or `regex`".to_string())),
+            }
+        }
+        Ok(highlights)
+    }
+
+    pub fn key_map(&self) -> Result<KeyMap, String> {
+        let mut keys = KeyMap::default();
+        // the configured actions give up their default keys first, so keys
+        // can be swapped between them
+        for action in self.keys.keys() {
+            keys.unbind(action)
+                .map_err(|err| format!("keys.{}: {}", action, err))?;
+        }
+        for (action, bound) in &self.keys {
+            let bound: Vec<&str> = match bound {
+                Keys::One(key) => vec![key],
+                Keys::Many(list) => list.iter().map(String::as_str).collect(),
+            };
+            keys.bind(action, &bound)
+                .map_err(|err| format!("keys.{}: {}", action, err))?;
+        }
+        Ok(keys)
+    }
+}
+
+pub fn parse_log_level(level: &str) -> Option<LogLevel> {
+    match level {
+        "trace" => Some(LogLevel::Trace),
+        "debug" => Some(LogLevel::Debug),
+        "info" => Some(LogLevel::Info),
+        "warn" => Some(LogLevel::Warn),
+        "error" => Some(LogLevel::Error),
+        _ => None,
+    }
+}
+
+/// A colour by name, like `dark_red`, or as `#rrggbb`.
+fn parse_color(color: &str) -> Result<Color, String> {
+    let unknown = || format!("unknown colour `{}`", color);
+    match color.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.is_ascii() => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| unknown());
+            Ok(Color::Rgb {
+                r: channel(0)?,
+                g: channel(2)?,
+                b: channel(4)?,
+            })
+        }
+        Some(_) => Err(unknown()),
+        None => Color::try_from(color).map_err(|_| unknown()),
+    }
+}
+
+fn to_style(
+    fg: &Option<String>,
+    bg: &Option<String>,
+    attributes: [bool; 3],
+) -> Result<Style, String> {
+    let fg = fg.as_deref().map(parse_color).transpose()?;
+    let bg = bg.as_deref().map(parse_color).transpose()?;
+    Ok(Style::new(fg, bg).attributes(to_attributes(attributes)))
+}
+
+fn to_attributes([bold, italic, underline]: [bool; 3]) -> Attributes {
+    let mut attributes = Attributes::default();
+    for (set, attribute) in [
+        (bold, Attribute::Bold),
+        (italic, Attribute::Italic),
+        (underline, Attribute::Underlined),
+    ] {
+        if set {
+            attributes.set(attribute);
+        }
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{chat_message::ChatMessage, command::Command as IrcCommand};
+
+    const FULL: &str = r##"
+nick = "foobar"
+channels = ["dallas", "#Forsen"]
+irc = true
+timestamp_format = "%H:%M"
+
+[token]
+command = "echo '  oauth:abc  '"
+
+[log]
+enabled = true
+level = "debug"
+file = "/tmp/chat.log"
+
+[theme]
+tenure = false
+
+[theme.badges]
+moderator = { glyph = "M", color = "#00ff00" }
+vip = false
+
+[theme.highlights]
+first_msg = false
+mention = { fg = "white", bg = "dark_magenta", bold = true }
+
+[[theme.highlights.rules]]
+keyword = "rust"
+
+[[theme.highlights.rules]]
+regex = "^!\\w+"
+fg = "black"
+bg = "#ffaa00"
+
+[keys]
+quit = "Q"
+scroll_up = ["k", "up"]
+"##;
+
+    #[test]
+    fn should_read_every_section() {
+        let config = Config::parse(FULL).unwrap();
+        assert_eq!(config.nick.as_deref(), Some("foobar"));
+        assert_eq!(config.channels, vec!["dallas", "#Forsen"]);
+        assert!(config.irc);
+        assert_eq!(config.token().unwrap(), "oauth:abc");
+        assert_eq!(config.log_level().unwrap(), Some(LogLevel::Debug));
+        assert_eq!(config.log.file.as_deref(), Some("/tmp/chat.log"));
+
+        let badges = config.badge_theme().unwrap();
+        assert!(!badges.tenure);
+        assert_eq!(badges.glyph(Badges::Vip), None);
+        assert_eq!(
+            badges.glyph(Badges::Moderator),
+            Some(&BadgeGlyph::new(
+                "M",
+                Some(Color::Rgb { r: 0, g: 255, b: 0 })
+            ))
+        );
+
+        let highlights = config.highlights().unwrap();
+        assert_eq!(highlights.first_msg, None);
+        assert_eq!(
+            highlights.mention,
+            Some(
+                Style::new(Some(Color::White), Some(Color::DarkMagenta))
+                    .attributes(Attribute::Bold)
+            )
+        );
+        assert_eq!(
+            highlights.broadcaster_reply,
+            Highlights::default().broadcaster_reply
+        );
+
+        let mut keys = KeyMap::default();
+        keys.bind("quit", &["Q"]).unwrap();
+        keys.bind("scroll_up", &["k", "up"]).unwrap();
+        assert_eq!(config.key_map().unwrap(), keys);
+    }
+
+    #[test]
+    fn should_default_to_token_from_env() {
+        let config = Config::parse("nick = \"foobar\"").unwrap();
+        assert_eq!(
+            config.token,
+            TokenSource::Env("TWITCH_BOT_TOKEN".to_string())
+        );
+        assert_eq!(config.log, LogConfig::default());
+        assert_eq!(config.key_map().unwrap(), KeyMap::default());
+    }
+
+    #[test]
+    fn should_keep_attributes_of_rules_without_colours() {
+        let config = Config::parse(
+            "[[theme.highlights.rules]]\nkeyword = \"rust\"\nbold = true\nunderline = true",
+        )
+        .unwrap();
+        let highlights = config.highlights().unwrap();
+        let line = "@id=1;room-id=1;tmi-sent-ts=1500000000;user-id=10 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :rust is nice";
+        let message = match ChatMessage::parse(line).unwrap().command {
+            IrcCommand::PrivMsg(message) => message,
+            command => panic!("expected PRIVMSG, got {:?}", command),
+        };
+        assert_eq!(
+            highlights.style(&message),
+            Some(
+                Highlights::rule_style()
+                    .attributes([Attribute::Bold, Attribute::Underlined].as_slice())
+            )
+        );
+    }
+
+    #[test]
+    fn should_explain_invalid_entries() {
+        let error = |text: &str| Config::parse(text).unwrap_err();
+        assert!(error("nik = \"foo\"").starts_with("unknown field `nik`"));
+        assert!(error("channels = \"dallas\"").contains("invalid type: string"));
+        assert_eq!(
+            error("[log]\nlevel = \"loud\""),
+            "log.level: unknown level `loud`, expected one of trace, debug, info, warn, error"
+        );
+        assert_eq!(
+            error("[theme.badges]\nknight = false"),
+            "theme.badges: unknown badge `knight`"
+        );
+        assert_eq!(
+            error("[theme.badges]\nvip = { glyph = \"V\", color = \"#zzzzzz\" }"),
+            "theme.badges.vip: unknown colour `#zzzzzz`"
+        );
+        assert_eq!(
+            error("[theme.badges]\nvip = { glyph = \"V\", color = \"#\u{20AC}\u{20AC}\" }"),
+            "theme.badges.vip: unknown colour `#\u{20AC}\u{20AC}`"
+        );
+        assert!(
+            error("[theme.badges]\nvip = { glyph = \"V\", colour = \"red\" }")
+                .starts_with("data did not match any variant")
+        );
+        assert_eq!(
+            error("[[theme.highlights.rules]]\nfg = \"red\""),
+            "theme.highlights.rules[0]: expected either `keyword` or `regex`"
+        );
+        assert_eq!(
+            error("[keys]\nquit = \"hyper+q\""),
+            "keys.quit: invalid key `hyper+q`"
+        );
+        assert_eq!(
+            error("[keys]\nscroll_up = \"q\""),
+            "keys.scroll_up: key `q` is already bound to `quit`"
+        );
+        assert!(error("[token]\nenv = \"A\"\nfile = \"b\"")
+            .starts_with("token: expected exactly one of `env`, `file` or `command`"));
+        assert_eq!(
+            error("timestamp_format = \"%Q\""),
+            "timestamp_format: invalid format `%Q`"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crossbeam::channel::Sender;
+
+/// PNG files start with these bytes, anything else is not stored.
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Emote images kept on disk as `<emote id>.png`, once an emote has been
+/// fetched it is available without a connection. Fetching is only possible
+/// with the tls feature, without it the cache has to be filled by hand.
+#[derive(Debug)]
+pub struct EmoteCache {
+    dir: PathBuf,
+    requested: Arc<Mutex<HashSet<String>>>,
+    fetcher: Option<Sender<String>>,
+}
+
+impl EmoteCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            requested: Arc::new(Mutex::new(HashSet::new())),
+            fetcher: None,
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/twitch-chat/emotes`, falling back to `~/.cache`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(base.join("twitch-chat").join("emotes"))
+    }
+
+    /// Emote ids end up in file names so only the characters Twitch uses
+    /// for them are accepted.
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        valid.then(|| self.dir.join(format!("{}.png", id)))
+    }
+
+    pub fn get(&self, id: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(id)?)
+            .ok()
+            .filter(|png| png.starts_with(PNG_MAGIC))
+    }
+
+    /// Writes the image next to its final name first, so that a half
+    /// written file is never picked up.
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    pub fn store(&self, id: &str, png: &[u8]) -> Result<(), String> {
+        let path = self
+            .path(id)
+            .ok_or_else(|| format!("invalid emote id: {}", id))?;
+        if !png.starts_with(PNG_MAGIC) {
+            return Err(format!("emote {} is not a png", id));
+        }
+        fs::create_dir_all(&self.dir).map_err(|err| err.to_string())?;
+        let partial = path.with_extension("part");
+        fs::write(&partial, png).map_err(|err| err.to_string())?;
+        fs::rename(&partial, &path).map_err(|err| err.to_string())
+    }
+
+    /// Asks for a missing emote to be fetched in the background, every emote
+    /// is only asked for once.
+    pub fn request(&self, id: &str) {
+        let fetcher = match &self.fetcher {
+            Some(fetcher) => fetcher,
+            None => return,
+        };
+        if self.path(id).is_none() || !self.requested.lock().unwrap().insert(id.to_string()) {
+            return;
+        }
+        fetcher.send(id.to_string()).unwrap_or(());
+    }
+
+    /// Starts a thread that downloads requested emotes from the Twitch CDN
+    /// into the cache. It ends once the cache is dropped.
+    #[cfg(feature = "tls")]
+    pub fn with_fetcher(mut self) -> Self {
+        use crate::log::get_logger;
+        use std::any::type_name;
+
+        let (sender, receiver) = crossbeam::channel::unbounded::<String>();
+        let cache = Self::new(self.dir.clone());
+        let spawned = std::thread::Builder::new()
+            .name("emote-fetcher".to_string())
+            .spawn(move || {
+                let log = get_logger();
+                for id in receiver {
+                    match fetch::emote(&id).and_then(|png| Ok(cache.store(&id, &png)?)) {
+                        Ok(()) => log.debug(format!("Fetched emote: {}", id), type_name::<Self>()),
+                        Err(err) => log.warn(
+                            format!("Could not fetch emote {}: {}", id, err),
+                            type_name::<Self>(),
+                        ),
+                    }
+                }
+            });
+        match spawned {
+            Ok(_) => self.fetcher = Some(sender),
+            Err(err) => get_logger().error(
+                format!("Could not start emote fetcher: {}", err),
+                type_name::<Self>(),
+            ),
+        }
+        self
+    }
+}
+
+#[cfg(feature = "tls")]
+mod fetch {
+    use std::{
+        error::Error,
+        io::{ErrorKind, Read, Write},
+        net::TcpStream,
+        time::Duration,
+    };
+
+    use crate::transport::tls;
+
+    const CDN: &str = "static-cdn.jtvnw.net";
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Downloads the smallest static image of an emote. HTTP/1.0 keeps the
+    /// response simple, the body is whatever comes before the server hangs
+    /// up.
+    pub fn emote(id: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let stream = TcpStream::connect((CDN, 443))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut stream = tls::connect(stream, CDN)?;
+        write!(
+            stream,
+            "GET /emoticons/v2/{}/static/dark/1.0 HTTP/1.0\r\nHost: {}\r\nUser-Agent: twitch-chat\r\n\r\n",
+            id, CDN
+        )?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        match stream.read_to_end(&mut response) {
+            Ok(_) => (),
+            // not every server says goodbye before closing the connection
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof && !response.is_empty() => (),
+            Err(err) => return Err(err.into()),
+        }
+        body(&response)
+    }
+
+    /// The body of a response, as long as the status is 200.
+    pub fn body(response: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or("incomplete response")?;
+        let head = String::from_utf8_lossy(&response[..end]);
+        let status = head.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(format!("unexpected response: {}", status).into());
+        }
+        Ok(response[end + 4..].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixtures() -> EmoteCache {
+        EmoteCache::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/emotes"))
+    }
+
+    #[test]
+    fn should_read_cached_emotes() {
+        let cache = fixtures();
+        assert!(cache.get("25").unwrap().starts_with(PNG_MAGIC));
+        assert_eq!(cache.get("1902"), None);
+        assert_eq!(cache.get("../emotes/25"), None);
+    }
+
+    #[test]
+    fn should_store_only_pngs() {
+        let dir = env::temp_dir().join(format!("twitch-chat-cache-{}", std::process::id()));
+        let cache = EmoteCache::new(&dir);
+        let png = fixtures().get("25").unwrap();
+        cache.store("emotesv2_abc", &png).unwrap();
+        assert_eq!(cache.get("emotesv2_abc"), Some(png));
+        assert!(cache.store("1", b"<html>").is_err());
+        assert_eq!(cache.get("1"), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn should_take_body_of_successful_response() {
+        assert_eq!(
+            fetch::body(b"HTTP/1.0 200 OK\r\nContent-Type: image/png\r\n\r\n\x89PNG").unwrap(),
+            b"\x89PNG"
+        );
+        assert_eq!(
+            fetch::body(b"HTTP/1.0 404 Not Found\r\n\r\n")
+                .err()
+                .unwrap()
+                .to_string(),
+            "unexpected response: HTTP/1.0 404 Not Found"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crossterm::style::Color;
+
+use super::buffer::Style;
+use crate::parser::Badges;
+
+/// What is drawn in front of a name for a badge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadgeGlyph {
+    pub glyph: String,
+    pub color: Option<Color>,
+}
+
+impl BadgeGlyph {
+    pub fn new(glyph: impl Into<String>, color: Option<Color>) -> Self {
+        Self {
+            glyph: glyph.into(),
+            color,
+        }
+    }
+}
+
+/// Maps badges to glyphs, badges without a glyph are not shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadgeTheme {
+    glyphs: HashMap<Badges, BadgeGlyph>,
+    /// Puts the months subscribed behind the subscriber and founder glyphs.
+    pub tenure: bool,
+}
+
+impl Default for BadgeTheme {
+    fn default() -> Self {
+        let rgb = |r, g, b| Some(Color::Rgb { r, g, b });
+        let glyphs = [
+            (Badges::Broadcaster, "\u{25C9}", rgb(233, 25, 22)),
+            (Badges::Moderator, "\u{2694}", rgb(0, 173, 3)),
+            (Badges::Vip, "\u{25C6}", rgb(224, 5, 185)),
+            (Badges::Founder, "\u{2726}", rgb(255, 196, 0)),
+            (Badges::Subscriber, "\u{2605}", rgb(130, 5, 180)),
+            (Badges::Staff, "\u{2692}", rgb(145, 70, 255)),
+            (Badges::Admin, "\u{2692}", rgb(250, 175, 25)),
+            (Badges::GlobalMod, "\u{2694}", rgb(0, 100, 0)),
+            (Badges::Partner, "\u{2713}", rgb(145, 70, 255)),
+            (Badges::Artist, "\u{270E}", rgb(30, 105, 255)),
+            (Badges::SubGifter, "\u{2740}", rgb(130, 5, 180)),
+            (Badges::Bits, "\u{2666}", rgb(150, 150, 255)),
+            (Badges::Premium, "\u{265B}", rgb(0, 160, 214)),
+        ];
+        Self {
+            glyphs: glyphs
+                .into_iter()
+                .map(|(badge, glyph, color)| (badge, BadgeGlyph::new(glyph, color)))
+                .collect(),
+            tenure: true,
+        }
+    }
+}
+
+impl BadgeTheme {
+    /// A theme that shows no badges at all.
+    #[cfg(test)]
+    pub fn empty() -> Self {
+        Self {
+            glyphs: HashMap::new(),
+            tenure: false,
+        }
+    }
+
+    /// Changes the glyph for a badge, `None` hides the badge.
+    pub fn set(&mut self, badge: Badges, glyph: Option<BadgeGlyph>) {
+        match glyph {
+            Some(glyph) => self.glyphs.insert(badge, glyph),
+            None => self.glyphs.remove(&badge),
+        };
+    }
+
+    #[cfg(test)]
+    pub fn glyph(&self, badge: Badges) -> Option<&BadgeGlyph> {
+        self.glyphs.get(&badge)
+    }
+
+    /// The styled glyphs for the badges of a user, in the order Twitch sent
+    /// them. Tenure comes from badge-info, which holds the exact months
+    /// where the badge version is only the tier.
+    pub fn render(
+        &self,
+        badges: &[(Badges, &str)],
+        badge_info: &[(Badges, &str)],
+    ) -> Vec<(String, Style)> {
+        badges
+            .iter()
+            .filter_map(|(badge, _)| {
+                let glyph = self.glyphs.get(badge)?;
+                let mut text = glyph.glyph.clone();
+                if self.tenure && matches!(badge, Badges::Subscriber | Badges::Founder) {
+                    let months = badge_info.iter().find(|(info, _)| info == badge);
+                    if let Some((_, months)) = months {
+                        text.push_str(months);
+                    }
+                }
+                Some((text, Style::fg(glyph.color)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(rendered: Vec<(String, Style)>) -> String {
+        rendered.into_iter().map(|(text, _)| text).collect()
+    }
+
+    #[test]
+    fn should_render_known_badges_with_tenure() {
+        let theme = BadgeTheme::default();
+        let badges = [
+            (Badges::Moderator, "1"),
+            (Badges::Subscriber, "3012"),
+            (Badges::NoAudio, "1"),
+        ];
+        let info = [(Badges::Subscriber, "14")];
+        assert_eq!(text(theme.render(&badges, &info)), "\u{2694}\u{2605}14");
+        assert_eq!(
+            theme.render(&badges[..1], &[])[0].1,
+            Style::fg(Some(Color::Rgb { r: 0, g: 173, b: 3 }))
+        );
+    }
+
+    #[test]
+    fn should_use_configured_glyphs() {
+        let mut theme = BadgeTheme {
+            tenure: false,
+            ..Default::default()
+        };
+        theme.set(Badges::Moderator, None);
+        theme.set(Badges::NoAudio, Some(BadgeGlyph::new("x", None)));
+        let badges = [
+            (Badges::Moderator, "1"),
+            (Badges::Subscriber, "3012"),
+            (Badges::NoAudio, "1"),
+        ];
+        let info = [(Badges::Subscriber, "14")];
+        assert_eq!(text(theme.render(&badges, &info)), "\u{2605}x");
+    }
+}
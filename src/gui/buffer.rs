@@ -1,6 +1,6 @@
 use std::ops::Deref;
 
-use crossterm::style::Color;
+use crossterm::style::{Attributes, Color};
 use unicode_width::UnicodeWidthChar;
 
 use super::{Pos, Size};
@@ -9,15 +9,25 @@ use super::{Pos, Size};
 pub struct Style {
     pub fg: Option<Color>,
     pub bg: Option<Color>,
+    pub attributes: Attributes,
 }
 
 impl Style {
     pub fn new(fg: Option<Color>, bg: Option<Color>) -> Self {
-        Self { fg, bg }
+        Self {
+            fg,
+            bg,
+            attributes: Attributes::default(),
+        }
     }
 
     pub fn fg(fg: Option<Color>) -> Self {
-        Self { fg, bg: None }
+        Self::new(fg, None)
+    }
+
+    pub fn attributes(mut self, attributes: impl Into<Attributes>) -> Self {
+        self.attributes = attributes.into();
+        self
     }
 
     pub fn none() -> Style {
@@ -78,13 +88,18 @@ impl Buffer {
     pub fn new(size: impl Into<Size>) -> Self {
         let size = size.into();
         Self {
-            inner: vec![Cell::empty(); (size.width * size.height) as usize],
+            inner: vec![Cell::empty(); size.width as usize * size.height as usize],
             size,
         }
     }
 
+    /// Computed in `usize`, large terminals have more cells than `u16` holds.
+    fn index(size: Size, x: u16, y: u16) -> usize {
+        y as usize * size.width as usize + x as usize
+    }
+
     pub fn put(&mut self, cell: Cell, pos: Pos) {
-        let index = pos.y * self.size.width + pos.x;
+        let index = Self::index(self.size, pos.x, pos.y);
 
         if let CellState::Occupied(c) = cell.cell_state {
             if pos.x < self.size.width {
@@ -94,14 +109,28 @@ impl Buffer {
             }
         }
 
-        self.inner[index as usize] = cell;
+        self.inner[index] = cell;
     }
 
     pub fn empty(&mut self, pos: Pos) {
-        let index = pos.y * self.size.width + pos.x;
-        self.inner[index as usize] = Cell::empty();
+        let index = Self::index(self.size, pos.x, pos.y);
+        self.inner[index] = Cell::empty();
+    }
+
+    /// Changes the size of the buffer, keeping the cells that still fit.
+    pub fn resize(&mut self, size: impl Into<Size>) {
+        let size = size.into();
+        let mut inner = vec![Cell::empty(); size.width as usize * size.height as usize];
+        for y in 0..self.size.height.min(size.height) {
+            for x in 0..self.size.width.min(size.width) {
+                inner[Self::index(size, x, y)] = self.inner[Self::index(self.size, x, y)];
+            }
+        }
+        self.inner = inner;
+        self.size = size;
     }
 
+    #[cfg(test)]
     pub fn lines(&self) -> impl Iterator<Item = &[Cell]> {
         self.inner.chunks(self.size.width as usize)
     }
@@ -160,6 +189,32 @@ mod test {
         );
     }
 
+    #[test]
+    pub fn should_keep_overlapping_cells_on_resize() {
+        let mut buffer = Buffer::new(Size::new(2, 2));
+        let cell = Cell::new('a', Style::none());
+        buffer.put(cell, Pos::new(1, 0));
+        buffer.put(cell, Pos::new(0, 1));
+
+        buffer.resize(Size::new(3, 1));
+        assert_eq!(buffer.size, Size::new(3, 1));
+        assert_eq!(buffer.inner, vec![Cell::empty(), cell, Cell::empty()]);
+    }
+
+    #[test]
+    pub fn should_resize_past_u16_cells() {
+        let mut buffer = Buffer::new(Size::new(2, 2));
+        let cell = Cell::new('a', Style::none());
+        buffer.put(cell, Pos::new(1, 1));
+
+        let size = Size::new(300, 220);
+        buffer.resize(size);
+        assert_eq!(buffer.inner.len(), 300 * 220);
+        buffer.put(cell, Pos::new(299, 219));
+        assert_eq!(buffer.inner[301], cell);
+        assert_eq!(buffer.inner[300 * 220 - 1], cell);
+    }
+
     #[test]
     pub fn should_return_lines() {
         let buffer = Buffer::new(Size::new(5, 5));
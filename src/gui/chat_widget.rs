@@ -1,14 +1,65 @@
-use std::{any::type_name, io::Write};
+use std::{any::type_name, collections::VecDeque, io::Write, mem, ops::Range};
 
-use chrono::Local;
-use crossterm::style::Color;
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, Utc,
+};
+use crossterm::style::{Attribute, Color};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{
-    color_holder::ColorCache, log::get_logger, parser::chat_message::ChatMessage,
+    color_holder::ColorCache,
+    log::get_logger,
+    parser::{
+        chat_message::ChatMessage,
+        command::{ClearChat, ClearMsg, Command, PrivMsg, UserNotice},
+        user_notice::{SubPlan, UserNoticeEvent},
+    },
     string_padder::StringPadder,
 };
 
-use super::{buffer::Style, screen::Screen, window::Window, Pos, Size};
+use super::{
+    badges::BadgeTheme,
+    buffer::Style,
+    emotes::{segment, EmoteMap},
+    highlight::{self, Highlights},
+    screen::Screen,
+    window::Window,
+    Pos, Size,
+};
+
+const MAX_RECORDS: usize = 1000;
+
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%H:%M:%S";
+
+/// A single row of the chat, made up of differently styled pieces of text.
+pub type Line = Vec<(String, Style)>;
+
+/// A received message, we keep the raw line around so that it can be laid
+/// out again whenever something about it changes.
+struct Record {
+    /// The lines it was last laid out as, and the layout generation they
+    /// belong to.
+    laid_out: Option<(u64, Vec<Line>)>,
+    /// The IRC line, or the text of a line of our own.
+    line: String,
+    /// Whether the line comes from us rather than the server, like the
+    /// connection state.
+    system: bool,
+    channel: Option<String>,
+    id: Option<String>,
+    user_id: Option<u32>,
+    login: Option<String>,
+    /// Id of the message this one replies to.
+    parent_id: Option<String>,
+    deleted: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeletedMode {
+    Hide,
+    Show,
+}
 
 pub struct ChatWidget<'a> {
     window: &'a mut Window,
@@ -16,6 +67,19 @@ pub struct ChatWidget<'a> {
     pos: Pos,
     padder: StringPadder,
     color_cache: ColorCache,
+    records: VecDeque<Record>,
+    deleted_mode: DeletedMode,
+    scroll: usize,
+    unseen: usize,
+    channels: Vec<String>,
+    view: Option<String>,
+    badges: BadgeTheme,
+    emotes: EmoteMap,
+    highlights: Highlights,
+    timestamp_format: String,
+    timestamp_width: usize,
+    /// Goes up whenever the records would be laid out differently.
+    generation: u64,
 }
 
 impl<'a> ChatWidget<'a> {
@@ -26,40 +90,602 @@ impl<'a> ChatWidget<'a> {
             size,
             padder: StringPadder::new(),
             color_cache: ColorCache::new(),
+            records: VecDeque::new(),
+            deleted_mode: DeletedMode::Show,
+            scroll: 0,
+            unseen: 0,
+            channels: Vec::new(),
+            view: None,
+            badges: BadgeTheme::default(),
+            emotes: EmoteMap::default(),
+            highlights: Highlights::default(),
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+            timestamp_width: 8,
+            generation: 0,
+        }
+    }
+
+    /// Changes how the time a message was sent is shown, the format has to
+    /// be valid, see `valid_timestamp_format`.
+    pub fn set_timestamp_format(&mut self, format: &str) {
+        self.timestamp_format = format.to_string();
+        self.timestamp_width = Local::now().format(format).to_string().width();
+        self.generation += 1;
+    }
+
+    /// Changes which messages stand out.
+    pub fn set_highlights(&mut self, highlights: Highlights) {
+        self.highlights = highlights;
+        self.generation += 1;
+    }
+
+    /// Changes the glyphs emotes are shown as.
+    pub fn set_emotes(&mut self, emotes: EmoteMap) {
+        self.emotes = emotes;
+        self.generation += 1;
+    }
+
+    /// Changes the glyphs shown in front of names.
+    pub fn set_badge_theme(&mut self, badges: BadgeTheme) {
+        self.badges = badges;
+        self.generation += 1;
+    }
+
+    /// The channels that are joined, with more than one of them a channel
+    /// column is shown and the views can be switched between.
+    pub fn set_channels(&mut self, channels: Vec<String>) {
+        self.channels = channels;
+        self.generation += 1;
+    }
+
+    /// The channel that is shown on its own, `None` for the merged view.
+    pub fn view(&self) -> Option<&str> {
+        self.view.as_deref()
+    }
+
+    /// Cycles through the merged view followed by every channel.
+    pub fn next_view(&mut self, screen: &mut Screen<impl Write>) {
+        let next = match self.view_index() {
+            Some(i) if i + 1 < self.channels.len() => Some(self.channels[i + 1].clone()),
+            Some(_) => None,
+            None => self.channels.first().cloned(),
+        };
+        self.set_view(screen, next);
+    }
+
+    pub fn prev_view(&mut self, screen: &mut Screen<impl Write>) {
+        let prev = match self.view_index() {
+            Some(0) => None,
+            Some(i) => Some(self.channels[i - 1].clone()),
+            None => self.channels.last().cloned(),
+        };
+        self.set_view(screen, prev);
+    }
+
+    fn view_index(&self) -> Option<usize> {
+        let view = self.view.as_ref()?;
+        self.channels.iter().position(|channel| channel == view)
+    }
+
+    fn set_view(&mut self, screen: &mut Screen<impl Write>, view: Option<String>) {
+        if self.channels.len() < 2 || view == self.view {
+            return;
+        }
+        self.view = view;
+        self.generation += 1;
+        self.scroll = 0;
+        self.unseen = 0;
+        self.redraw(screen);
+    }
+
+    /// Prints a PRIVMSG, USERNOTICE or NOTICE line and remembers it so it can
+    /// be redacted later on, anything else is ignored.
+    pub fn print(&mut self, screen: &mut Screen<impl Write>, line: &str) {
+        let message = match ChatMessage::parse(line) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+        let channel = message.destination().map(str::to_string);
+        let record = match message {
+            ChatMessage {
+                command: Command::PrivMsg(message),
+                ..
+            } => Record {
+                laid_out: None,
+                line: line.to_string(),
+                system: false,
+                channel,
+                id: Some(message.meta_data.id.to_string()),
+                user_id: Some(message.meta_data.user_id),
+                login: message.login.map(str::to_string),
+                parent_id: message.meta_data.reply.msg_id.map(str::to_string),
+                deleted: false,
+            },
+            ChatMessage {
+                command: Command::UserNotice(notice),
+                ..
+            } => Record {
+                laid_out: None,
+                line: line.to_string(),
+                system: false,
+                channel,
+                id: notice.id.map(str::to_string),
+                user_id: notice.user_id,
+                login: notice.login.map(str::to_string),
+                parent_id: None,
+                deleted: false,
+            },
+            ChatMessage {
+                command: Command::Notice(_),
+                ..
+            } => Record {
+                laid_out: None,
+                line: line.to_string(),
+                system: false,
+                channel,
+                id: None,
+                user_id: None,
+                login: None,
+                parent_id: None,
+                deleted: false,
+            },
+            _ => return,
+        };
+        self.push(screen, record);
+    }
+
+    /// Prints a line of our own, like a change of the connection state. It is
+    /// shown in every view and never redacted.
+    pub fn system_line(&mut self, screen: &mut Screen<impl Write>, text: &str) {
+        let record = Record {
+            laid_out: None,
+            line: text.to_string(),
+            system: true,
+            channel: None,
+            id: None,
+            user_id: None,
+            login: None,
+            parent_id: None,
+            deleted: false,
+        };
+        self.push(screen, record);
+    }
+
+    fn push(&mut self, screen: &mut Screen<impl Write>, mut record: Record) {
+        let lines = self.layout(&mut record);
+        if self.records.len() == MAX_RECORDS {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+
+        if lines.is_empty() {
+            // hidden, the record is only shown once its channel is viewed
+            return;
+        }
+        if self.scroll == 0 {
+            self.draw(screen, lines);
+        } else {
+            // keep the view where it is while the user reads the scrollback
+            self.scroll += lines.len();
+            self.unseen += 1;
+            self.redraw(screen);
+        }
+    }
+
+    /// Moves the widget to its new region and re-wraps the retained history.
+    pub fn resize(&mut self, screen: &mut Screen<impl Write>, pos: Pos, size: Size) {
+        let log = get_logger();
+        log.debug(
+            format!("Resizing chat window to: {} at {:?}", size, pos),
+            type_name::<ChatWidget>(),
+        );
+        self.pos = pos;
+        if size.width() != self.size.width() {
+            self.generation += 1;
+        }
+        self.size = size;
+        self.window.move_to(pos);
+        self.window.resize(size);
+        self.redraw(screen);
+    }
+
+    /// Number of rows a page up or page down moves.
+    pub fn page_size(&self) -> usize {
+        self.size.height().saturating_sub(1).max(1) as usize
+    }
+
+    pub fn scroll_up(&mut self, screen: &mut Screen<impl Write>, lines: usize) {
+        self.scroll = self.scroll.saturating_add(lines);
+        self.redraw(screen);
+    }
+
+    pub fn scroll_down(&mut self, screen: &mut Screen<impl Write>, lines: usize) {
+        if self.scroll == 0 {
+            return;
+        }
+        self.scroll = self.scroll.saturating_sub(lines);
+        self.redraw(screen);
+    }
+
+    pub fn scroll_to_top(&mut self, screen: &mut Screen<impl Write>) {
+        self.scroll_up(screen, usize::MAX);
+    }
+
+    pub fn scroll_to_bottom(&mut self, screen: &mut Screen<impl Write>) {
+        self.scroll_down(screen, usize::MAX);
+    }
+
+    /// Scrolls back to the message that the newest reply on screen answers,
+    /// as long as it is still kept. Pressed again it follows the thread, or
+    /// moves on to an older reply.
+    pub fn jump_to_parent(&mut self, screen: &mut Screen<impl Write>) {
+        let height = self.size.height().saturating_sub(1) as usize;
+        let mut records = mem::take(&mut self.records);
+        // lines of every shown record, from the newest one back
+        let lines: Vec<usize> = records
+            .iter_mut()
+            .rev()
+            .map(|record| self.layout(record).len())
+            .collect();
+
+        let mut below = 0;
+        let mut target = None;
+        for (i, (record, count)) in records.iter().rev().zip(&lines).enumerate() {
+            if below >= self.scroll + height {
+                break;
+            }
+            let shown = below + count > self.scroll;
+            below += count;
+            let parent_id = match (&record.parent_id, shown) {
+                (Some(parent_id), true) => parent_id,
+                _ => continue,
+            };
+            let parent = records
+                .iter()
+                .rev()
+                .skip(i + 1)
+                .position(|parent| parent.id.as_ref() == Some(parent_id));
+            match parent {
+                Some(offset) if lines[i + 1 + offset] > 0 => {
+                    target = Some(i + 1 + offset);
+                    break;
+                }
+                _ => get_logger().debug(
+                    format!("Parent of reply is not shown: {}", parent_id),
+                    type_name::<ChatWidget>(),
+                ),
+            }
+        }
+        self.records = records;
+
+        if let Some(target) = target {
+            // the parent ends up on the bottom row
+            self.scroll = lines[..target].iter().sum();
+            self.redraw(screen);
         }
     }
 
-    pub fn print(&mut self, screen: &mut Screen<impl Write>, message: ChatMessage) {
+    /// Handles a CLEARCHAT, either a single user got timed out or banned, or
+    /// the whole room was cleared.
+    pub fn clear_chat(&mut self, screen: &mut Screen<impl Write>, clear: &ClearChat) {
         let log = get_logger();
-        let timestamp = message
-            .meta_data
+        log.debug(
+            format!("Clearing chat for: {:?}", clear.target_user),
+            type_name::<ChatWidget>(),
+        );
+        let mut changed = false;
+        for record in self.records.iter_mut() {
+            if record.channel.as_deref() != Some(clear.channel) {
+                continue;
+            }
+            let cleared = match (clear.target_user_id, clear.target_user) {
+                (None, None) => true,
+                (Some(user_id), _) => record.user_id == Some(user_id),
+                (None, Some(login)) => record
+                    .login
+                    .as_deref()
+                    .is_some_and(|known| known.eq_ignore_ascii_case(login)),
+            };
+            if cleared {
+                changed |= !record.deleted;
+                record.deleted = true;
+                record.laid_out = None;
+            }
+        }
+        if changed {
+            self.redraw(screen);
+        }
+    }
+
+    pub fn clear_msg(&mut self, screen: &mut Screen<impl Write>, clear: &ClearMsg) {
+        let log = get_logger();
+        log.debug(
+            format!("Deleting message: {:?}", clear.target_msg_id),
+            type_name::<ChatWidget>(),
+        );
+        let record = self
+            .records
+            .iter_mut()
+            .find(|record| record.id.as_deref() == clear.target_msg_id && !record.deleted);
+        if let Some(record) = record {
+            record.deleted = true;
+            record.laid_out = None;
+            self.redraw(screen);
+        }
+    }
+
+    /// Switches between hiding deleted messages and showing them struck out.
+    pub fn toggle_deleted(&mut self, screen: &mut Screen<impl Write>) {
+        self.deleted_mode = match self.deleted_mode {
+            DeletedMode::Hide => DeletedMode::Show,
+            DeletedMode::Show => DeletedMode::Hide,
+        };
+        self.redraw(screen);
+    }
+
+    fn redraw(&mut self, screen: &mut Screen<impl Write>) {
+        // the last row is left empty for the cursor, just like when printing,
+        // and is used for the paused indicator while scrolled back
+        let height = self.size.height().saturating_sub(1) as usize;
+        let needed = height.saturating_add(self.scroll);
+        let mut records = mem::take(&mut self.records);
+        let mut lines = VecDeque::new();
+        for record in records.iter_mut().rev() {
+            if lines.len() >= needed {
+                break;
+            }
+            for line in self.layout(record).into_iter().rev() {
+                lines.push_front(line);
+            }
+        }
+        self.records = records;
+
+        self.scroll = self.scroll.min(lines.len().saturating_sub(height));
+        if self.scroll == 0 {
+            self.unseen = 0;
+        }
+        lines.truncate(lines.len() - self.scroll);
+        while lines.len() > height {
+            lines.pop_front();
+        }
+        self.window.clear(screen);
+        self.draw(screen, lines);
+
+        if self.scroll > 0 {
+            self.draw_paused(screen);
+        }
+    }
+
+    fn draw_paused(&mut self, screen: &mut Screen<impl Write>) {
+        let text = match self.unseen {
+            0 => "-- paused -- press End to follow chat".to_string(),
+            1 => "-- paused -- 1 new message".to_string(),
+            n => format!("-- paused -- {} new messages", n),
+        };
+        let text = text
+            .chars()
+            .take(self.size.width().saturating_sub(1) as usize)
+            .collect::<String>();
+        self.window.print(
+            screen,
+            text,
+            Style::new(Some(Color::Black), Some(Color::Yellow)),
+        );
+    }
+
+    fn draw(&mut self, screen: &mut Screen<impl Write>, lines: impl IntoIterator<Item = Line>) {
+        for line in lines {
+            for (text, style) in line {
+                self.window.print(screen, text, style);
+            }
+            self.window.newline(screen);
+        }
+    }
+
+    /// The lines a record is shown as, none when it is hidden. They are kept
+    /// with the record so that scrolling does not parse the history again.
+    fn layout(&mut self, record: &mut Record) -> Vec<Line> {
+        if record.deleted && self.deleted_mode == DeletedMode::Hide {
+            return vec![];
+        }
+        if let (Some(view), Some(channel)) = (&self.view, &record.channel) {
+            if view != channel {
+                return vec![];
+            }
+        }
+        if let Some((generation, lines)) = &record.laid_out {
+            if *generation == self.generation {
+                return lines.clone();
+            }
+        }
+        let padded = self.padder.current_max;
+        let mut lines = if record.system {
+            self.layout_dimmed(&record.line)
+        } else {
+            match ChatMessage::parse(&record.line) {
+                Ok(ChatMessage {
+                    command: Command::PrivMsg(message),
+                    ..
+                }) => self.layout_message(message, record.deleted),
+                Ok(ChatMessage {
+                    command: Command::UserNotice(notice),
+                    ..
+                }) => self.layout_notice(notice, record.deleted),
+                Ok(ChatMessage {
+                    command: Command::Notice(notice),
+                    ..
+                }) => self.layout_dimmed(notice.message),
+                _ => vec![],
+            }
+        };
+        if self.column_width() > 0 {
+            self.channel_column(&mut lines, record.channel.as_deref());
+        }
+        if self.padder.current_max != padded {
+            // a longer name widens the name column of every other record
+            self.generation += 1;
+        }
+        record.laid_out = Some((self.generation, lines.clone()));
+        lines
+    }
+
+    /// Width of the channel column, it is only shown in the merged view when
+    /// more than one channel is joined.
+    fn column_width(&self) -> usize {
+        if self.view.is_some() || self.channels.len() < 2 {
+            return 0;
+        }
+        let longest = self.channels.iter().map(|c| c.chars().count()).max();
+        "| #".len() + longest.unwrap_or_default() + 1
+    }
+
+    fn channel_column(&mut self, lines: &mut [Line], channel: Option<&str>) {
+        let width = self.column_width() - "| ".len() - 1;
+        let style = match channel {
+            Some(channel) => {
+                let (r, g, b) = self.color_cache.get(channel);
+                Style::fg(Some(Color::Rgb { r, g, b }))
+            }
+            None => Style::none(),
+        };
+        for (i, line) in lines.iter_mut().enumerate() {
+            let name = match channel {
+                Some(channel) if i == 0 => format!("#{}", channel),
+                _ => String::new(),
+            };
+            line.insert(0, ("| ".to_string(), Style::none()));
+            line.insert(1, (format!("{:1$}", name, width), style));
+            line.insert(2, (" ".to_string(), Style::none()));
+        }
+    }
+
+    fn layout_message(&mut self, message: PrivMsg, deleted: bool) -> Vec<Line> {
+        let timestamp = format_timestamp(message.meta_data.tmi_sent_ts, &self.timestamp_format);
+
+        let display_name = message.name();
+        let color = self
+            .color_cache
+            .user_color(display_name, message.meta_data.user_info.color);
+        let badges = self.badges.render(
+            &message.meta_data.user_info.badges,
+            &message.meta_data.badge_info,
+        );
+        let msg = message_text(&self.emotes, &self.highlights, &message, deleted);
+
+        let mut lines =
+            self.layout_lines(Some(timestamp), Some((display_name, color, badges)), msg);
+        if let Some(reply) = self.reply_line(&message) {
+            lines.insert(0, reply);
+        }
+        lines
+    }
+
+    /// A single dimmed line telling who is being replied to and what they
+    /// said, cut off where the message column ends.
+    fn reply_line(&self, message: &PrivMsg) -> Option<Line> {
+        let reply = &message.meta_data.reply;
+        reply.msg_id?;
+        let name = reply.display_name.or(reply.user_login).unwrap_or_default();
+        let body = reply.msg_body.as_deref().unwrap_or_default();
+        let text = format!("\u{21B3} replying to @{}: {}", name, body);
+
+        let width = self.message_width();
+        let text = if text.width() > width {
+            // room is left for the ellipsis
+            let mut used = 0;
+            let mut cut = String::new();
+            for c in text.chars() {
+                used += c.width().unwrap_or(0);
+                if used + 1 > width {
+                    break;
+                }
+                cut.push(c);
+            }
+            cut + "\u{2026}"
+        } else {
+            text
+        };
+
+        let mut line = Line::new();
+        self.timestamp(&mut line, None);
+        self.display_name(&mut line, None);
+        line.push(("| ".to_string(), Style::none()));
+        line.push((
+            text,
+            Style::fg(Some(Color::DarkGrey)).attributes(Attribute::Italic),
+        ));
+        Some(line)
+    }
+
+    /// Columns left for the message once the other columns are drawn.
+    fn message_width(&self) -> usize {
+        self.size
+            .width
+            .saturating_sub(
+                (10 + self.timestamp_width + self.padder.current_max as usize + self.column_width())
+                    as u16,
+            )
+            .max(1) as usize
+    }
+
+    fn layout_notice(&mut self, notice: UserNotice, deleted: bool) -> Vec<Line> {
+        let timestamp = notice
             .tmi_sent_ts
-            .with_timezone(&Local)
-            .format("%H:%M:%S")
-            .to_string();
-        self.print_timestamp(screen, Some(timestamp));
-
-        let (r, g, b) = message.meta_data.user_info.color.unwrap_or_else(|| {
-            let username = message.meta_data.user_info.display_name.unwrap();
-            log.debug(
-                format!("no color information found for user: {username}"),
-                type_name::<ChatWidget>(),
-            );
-            self.color_cache
-                .get(message.meta_data.user_info.display_name.unwrap())
-        });
+            .map(|ts| format_timestamp(ts, &self.timestamp_format));
+        let mut lines = self.layout_lines(
+            timestamp,
+            None,
+            vec![(notice_text(&notice), notice_style(&notice.event))],
+        );
 
-        let display_name = message.meta_data.user_info.display_name.unwrap();
-        let display_name = self.padder.add_pad(display_name);
+        if let Some(msg) = notice.message {
+            let display_name = notice.display_name.or(notice.login).unwrap_or_default();
+            let color = self.color_cache.user_color(display_name, notice.color);
+            lines.append(&mut self.layout_lines(
+                None,
+                Some((display_name, color, Line::new())),
+                vec![(msg.to_string(), message_style(Style::none(), deleted))],
+            ));
+        }
+        lines
+    }
 
-        self.print_display_name(screen, Some(&display_name), Some((r, g, b)));
+    /// Server notices and lines of our own.
+    fn layout_dimmed(&mut self, message: &str) -> Vec<Line> {
+        let text = format!("\u{25CF} {}", message);
+        let style = Style::fg(Some(Color::DarkGrey)).attributes(Attribute::Italic);
+        self.layout_lines(None, None, vec![(text, style)])
+    }
 
-        let msg = message.message.replace("Kappa", "\u{1F608}");
-        let msg = msg.replace(":)", "\u{1F600}");
+    fn layout_lines(
+        &mut self,
+        timestamp: Option<String>,
+        display_name: Option<(&str, (u8, u8, u8), Line)>,
+        msg: Line,
+    ) -> Vec<Line> {
+        let log = get_logger();
+        let display_name = display_name.map(|(name, color, mut badges)| {
+            if !badges.is_empty() {
+                badges.push((" ".to_string(), Style::none()));
+            }
+            // the badges share the padded column with the name
+            let prefix: usize = badges.iter().map(|(text, _)| text.chars().count()).sum();
+            let padded: String = badges.iter().map(|(text, _)| text.as_str()).collect();
+            let padded = self.padder.add_pad(padded + name);
+            (badges, padded.chars().skip(prefix).collect(), color)
+        });
 
-        let msg = msg.trim();
-        let current_width = (self.size.width - (18 + self.padder.current_max) as u16) as usize;
-        let msg = textwrap::wrap(msg, current_width);
+        // the pieces are wrapped as one text and cut up again afterwards
+        let mut ranges = Vec::new();
+        let mut text = String::new();
+        for (piece, style) in &msg {
+            ranges.push((text.len()..text.len() + piece.len(), *style));
+            text.push_str(piece);
+        }
+        let trimmed = text.trim();
+        let offset = text.len() - text.trim_start().len();
+        let current_width = self.message_width();
+        let msg = textwrap::wrap(trimmed, current_width);
 
         log.debug(
             format!(
@@ -71,59 +697,634 @@ impl<'a> ChatWidget<'a> {
             type_name::<ChatWidget>(),
         );
 
-        for (i, msg) in msg.iter().enumerate() {
-            if i == 0 {
-                self.print_msg(screen, msg)
-            } else {
-                self.print_timestamp(screen, None);
-                self.print_display_name(screen, None, None);
-                self.print_msg(screen, msg);
-            }
-            self.window.newline(screen);
-        }
+        let mut cursor = 0;
+        msg.iter()
+            .enumerate()
+            .map(|(i, msg)| {
+                let mut line = Line::new();
+                if i == 0 {
+                    self.timestamp(&mut line, timestamp.clone());
+                    self.display_name(&mut line, display_name.clone());
+                } else {
+                    self.timestamp(&mut line, None);
+                    self.display_name(&mut line, None);
+                }
+                line.push(("| ".to_string(), Style::none()));
+                // wrapped lines are pieces of the text with the whitespace
+                // at the breaks left out
+                match trimmed[cursor..].find(msg.as_ref()) {
+                    Some(at) => {
+                        let start = cursor + at;
+                        cursor = start + msg.len();
+                        line.extend(styled(msg, offset + start, &ranges));
+                    }
+                    None => line.push((msg.to_string(), Style::none())),
+                }
+                line
+            })
+            .collect()
     }
 
-    fn print_timestamp(&mut self, screen: &mut Screen<impl Write>, timestamp: Option<String>) {
-        self.window.print(screen, "| ", Style::none());
-        if let Some(timestamp) = timestamp {
-            self.window.print(screen, timestamp, Style::none());
-        } else {
-            self.window.print(screen, "        ", Style::none());
-        }
-        self.window.print(screen, " ", Style::none());
+    fn timestamp(&self, line: &mut Line, timestamp: Option<String>) {
+        line.push(("| ".to_string(), Style::none()));
+        line.push((
+            timestamp.unwrap_or_else(|| " ".repeat(self.timestamp_width)),
+            Style::none(),
+        ));
+        line.push((" ".to_string(), Style::none()));
     }
 
-    fn print_display_name(
-        &mut self,
-        screen: &mut Screen<impl Write>,
-        display_name: Option<&str>,
-        color: Option<(u8, u8, u8)>,
-    ) {
-        self.window.print(screen, "| ", Style::none());
-        if let Some(display_name) = display_name {
-            let (r, g, b) = color.unwrap();
-            self.window.print(
-                screen,
-                display_name,
-                Style::fg(Some(Color::Rgb { r, g, b })),
-            );
+    fn display_name(&self, line: &mut Line, display_name: Option<(Line, String, (u8, u8, u8))>) {
+        line.push(("| ".to_string(), Style::none()));
+        if let Some((badges, display_name, (r, g, b))) = display_name {
+            line.extend(badges);
+            line.push((display_name, Style::fg(Some(Color::Rgb { r, g, b }))));
         } else {
             let value = format!("{:1$}", " ", self.padder.current_max as usize);
-            self.window.print(screen, value, Style::none());
+            line.push((value, Style::none()));
         }
-        self.window.print(screen, " ", Style::none());
-    }
-
-    fn print_msg(&mut self, screen: &mut Screen<impl Write>, msg: &str) {
-        self.window.print(screen, "| ", Style::none());
-        self.window.print(screen, msg, Style::none());
+        line.push((" ".to_string(), Style::none()));
     }
 
     pub fn clear(&mut self, screen: &mut Screen<impl Write>) {
         let log = get_logger();
         log.debug("Clearing chat window", type_name::<ChatWidget>());
         self.padder.reset();
+        self.generation += 1;
+        self.records.clear();
+        self.scroll = 0;
+        self.unseen = 0;
         screen.erase_region(self.pos, self.size);
         self.window.cursor = Pos::zero();
     }
 }
+
+/// The time a message was sent in local time.
+pub fn format_timestamp(sent: DateTime<Utc>, format: &str) -> String {
+    sent.with_timezone(&Local).format(format).to_string()
+}
+
+/// The text of a chat message with its emotes rendered and its highlight,
+/// if any, laid over it.
+pub fn message_text(
+    emotes: &EmoteMap,
+    highlights: &Highlights,
+    message: &PrivMsg,
+    deleted: bool,
+) -> Line {
+    let highlight = highlights.style(message);
+    let spans = segment(message.message, &message.meta_data.emotes);
+    emotes
+        .render(&spans, Style::none())
+        .into_iter()
+        .map(|(text, style)| {
+            let style = match highlight {
+                Some(highlight) => highlight::apply(highlight, style),
+                None => style,
+            };
+            (text, message_style(style, deleted))
+        })
+        .collect()
+}
+
+/// Whether chrono understands every specifier in the format.
+pub fn valid_timestamp_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+/// The styled pieces covering `text`, which starts at `start` in the text
+/// the `ranges` were taken from.
+fn styled(text: &str, start: usize, ranges: &[(Range<usize>, Style)]) -> Line {
+    let end = start + text.len();
+    ranges
+        .iter()
+        .filter_map(|(range, style)| {
+            let from = range.start.max(start);
+            let to = range.end.min(end);
+            (from < to).then(|| (text[from - start..to - start].to_string(), *style))
+        })
+        .collect()
+}
+
+fn message_style(style: Style, deleted: bool) -> Style {
+    if deleted {
+        Style::fg(Some(Color::DarkGrey)).attributes(Attribute::CrossedOut)
+    } else {
+        style
+    }
+}
+
+pub fn notice_style(event: &UserNoticeEvent) -> Style {
+    let bg = match event {
+        UserNoticeEvent::Sub(_) | UserNoticeEvent::Resub(_) => Color::DarkMagenta,
+        UserNoticeEvent::SubGift(_) | UserNoticeEvent::SubMysteryGift(_) => Color::Magenta,
+        UserNoticeEvent::Raid(_) => Color::DarkYellow,
+        UserNoticeEvent::Unraid => Color::DarkGrey,
+        UserNoticeEvent::Ritual { .. } => Color::DarkGreen,
+        UserNoticeEvent::BitsBadgeTier { .. } => Color::DarkCyan,
+        UserNoticeEvent::Announcement { color } => match color.unwrap_or_default() {
+            "BLUE" => Color::DarkBlue,
+            "GREEN" => Color::DarkGreen,
+            "ORANGE" => Color::DarkYellow,
+            "PURPLE" => Color::DarkMagenta,
+            _ => Color::DarkRed,
+        },
+        UserNoticeEvent::Other(_) => Color::DarkGrey,
+    };
+    Style::new(Some(Color::White), Some(bg))
+}
+
+/// `None` for an unknown plan, it is left out of the sentence.
+fn sub_plan_text(plan: SubPlan) -> Option<&'static str> {
+    match plan {
+        SubPlan::Prime => Some("Prime"),
+        SubPlan::Tier1 => Some("Tier 1"),
+        SubPlan::Tier2 => Some("Tier 2"),
+        SubPlan::Tier3 => Some("Tier 3"),
+        SubPlan::Unknown => None,
+    }
+}
+
+pub fn notice_text(notice: &UserNotice) -> String {
+    let name = notice.display_name.or(notice.login).unwrap_or("Someone");
+    let system_msg = || {
+        notice
+            .system_msg
+            .as_deref()
+            .unwrap_or(notice.msg_id)
+            .to_string()
+    };
+
+    let text = match &notice.event {
+        UserNoticeEvent::Sub(sub) => match sub_plan_text(sub.sub_plan) {
+            Some(plan) => format!("{} subscribed with {}", name, plan),
+            None => format!("{} subscribed", name),
+        },
+        UserNoticeEvent::Resub(sub) => {
+            let mut text = format!("{} resubscribed", name);
+            if let Some(plan) = sub_plan_text(sub.sub_plan) {
+                text.push_str(&format!(" with {}", plan));
+            }
+            if let Some(months) = sub.cumulative_months {
+                text.push_str(&format!(", {} months in total", months));
+            }
+            if let (true, Some(streak)) = (sub.should_share_streak, sub.streak_months) {
+                text.push_str(&format!(", {} months streak", streak));
+            }
+            text
+        }
+        UserNoticeEvent::SubGift(gift) => format!(
+            "{} gifted a {}sub to {}",
+            name,
+            sub_plan_text(gift.sub_plan)
+                .map(|plan| format!("{} ", plan))
+                .unwrap_or_default(),
+            gift.recipient_display_name
+                .or(gift.recipient_user_name)
+                .unwrap_or("someone")
+        ),
+        UserNoticeEvent::SubMysteryGift(gift) => format!(
+            "{} is gifting {} {}subs to the community",
+            name,
+            gift.mass_gift_count.unwrap_or(1),
+            sub_plan_text(gift.sub_plan)
+                .map(|plan| format!("{} ", plan))
+                .unwrap_or_default()
+        ),
+        UserNoticeEvent::Raid(raid) => format!(
+            "{} is raiding with {} viewers",
+            raid.display_name.or(raid.login).unwrap_or(name),
+            raid.viewer_count.unwrap_or_default()
+        ),
+        UserNoticeEvent::Ritual {
+            name: Some("new_chatter"),
+        } => format!("{} is new to chat, say hello!", name),
+        UserNoticeEvent::BitsBadgeTier {
+            threshold: Some(threshold),
+        } => format!("{} just earned a new {} bits badge!", name, threshold),
+        UserNoticeEvent::Announcement { .. } => format!("Announcement from {}", name),
+        UserNoticeEvent::Unraid
+        | UserNoticeEvent::Ritual { .. }
+        | UserNoticeEvent::BitsBadgeTier { .. }
+        | UserNoticeEvent::Other(_) => system_msg(),
+    };
+    format!("\u{2605} {}", text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        gui::buffer::CellState,
+        log,
+        parser::{chat_message::ChatMessage, command::Command},
+    };
+
+    const FIRST: &str = "@badge-info=;badges=;color=#FF0000;display-name=ronni;emotes=;id=msg-1;mod=0;room-id=1;subscriber=0;tmi-sent-ts=1500000000;turbo=0;user-id=10;user-type= :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :first message";
+    const SECOND: &str = "@badge-info=;badges=;color=#00FF00;display-name=foo;emotes=;id=msg-2;mod=0;room-id=1;subscriber=0;tmi-sent-ts=1500000000;turbo=0;user-id=20;user-type= :foo!foo@foo.tmi.twitch.tv PRIVMSG #dallas :second message";
+
+    fn screen_rows(screen: &Screen<Vec<u8>>) -> Vec<String> {
+        screen
+            .buffer()
+            .lines()
+            .map(|line| {
+                line.iter()
+                    .map(|cell| match cell.cell_state {
+                        CellState::Occupied(c) => c,
+                        _ => ' ',
+                    })
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    fn widget(width: u16, height: u16) -> (Screen<Vec<u8>>, ChatWidget<'static>) {
+        let size = Size::new(width, height);
+        // the window is leaked so it lives as long as the test
+        let window = Box::leak(Box::new(Window::new(Pos::zero(), size)));
+        let screen = Screen::new(Vec::new(), size).unwrap();
+        (screen, ChatWidget::new(window, Pos::zero(), size))
+    }
+
+    fn clear(input: &str) -> Command<'_> {
+        ChatMessage::parse(input).unwrap().command
+    }
+
+    fn notice(input: &str) -> UserNotice<'_> {
+        match ChatMessage::parse(input).unwrap().command {
+            Command::UserNotice(notice) => notice,
+            command => panic!("expected USERNOTICE, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn should_describe_subgift() {
+        let notice = notice("@display-name=tww2;msg-id=subgift;msg-param-recipient-display-name=Mr_Woodchuck;msg-param-sub-plan=1000 :tmi.twitch.tv USERNOTICE #forstycup");
+        assert_eq!(
+            notice_text(&notice),
+            "\u{2605} tww2 gifted a Tier 1 sub to Mr_Woodchuck"
+        );
+        assert_eq!(
+            notice_style(&notice.event),
+            Style::new(Some(Color::White), Some(Color::Magenta))
+        );
+    }
+
+    #[test]
+    fn should_leave_out_unknown_sub_plan() {
+        let text = |input| notice_text(&notice(input));
+        assert_eq!(
+            text("@display-name=tww2;msg-id=subgift;msg-param-recipient-display-name=Mr_Woodchuck;msg-param-sub-plan=5000 :tmi.twitch.tv USERNOTICE #forstycup"),
+            "\u{2605} tww2 gifted a sub to Mr_Woodchuck"
+        );
+        assert_eq!(
+            text("@display-name=tww2;msg-id=submysterygift;msg-param-mass-gift-count=5 :tmi.twitch.tv USERNOTICE #forstycup"),
+            "\u{2605} tww2 is gifting 5 subs to the community"
+        );
+        assert_eq!(
+            text("@display-name=tww2;msg-id=sub :tmi.twitch.tv USERNOTICE #forstycup"),
+            "\u{2605} tww2 subscribed"
+        );
+    }
+
+    #[test]
+    fn should_fall_back_to_system_msg() {
+        let notice = notice("@msg-id=unraid;system-msg=The\\sraid\\shas\\sbeen\\scanceled. :tmi.twitch.tv USERNOTICE #forstycup");
+        assert_eq!(notice_text(&notice), "\u{2605} The raid has been canceled.");
+    }
+
+    #[test]
+    fn should_strike_out_and_hide_deleted_message() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.print(&mut screen, FIRST);
+        chat.print(&mut screen, SECOND);
+
+        if let Command::ClearMsg(clear) =
+            clear("@login=ronni;target-msg-id=msg-1 :tmi.twitch.tv CLEARMSG #dallas :first message")
+        {
+            chat.clear_msg(&mut screen, &clear);
+        }
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("first message"));
+        assert!(rows[1].ends_with("second message"));
+        let struck = screen
+            .buffer()
+            .lines()
+            .next()
+            .unwrap()
+            .iter()
+            .filter(|cell| cell.style.attributes.has(Attribute::CrossedOut))
+            .count();
+        assert_eq!(struck, "first message".len());
+
+        chat.toggle_deleted(&mut screen);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("second message"));
+        assert_eq!(rows[1], "");
+    }
+
+    #[test]
+    fn should_clear_messages_of_banned_user_and_whole_room() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.toggle_deleted(&mut screen);
+        chat.print(&mut screen, FIRST);
+        chat.print(&mut screen, SECOND);
+
+        if let Command::ClearChat(clear) =
+            clear("@ban-duration=10;target-user-id=20 :tmi.twitch.tv CLEARCHAT #dallas :foo")
+        {
+            chat.clear_chat(&mut screen, &clear);
+        }
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("first message"));
+        assert_eq!(rows[1], "");
+
+        if let Command::ClearChat(clear) = clear(":tmi.twitch.tv CLEARCHAT #dallas") {
+            chat.clear_chat(&mut screen, &clear);
+        }
+        assert!(screen_rows(&screen).iter().all(|row| row.is_empty()));
+    }
+
+    #[test]
+    fn should_clear_user_by_login_without_user_id() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.toggle_deleted(&mut screen);
+        chat.print(&mut screen, FIRST);
+        chat.print(&mut screen, SECOND);
+
+        if let Command::ClearChat(clear) = clear(":tmi.twitch.tv CLEARCHAT #dallas :foo") {
+            chat.clear_chat(&mut screen, &clear);
+        }
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("first message"));
+        assert_eq!(rows[1], "");
+    }
+
+    #[test]
+    fn should_rewrap_history_on_resize() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.print(&mut screen, FIRST);
+        chat.print(&mut screen, SECOND);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("first message"));
+        assert!(rows[1].ends_with("second message"));
+
+        let size = Size::new(32, 5);
+        screen.resize(size).unwrap();
+        chat.resize(&mut screen, Pos::zero(), size);
+        let rows = screen_rows(&screen);
+        assert_eq!(rows.len(), 5);
+        assert!(rows[0].ends_with("first"));
+        assert!(rows[1].ends_with("message"));
+        assert!(rows[2].ends_with("second"));
+        assert!(rows[3].ends_with("message"));
+    }
+
+    #[test]
+    fn should_fall_back_to_login_without_display_name() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.print(&mut screen, "@color=;display-name=;id=msg-4;room-id=1;tmi-sent-ts=1500000000;user-id=40 :lurker!lurker@lurker.tmi.twitch.tv PRIVMSG #dallas :hi");
+        chat.print(&mut screen, "@id=msg-5;room-id=1;tmi-sent-ts=1500000000;user-id=50 :nobody!nobody@nobody.tmi.twitch.tv PRIVMSG #dallas :hey");
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("| lurker | hi"));
+        assert!(rows[1].ends_with("| nobody | hey"));
+    }
+
+    #[test]
+    fn should_lay_out_again_only_when_something_changed() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.print(&mut screen, FIRST);
+        let generation = chat.generation;
+        chat.print(&mut screen, SECOND);
+        chat.scroll_up(&mut screen, 1);
+        chat.scroll_down(&mut screen, 1);
+        assert_eq!(chat.generation, generation);
+        assert!(chat.records.iter().all(|record| record.laid_out.is_some()));
+
+        // the longer name widens the name column of the others
+        chat.print(
+            &mut screen,
+            &FIRST.replace("display-name=ronni", "display-name=barbarian"),
+        );
+        assert!(chat.generation > generation);
+        chat.scroll_up(&mut screen, 1);
+        chat.scroll_down(&mut screen, 1);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].contains("| ronni     | first message"));
+    }
+
+    #[test]
+    fn should_print_system_line_in_every_view() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.set_channels(vec!["dallas".to_string(), "forsen".to_string()]);
+        chat.system_line(&mut screen, "Connected");
+        chat.next_view(&mut screen);
+        assert!(screen_rows(&screen)[0].ends_with("\u{25CF} Connected"));
+
+        if let Command::ClearChat(clear) = clear(":tmi.twitch.tv CLEARCHAT #dallas") {
+            chat.clear_chat(&mut screen, &clear);
+        }
+        assert!(screen_rows(&screen)[0].ends_with("\u{25CF} Connected"));
+    }
+
+    #[test]
+    fn should_print_server_notice() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.print(
+            &mut screen,
+            ":tmi.twitch.tv NOTICE * :Lost connection to Twitch chat",
+        );
+        assert!(screen_rows(&screen)[0].ends_with("\u{25CF} Lost connection to Twitch chat"));
+    }
+
+    const FORSEN: &str = "@color=#0000FF;display-name=bar;id=msg-3;room-id=2;tmi-sent-ts=1500000000;user-id=20 :bar!bar@bar.tmi.twitch.tv PRIVMSG #forsen :other channel";
+
+    #[test]
+    fn should_show_channel_column_and_switch_views() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.set_channels(vec!["dallas".to_string(), "forsen".to_string()]);
+        chat.print(&mut screen, FIRST);
+        chat.print(&mut screen, FORSEN);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].starts_with("| #dallas | "));
+        assert!(rows[1].starts_with("| #forsen | "));
+        assert!(rows[1].ends_with("other channel"));
+
+        chat.next_view(&mut screen);
+        assert_eq!(chat.view(), Some("dallas"));
+        chat.print(&mut screen, SECOND);
+        chat.print(&mut screen, FORSEN);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].starts_with("| "));
+        assert!(rows[0].ends_with("first message"));
+        assert!(rows[1].ends_with("second message"));
+        assert_eq!(rows[2], "");
+
+        chat.next_view(&mut screen);
+        chat.next_view(&mut screen);
+        assert_eq!(chat.view(), None);
+        chat.prev_view(&mut screen);
+        assert_eq!(chat.view(), Some("forsen"));
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("other channel"));
+        assert!(rows[1].ends_with("other channel"));
+    }
+
+    #[test]
+    fn should_only_clear_chat_of_its_channel() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.set_channels(vec!["dallas".to_string(), "forsen".to_string()]);
+        chat.toggle_deleted(&mut screen);
+        chat.print(&mut screen, SECOND);
+        chat.print(&mut screen, FORSEN);
+
+        if let Command::ClearChat(clear) =
+            clear("@ban-duration=10;target-user-id=20 :tmi.twitch.tv CLEARCHAT #forsen :bar")
+        {
+            chat.clear_chat(&mut screen, &clear);
+        }
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("second message"));
+        assert_eq!(rows[1], "");
+    }
+
+    #[test]
+    fn should_put_badges_in_front_of_name() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.print(&mut screen, "@badge-info=subscriber/14;badges=moderator/1,subscriber/12;color=#FF0000;display-name=ronni;id=msg-1;room-id=1;tmi-sent-ts=1500000000;user-id=10 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :hi");
+        chat.print(&mut screen, SECOND);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].contains("| \u{2694}\u{2605}14 ronni | hi"));
+        assert!(rows[1].contains("| foo        | second message"));
+    }
+
+    #[test]
+    fn should_render_emotes_from_their_positions() {
+        log::init();
+        let (mut screen, mut chat) = widget(40, 4);
+        // Kappa and :) outside of the emote positions are just text
+        chat.print(&mut screen, "@color=#FF0000;display-name=ronni;emotes=25:0-4/1902:21-25;id=msg-1;room-id=1;tmi-sent-ts=1500000000;user-id=10 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :Kappa :) Kappas then Keepo");
+        let rows = screen_rows(&screen);
+        // the second cell of the wide glyph reads as a space
+        assert!(rows[0].ends_with("| \u{1F608}  :) Kappas then"));
+        assert!(rows[1].ends_with("| Keepo"));
+
+        let row = screen.buffer().lines().nth(1).unwrap();
+        let keepo = row
+            .iter()
+            .rposition(|cell| cell.cell_state == CellState::Occupied('K'));
+        assert_eq!(row[keepo.unwrap()].style, EmoteMap::default().unmapped);
+    }
+
+    #[test]
+    fn should_highlight_mentions_and_first_messages() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        let mut highlights = Highlights::default();
+        highlights.set_nick("foo");
+        chat.set_highlights(highlights.clone());
+        chat.print(&mut screen, "@color=#FF0000;display-name=ronni;first-msg=1;id=msg-1;room-id=1;tmi-sent-ts=1500000000;user-id=10 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :hello");
+        chat.print(&mut screen, "@color=#FF0000;display-name=ronni;id=msg-2;room-id=1;tmi-sent-ts=1500000000;user-id=10 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :hey @Foo");
+        chat.print(&mut screen, SECOND);
+
+        let style_of = |row: usize, c: char| {
+            let line = screen.buffer().lines().nth(row).unwrap();
+            let cell = line
+                .iter()
+                .rfind(|cell| cell.cell_state == CellState::Occupied(c))
+                .unwrap();
+            cell.style
+        };
+        assert_eq!(style_of(0, 'h'), highlights.first_msg.unwrap());
+        assert_eq!(style_of(1, 'F'), highlights.mention.unwrap());
+        assert_eq!(style_of(2, 'd'), Style::none());
+    }
+
+    const REPLY: &str = "@color=#0000FF;display-name=bar;id=msg-3;reply-parent-display-name=ronni;reply-parent-msg-body=first\\smessage\\sthat\\sgoes\\son\\sand\\son;reply-parent-msg-id=msg-1;reply-parent-user-id=10;reply-parent-user-login=ronni;room-id=1;tmi-sent-ts=1500000000;user-id=30 :bar!bar@bar.tmi.twitch.tv PRIVMSG #dallas :@ronni yes";
+
+    #[test]
+    fn should_show_what_a_reply_answers() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        chat.print(&mut screen, REPLY);
+        let rows = screen_rows(&screen);
+        assert_eq!(
+            rows[0],
+            "|          |     | \u{21B3} replying to @ronni: first message th\u{2026}"
+        );
+        assert!(rows[1].ends_with("| bar | @ronni yes"));
+    }
+
+    #[test]
+    fn should_jump_to_parent_of_reply() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        for n in 1..4 {
+            chat.print(&mut screen, &message(n));
+        }
+        chat.print(&mut screen, FIRST);
+        for n in 10..14 {
+            chat.print(&mut screen, &message(n));
+        }
+        chat.print(&mut screen, REPLY);
+        chat.print(&mut screen, SECOND);
+
+        chat.jump_to_parent(&mut screen);
+        let rows = screen_rows(&screen);
+        assert!(rows[2].ends_with("| first message"));
+        assert!(rows[3].starts_with("-- paused --"));
+
+        // the parent is no reply, and no other reply is on screen
+        chat.jump_to_parent(&mut screen);
+        assert_eq!(screen_rows(&screen), rows);
+    }
+
+    fn message(n: usize) -> String {
+        format!("@color=#FF0000;display-name=ronni;id=msg-{n};room-id=1;tmi-sent-ts=1500000000;user-id=10 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :message {n}")
+    }
+
+    #[test]
+    fn should_scroll_back_and_follow_again() {
+        log::init();
+        let (mut screen, mut chat) = widget(60, 4);
+        for n in 1..=5 {
+            chat.print(&mut screen, &message(n));
+        }
+
+        chat.scroll_up(&mut screen, 1);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("message 2"));
+        assert!(rows[2].ends_with("message 4"));
+        assert!(rows[3].starts_with("-- paused --"));
+
+        chat.print(&mut screen, &message(6));
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("message 2"));
+        assert_eq!(rows[3], "-- paused -- 1 new message");
+
+        chat.scroll_to_top(&mut screen);
+        assert!(screen_rows(&screen)[0].ends_with("message 1"));
+
+        chat.scroll_to_bottom(&mut screen);
+        let rows = screen_rows(&screen);
+        assert!(rows[0].ends_with("message 4"));
+        assert!(rows[2].ends_with("message 6"));
+        assert_eq!(rows[3], "");
+
+        chat.print(&mut screen, &message(7));
+        assert!(screen_rows(&screen)[2].ends_with("message 7"));
+    }
+}
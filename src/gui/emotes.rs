@@ -0,0 +1,265 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use crossterm::style::{Attribute, Color};
+
+use super::buffer::Style;
+#[cfg(feature = "images")]
+use super::graphics::EmoteImages;
+use crate::parser::Emote;
+
+/// A piece of a chat message, either plain text or a single use of an emote.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Span<'m> {
+    Text(&'m str),
+    Emote { id: &'m str, name: &'m str },
+}
+
+/// Splits a message into text and emotes using the positions Twitch sent
+/// along, which count code points and include the last one. Positions that
+/// fall outside of the message or overlap an earlier emote are ignored.
+pub fn segment<'m>(message: &'m str, emotes: &[Emote<'m>]) -> Vec<Span<'m>> {
+    // byte offset of every code point, plus the end of the message
+    let offsets: Vec<usize> = message
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(message.len()))
+        .collect();
+    let chars = offsets.len() - 1;
+
+    let mut uses: Vec<(usize, usize, &str)> = emotes
+        .iter()
+        .flat_map(|emote| {
+            emote
+                .indexes
+                .iter()
+                .map(move |&(first, last)| (first as usize, last as usize, emote.id))
+        })
+        .filter(|&(first, last, _)| first <= last && last < chars)
+        .collect();
+    uses.sort_unstable();
+
+    let mut spans = Vec::new();
+    let mut position = 0;
+    for (first, last, id) in uses {
+        if first < position {
+            continue;
+        }
+        if first > position {
+            spans.push(Span::Text(&message[offsets[position]..offsets[first]]));
+        }
+        let name = &message[offsets[first]..offsets[last + 1]];
+        spans.push(Span::Emote { id, name });
+        position = last + 1;
+    }
+    if position < chars {
+        spans.push(Span::Text(&message[offsets[position]..]));
+    }
+    spans
+}
+
+/// Glyphs to show instead of emotes, looked up by emote id first and by
+/// name second. Emotes without a glyph keep their name in `unmapped` style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmoteMap {
+    by_id: HashMap<String, String>,
+    by_name: HashMap<String, String>,
+    pub unmapped: Style,
+    /// Images go before glyphs, for the emotes that have one cached.
+    #[cfg(feature = "images")]
+    images: Option<EmoteImages>,
+}
+
+impl Default for EmoteMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+        map.by_name
+            .insert("Kappa".to_string(), "\u{1F608}".to_string());
+        map.by_name
+            .insert(":)".to_string(), "\u{1F600}".to_string());
+        map
+    }
+}
+
+impl EmoteMap {
+    pub fn empty() -> Self {
+        Self {
+            by_id: HashMap::new(),
+            by_name: HashMap::new(),
+            unmapped: Style::fg(Some(Color::Magenta)).attributes(Attribute::Bold),
+            #[cfg(feature = "images")]
+            images: None,
+        }
+    }
+
+    #[cfg(feature = "images")]
+    pub fn set_images(&mut self, images: EmoteImages) {
+        self.images = Some(images);
+    }
+
+    /// Reads a mapping file on top of the default mappings.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        let mut map = Self::default();
+        map.parse(&text)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        Ok(map)
+    }
+
+    /// Adds the mappings of a file, one `name = glyph` or `id:<id> = glyph`
+    /// per line. Blank lines and lines starting with `#` are skipped.
+    pub fn parse(&mut self, text: &str) -> Result<(), String> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, glyph) = line
+                .rsplit_once('=')
+                .map(|(key, glyph)| (key.trim(), glyph.trim()))
+                .filter(|(key, glyph)| !key.is_empty() && !glyph.is_empty())
+                .ok_or_else(|| {
+                    format!("line {}: expected `name = glyph`, got `{}`", n + 1, line)
+                })?;
+            match key.strip_prefix("id:") {
+                Some(id) => self.by_id.insert(id.trim().to_string(), glyph.to_string()),
+                None => self.by_name.insert(key.to_string(), glyph.to_string()),
+            };
+        }
+        Ok(())
+    }
+
+    pub fn glyph(&self, id: &str, name: &str) -> Option<&str> {
+        self.by_id
+            .get(id)
+            .or_else(|| self.by_name.get(name))
+            .map(String::as_str)
+    }
+
+    #[cfg(feature = "images")]
+    fn image(&self, id: &str) -> Option<String> {
+        self.images.as_ref()?.placeholder(id)
+    }
+
+    /// Turns the spans into styled text, `style` is used for the text and
+    /// for emotes that have a glyph.
+    pub fn render(&self, spans: &[Span], style: Style) -> Vec<(String, Style)> {
+        spans
+            .iter()
+            .map(|span| match span {
+                Span::Text(text) => (text.to_string(), style),
+                Span::Emote { id, name } => {
+                    #[cfg(feature = "images")]
+                    if let Some(placeholder) = self.image(id) {
+                        return (placeholder, style);
+                    }
+                    match self.glyph(id, name) {
+                        Some(glyph) => (glyph.to_string(), style),
+                        None => (name.to_string(), self.unmapped),
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn emote(id: &str, indexes: Vec<(u32, u32)>) -> Emote<'_> {
+        Emote { id, indexes }
+    }
+
+    #[test]
+    fn should_split_on_code_points() {
+        let message = "h\u{e9}j Kappa \u{1F600} Kappa4Head end";
+        let emotes = [
+            emote("25", vec![(4, 8)]),
+            emote("99", vec![(12, 21)]),
+            emote("bad", vec![(30, 50), (6, 7)]),
+        ];
+        assert_eq!(
+            segment(message, &emotes),
+            vec![
+                Span::Text("h\u{e9}j "),
+                Span::Emote {
+                    id: "25",
+                    name: "Kappa"
+                },
+                Span::Text(" \u{1F600} "),
+                Span::Emote {
+                    id: "99",
+                    name: "Kappa4Head"
+                },
+                Span::Text(" end"),
+            ]
+        );
+        assert_eq!(segment("hi", &[]), vec![Span::Text("hi")]);
+    }
+
+    #[test]
+    fn should_map_by_id_before_name() {
+        let mut map = EmoteMap::default();
+        map.parse("# mine\n\nid:25 = K\nLUL = L\n").unwrap();
+        let spans = [
+            Span::Emote {
+                id: "25",
+                name: "Kappa",
+            },
+            Span::Text(" "),
+            Span::Emote {
+                id: "1",
+                name: "LUL",
+            },
+            Span::Text(" "),
+            Span::Emote {
+                id: "2",
+                name: "PogChamp",
+            },
+        ];
+        let rendered = map.render(&spans, Style::none());
+        let text: String = rendered.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(text, "K L PogChamp");
+        assert_eq!(rendered[4].1, map.unmapped);
+        assert_eq!(rendered[0].1, Style::none());
+    }
+
+    #[cfg(feature = "images")]
+    #[test]
+    fn should_prefer_cached_images_over_glyphs() {
+        use crate::{
+            emote_cache::EmoteCache,
+            gui::graphics::{EmoteImages, Protocol},
+        };
+
+        let cache = EmoteCache::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/emotes"));
+        let mut map = EmoteMap::default();
+        map.set_images(EmoteImages::new(Protocol::ITerm2, cache));
+        let spans = [
+            Span::Emote {
+                id: "25",
+                name: "Kappa",
+            },
+            Span::Emote {
+                id: "1902",
+                name: ":)",
+            },
+        ];
+        let text: String = map
+            .render(&spans, Style::none())
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(text, "\u{F0000}\u{a0}\u{1F600}");
+    }
+
+    #[test]
+    fn should_report_bad_lines() {
+        assert_eq!(
+            EmoteMap::empty().parse("Kappa = K\nLUL\n"),
+            Err("line 2: expected `name = glyph`, got `LUL`".to_string())
+        );
+    }
+}
@@ -1,6 +0,0 @@
-pub type Result<T> = std::result::Result<T, Error>;
-
-pub enum Error {
-    Io,
-    Fmt,
-}
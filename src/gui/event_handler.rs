@@ -1,5 +1,6 @@
 use std::{
     any::type_name,
+    collections::HashMap,
     error::Error,
     ops::{Deref, DerefMut},
     thread::{self, JoinHandle},
@@ -16,15 +17,251 @@ pub struct EventHandler {
     worker: EventWorker,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Action {
     Clear,
+    ToggleDeleted,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollTop,
+    ScrollBottom,
+    NextTab,
+    PrevTab,
+    ToggleEvents,
+    JumpToParent,
+    Resize(u16, u16),
+    Focus,
+    Unfocus,
+    Input(KeyEvent),
     Exit,
 }
 
+/// The actions that can be bound to keys, with the names used for them in
+/// the config file.
+const BINDABLE: [(&str, Action); 14] = [
+    ("quit", Action::Exit),
+    ("clear", Action::Clear),
+    ("toggle_deleted", Action::ToggleDeleted),
+    ("toggle_events", Action::ToggleEvents),
+    ("jump_to_parent", Action::JumpToParent),
+    ("scroll_up", Action::ScrollUp),
+    ("scroll_down", Action::ScrollDown),
+    ("page_up", Action::PageUp),
+    ("page_down", Action::PageDown),
+    ("scroll_top", Action::ScrollTop),
+    ("scroll_bottom", Action::ScrollBottom),
+    ("next_tab", Action::NextTab),
+    ("prev_tab", Action::PrevTab),
+    ("input", Action::Focus),
+];
+
+/// Which key does what while the input line does not have focus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let keys = [
+            (KeyCode::Char('q'), Action::Exit),
+            (KeyCode::Char('c'), Action::Clear),
+            (KeyCode::Char('d'), Action::ToggleDeleted),
+            (KeyCode::Char('e'), Action::ToggleEvents),
+            (KeyCode::Char('p'), Action::JumpToParent),
+            (KeyCode::Char('i'), Action::Focus),
+            (KeyCode::Up, Action::ScrollUp),
+            (KeyCode::Down, Action::ScrollDown),
+            (KeyCode::PageUp, Action::PageUp),
+            (KeyCode::PageDown, Action::PageDown),
+            (KeyCode::Home, Action::ScrollTop),
+            (KeyCode::End, Action::ScrollBottom),
+            (KeyCode::Tab, Action::NextTab),
+            (KeyCode::BackTab, Action::PrevTab),
+        ];
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|(code, action)| ((code, KeyModifiers::NONE), action))
+                .collect(),
+        }
+    }
+}
+
+impl KeyMap {
+    /// Binds the action called `name` to `keys` instead of its current keys.
+    /// Keys are written like `q`, `ctrl+l`, `pageup` or `shift+tab`, a key
+    /// another action still uses is refused.
+    pub fn bind(&mut self, name: &str, keys: &[&str]) -> Result<(), String> {
+        let action = bindable(name)?;
+        if action == Action::Exit && keys.is_empty() {
+            return Err(format!("`{}` needs at least one key", name));
+        }
+        let parsed = keys
+            .iter()
+            .map(|key| parse_key(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (key, parsed) in keys.iter().zip(&parsed) {
+            match self.keys.get(parsed) {
+                Some(bound) if *bound != action => {
+                    return Err(format!(
+                        "key `{}` is already bound to `{}`",
+                        key,
+                        action_name(bound)
+                    ))
+                }
+                _ => (),
+            }
+        }
+        self.keys.retain(|_, bound| *bound != action);
+        for key in parsed {
+            self.keys.insert(key, action.clone());
+        }
+        Ok(())
+    }
+
+    /// Takes all keys from the action called `name`, so they can be given to
+    /// other actions.
+    pub fn unbind(&mut self, name: &str) -> Result<(), String> {
+        let action = bindable(name)?;
+        self.keys.retain(|_, bound| *bound != action);
+        Ok(())
+    }
+
+    /// One of the keys bound to `action`, written the way the config does.
+    pub fn key(&self, action: &Action) -> Option<String> {
+        self.keys
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .filter_map(|(key, _)| key_text(*key))
+            .min()
+    }
+
+    pub fn action(&self, key: KeyEvent) -> Option<Action> {
+        self.keys.get(&normalize(key.code, key.modifiers)).cloned()
+    }
+}
+
+fn bindable(name: &str) -> Result<Action, String> {
+    BINDABLE
+        .iter()
+        .find(|(bindable, _)| *bindable == name)
+        .map(|(_, action)| action.clone())
+        .ok_or_else(|| {
+            let names: Vec<&str> = BINDABLE.iter().map(|(name, _)| *name).collect();
+            format!(
+                "unknown action `{}`, expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+fn action_name(action: &Action) -> &'static str {
+    BINDABLE
+        .iter()
+        .find(|(_, bindable)| bindable == action)
+        .map_or("?", |(name, _)| *name)
+}
+
+/// Shift is already part of characters and back tab, so it is left out for
+/// those.
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    match code {
+        KeyCode::Char(_) | KeyCode::BackTab => (code, modifiers - KeyModifiers::SHIFT),
+        _ => (code, modifiers),
+    }
+}
+
+fn key_text((code, modifiers): (KeyCode, KeyModifiers)) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "shift+tab".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        _ => return None,
+    };
+    let mut text = String::new();
+    for (modifier, prefix) in [
+        (KeyModifiers::CONTROL, "ctrl+"),
+        (KeyModifiers::ALT, "alt+"),
+        (KeyModifiers::SHIFT, "shift+"),
+    ] {
+        if modifiers.contains(modifier) {
+            text.push_str(prefix);
+        }
+    }
+    text.push_str(&name);
+    Some(text)
+}
+
+fn parse_key(key: &str) -> Result<(KeyCode, KeyModifiers), String> {
+    let invalid = || format!("invalid key `{}`", key);
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = key.split('+').collect();
+    // `+` on its own, or as the last key after a modifier
+    let name = match parts.pop() {
+        Some("") if parts.last() == Some(&"") => {
+            parts.pop();
+            "+"
+        }
+        Some(name) => name,
+        None => return Err(invalid()),
+    };
+    for modifier in parts {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(invalid()),
+        };
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match name.to_lowercase().as_str() {
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "enter" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            "delete" => KeyCode::Delete,
+            "backspace" => KeyCode::Backspace,
+            name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n @ 1..=12) => KeyCode::F(n),
+                _ => return Err(invalid()),
+            },
+        },
+    };
+    Ok(normalize(code, modifiers))
+}
+
 impl EventHandler {
-    pub fn new() -> EventHandler {
+    pub fn new(keys: KeyMap) -> EventHandler {
         let (sender, receiver) = unbounded();
-        let worker = EventWorker::run("event_worker", sender).unwrap();
+        let worker = EventWorker::run("event_worker", sender, keys).unwrap();
         Self { receiver, worker }
     }
 }
@@ -40,7 +277,6 @@ impl Drop for EventHandler {
 }
 
 pub struct EventWorker {
-    name: String,
     thread: Option<JoinHandle<()>>,
     sender: Sender<Action>,
 }
@@ -49,33 +285,53 @@ impl EventWorker {
     pub fn run(
         name: impl Into<String>,
         sender: Sender<Action>,
+        keys: KeyMap,
     ) -> Result<EventWorker, Box<dyn Error>> {
         let (tx, rx) = unbounded();
-        let name = name.into();
         let handle = thread::Builder::new()
-            .name(name.clone())
-            .spawn(move || loop {
-                if poll(Duration::from_millis(100)).unwrap() {
-                    match read().unwrap() {
-                        Event::Key(KeyEvent {
-                            code: KeyCode::Char('q'),
-                            modifiers: KeyModifiers::NONE,
-                        }) => sender.send(Action::Exit).unwrap(),
-                        Event::Key(KeyEvent {
-                            code: KeyCode::Char('c'),
-                            modifiers: KeyModifiers::NONE,
-                        }) => sender.send(Action::Clear).unwrap_or(()),
-                        _ => (),
-                    };
-                }
-                if let Ok(Action::Exit) = rx.try_recv() {
-                    break;
+            .name(name.into())
+            .spawn(move || {
+                // while the input line has focus keys are handed to it
+                // instead of being treated as commands
+                let mut editing = false;
+                loop {
+                    if poll(Duration::from_millis(100)).unwrap() {
+                        match read().unwrap() {
+                            Event::Key(key) if editing => {
+                                let action = match key.code {
+                                    KeyCode::Esc => {
+                                        editing = false;
+                                        Action::Unfocus
+                                    }
+                                    KeyCode::PageUp => Action::PageUp,
+                                    KeyCode::PageDown => Action::PageDown,
+                                    _ => Action::Input(key),
+                                };
+                                sender.send(action).unwrap_or(())
+                            }
+                            Event::Key(key) => {
+                                let action = keys.action(key);
+                                if action == Some(Action::Focus) {
+                                    editing = true;
+                                }
+                                if let Some(action) = action {
+                                    sender.send(action).unwrap_or(())
+                                }
+                            }
+                            Event::Resize(width, height) => {
+                                sender.send(Action::Resize(width, height)).unwrap_or(())
+                            }
+                            _ => (),
+                        };
+                    }
+                    if let Ok(Action::Exit) = rx.try_recv() {
+                        break;
+                    }
                 }
             })
             .unwrap();
 
         Ok(EventWorker {
-            name,
             thread: Some(handle),
             sender: tx,
         })
@@ -95,3 +351,103 @@ impl DerefMut for EventHandler {
         &mut self.worker
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers }
+    }
+
+    #[test]
+    fn should_rebind_actions() {
+        let mut keys = KeyMap::default();
+        assert_eq!(
+            keys.action(key(KeyCode::BackTab, KeyModifiers::SHIFT)),
+            Some(Action::PrevTab)
+        );
+        keys.bind("scroll_up", &["k", "ctrl+p", "Up"]).unwrap();
+        keys.bind("quit", &["Q"]).unwrap();
+        assert_eq!(
+            keys.action(key(KeyCode::Char('k'), KeyModifiers::NONE)),
+            Some(Action::ScrollUp)
+        );
+        assert_eq!(
+            keys.action(key(KeyCode::Char('p'), KeyModifiers::CONTROL)),
+            Some(Action::ScrollUp)
+        );
+        assert_eq!(
+            keys.action(key(KeyCode::Char('Q'), KeyModifiers::SHIFT)),
+            Some(Action::Exit)
+        );
+        assert_eq!(
+            keys.action(key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            None
+        );
+        assert_eq!(
+            keys.action(key(KeyCode::Char('p'), KeyModifiers::NONE)),
+            Some(Action::JumpToParent)
+        );
+    }
+
+    #[test]
+    fn should_refuse_keys_of_other_actions() {
+        let mut keys = KeyMap::default();
+        assert_eq!(
+            keys.bind("scroll_up", &["k", "q"]),
+            Err("key `q` is already bound to `quit`".to_string())
+        );
+        assert_eq!(
+            keys.bind("quit", &[]),
+            Err("`quit` needs at least one key".to_string())
+        );
+        assert_eq!(keys, KeyMap::default());
+
+        // keys can be swapped once they are taken from their action
+        keys.unbind("quit").unwrap();
+        keys.unbind("clear").unwrap();
+        keys.bind("clear", &["q"]).unwrap();
+        keys.bind("quit", &["c"]).unwrap();
+        assert_eq!(
+            keys.action(key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            Some(Action::Clear)
+        );
+        assert_eq!(
+            keys.action(key(KeyCode::Char('c'), KeyModifiers::NONE)),
+            Some(Action::Exit)
+        );
+    }
+
+    #[test]
+    fn should_write_keys_like_the_config() {
+        let mut keys = KeyMap::default();
+        assert_eq!(keys.key(&Action::Focus), Some("i".to_string()));
+        assert_eq!(keys.key(&Action::PrevTab), Some("shift+tab".to_string()));
+        keys.bind("input", &["ctrl+alt+w", "F2"]).unwrap();
+        assert_eq!(keys.key(&Action::Focus), Some("ctrl+alt+w".to_string()));
+        keys.bind("input", &[]).unwrap();
+        assert_eq!(keys.key(&Action::Focus), None);
+    }
+
+    #[test]
+    fn should_reject_unknown_keys_and_actions() {
+        let mut keys = KeyMap::default();
+        assert_eq!(
+            keys.bind("quit", &["hyper+q"]),
+            Err("invalid key `hyper+q`".to_string())
+        );
+        assert!(keys
+            .bind("explode", &["x"])
+            .unwrap_err()
+            .starts_with("unknown action `explode`"));
+        assert_eq!(
+            parse_key("ctrl++"),
+            Ok((KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("shift+tab"),
+            Ok((KeyCode::BackTab, KeyModifiers::NONE))
+        );
+    }
+}
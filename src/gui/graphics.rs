@@ -0,0 +1,405 @@
+use std::{
+    any::type_name,
+    collections::HashMap,
+    fmt::{self, Debug},
+    sync::{Arc, Mutex},
+};
+
+use crate::{emote_cache::EmoteCache, log::get_logger};
+
+/// Emote images take up this many cells, on a single row.
+pub const IMAGE_COLUMNS: u16 = 2;
+
+/// Sixel has to be told the size in pixels, cells are assumed to be this big.
+const CELL_WIDTH_PX: u32 = 10;
+const CELL_HEIGHT_PX: u32 = 20;
+
+/// The kitty protocol only takes base64 in pieces of at most this size.
+const KITTY_CHUNK: usize = 4096;
+
+/// Images are drawn in place of characters from a private use plane, one
+/// for every emote.
+const FIRST_PLACEHOLDER: u32 = 0xF0000;
+const LAST_PLACEHOLDER: u32 = 0xFFFFD;
+
+/// Keeps the placeholder and the cell after it together when a message is
+/// wrapped, the image covers both.
+const PADDING: char = '\u{a0}';
+
+/// The ways a terminal can be asked to show an image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    ITerm2,
+    Sixel,
+}
+
+impl Protocol {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "kitty" => Some(Protocol::Kitty),
+            "iterm" | "iterm2" => Some(Protocol::ITerm2),
+            "sixel" => Some(Protocol::Sixel),
+            _ => None,
+        }
+    }
+
+    /// Guesses the protocol from the environment of the terminal.
+    pub fn detect() -> Option<Self> {
+        Self::detect_from(|name| std::env::var(name).ok())
+    }
+
+    pub fn detect_from(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some() || term.contains("kitty") {
+            Some(Protocol::Kitty)
+        } else if program == "iTerm.app" || program == "WezTerm" {
+            Some(Protocol::ITerm2)
+        } else if term.contains("sixel") || term.starts_with("mlterm") || term.starts_with("foot") {
+            Some(Protocol::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// An emote encoded for the terminal. `setup` is written once before the
+/// image is first drawn, `draw` every time it is placed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub id: String,
+    pub setup: Option<String>,
+    pub draw: String,
+}
+
+/// Hands out placeholders for emotes that are in the cache and remembers
+/// which image belongs to which placeholder. Clones share the same state,
+/// so the chat and the screen agree on the placeholders.
+#[derive(Clone)]
+pub struct EmoteImages {
+    protocol: Protocol,
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    cache: EmoteCache,
+    /// Placeholder per emote id, `None` when the image could not be used.
+    placeholders: HashMap<String, Option<char>>,
+    images: HashMap<char, Image>,
+}
+
+impl EmoteImages {
+    pub fn new(protocol: Protocol, cache: EmoteCache) -> Self {
+        Self {
+            protocol,
+            inner: Arc::new(Mutex::new(Inner {
+                cache,
+                placeholders: HashMap::new(),
+                images: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The text to put in a message instead of the emote, as long as the
+    /// image is cached. Missing images are requested so that they can be
+    /// shown the next time the message is drawn.
+    pub fn placeholder(&self, id: &str) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(placeholder) = inner.placeholders.get(id) {
+            return placeholder.map(|c| format!("{}{}", c, PADDING));
+        }
+        let png = match inner.cache.get(id) {
+            Some(png) => png,
+            None => {
+                inner.cache.request(id);
+                return None;
+            }
+        };
+
+        let number = FIRST_PLACEHOLDER + inner.images.len() as u32;
+        let placeholder = char::from_u32(number).filter(|_| number <= LAST_PLACEHOLDER)?;
+        let image = match encode(self.protocol, id, inner.images.len() as u32 + 1, &png) {
+            Ok(image) => Some(image),
+            Err(err) => {
+                get_logger().warn(
+                    format!("Could not use image of emote {}: {}", id, err),
+                    type_name::<Self>(),
+                );
+                None
+            }
+        };
+        let placeholder = image.map(|image| {
+            inner.images.insert(placeholder, image);
+            placeholder
+        });
+        inner.placeholders.insert(id.to_string(), placeholder);
+        placeholder.map(|c| format!("{}{}", c, PADDING))
+    }
+
+    /// The image drawn in place of a character, if it is a placeholder.
+    pub fn image(&self, c: char) -> Option<Image> {
+        if !(FIRST_PLACEHOLDER..=LAST_PLACEHOLDER).contains(&(c as u32)) {
+            return None;
+        }
+        self.inner.lock().unwrap().images.get(&c).cloned()
+    }
+}
+
+impl Debug for EmoteImages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmoteImages")
+            .field("protocol", &self.protocol)
+            .finish()
+    }
+}
+
+impl PartialEq for EmoteImages {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for EmoteImages {}
+
+fn encode(protocol: Protocol, id: &str, number: u32, png: &[u8]) -> Result<Image, String> {
+    let image = match protocol {
+        Protocol::Kitty => Image {
+            id: id.to_string(),
+            setup: Some(kitty_transmit(number, png)),
+            draw: kitty_place(number),
+        },
+        Protocol::ITerm2 => Image {
+            id: id.to_string(),
+            setup: None,
+            draw: iterm(png),
+        },
+        Protocol::Sixel => Image {
+            id: id.to_string(),
+            setup: None,
+            draw: sixel(png)?,
+        },
+    };
+    Ok(image)
+}
+
+/// Sends the png to kitty under `number`, without showing it yet.
+pub fn kitty_transmit(number: u32, png: &[u8]) -> String {
+    let data = base64::encode(png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        if i == 0 {
+            out.push_str(&format!(
+                "\x1b_Ga=t,i={},f=100,q=2,m={};{}\x1b\\",
+                number, more, chunk
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    out
+}
+
+/// Shows a transmitted image at the cursor, which is left where it is.
+pub fn kitty_place(number: u32) -> String {
+    format!(
+        "\x1b_Ga=p,i={},c={},r=1,C=1,q=2\x1b\\",
+        number, IMAGE_COLUMNS
+    )
+}
+
+/// Removes every image kitty shows, the transmitted data is kept.
+pub fn kitty_clear() -> &'static str {
+    "\x1b_Ga=d,d=a,q=2\x1b\\"
+}
+
+pub fn iterm(png: &[u8]) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;width={};height=1;preserveAspectRatio=1:{}\x07",
+        IMAGE_COLUMNS,
+        base64::encode(png)
+    )
+}
+
+/// Scales the png to the cells it covers and turns it into sixels, using a
+/// palette of 216 colours. Mostly transparent pixels are left out.
+pub fn sixel(png: &[u8]) -> Result<String, String> {
+    let (width, height, rgba) = decode(png)?;
+    let (w, h) = (IMAGE_COLUMNS as u32 * CELL_WIDTH_PX, CELL_HEIGHT_PX);
+    let pixel = |x: u32, y: u32| -> Option<usize> {
+        let (sx, sy) = (x * width / w, y * height / h);
+        let i = ((sy * width + sx) * 4) as usize;
+        let level = |c: u8| (c as usize * 5 + 127) / 255;
+        (rgba[i + 3] >= 128)
+            .then(|| level(rgba[i]) * 36 + level(rgba[i + 1]) * 6 + level(rgba[i + 2]))
+    };
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", w, h);
+    let mut used = [false; 216];
+    for y in 0..h {
+        for x in 0..w {
+            if let Some(color) = pixel(x, y) {
+                used[color] = true;
+            }
+        }
+    }
+    for (color, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+        let percent = |level: usize| level * 100 / 5;
+        out.push_str(&format!(
+            "#{};2;{};{};{}",
+            color,
+            percent(color / 36),
+            percent(color / 6 % 6),
+            percent(color % 6)
+        ));
+    }
+
+    for band in (0..h).step_by(6) {
+        let mut first = true;
+        for (color, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            let row: Vec<u8> = (0..w)
+                .map(|x| {
+                    (0..6)
+                        .filter(|dy| band + dy < h && pixel(x, band + dy) == Some(color))
+                        .fold(0, |bits, dy| bits | 1 << dy)
+                })
+                .collect();
+            if row.iter().all(|&bits| bits == 0) {
+                continue;
+            }
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            out.push_str(&format!("#{}", color));
+            let mut x = 0;
+            while x < row.len() {
+                let run = row[x..].iter().take_while(|&&bits| bits == row[x]).count();
+                let c = (63 + row[x]) as char;
+                if run > 3 {
+                    out.push_str(&format!("!{}{}", run, c));
+                } else {
+                    (0..run).for_each(|_| out.push(c));
+                }
+                x += run;
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    Ok(out)
+}
+
+/// Decodes a png into 8 bit rgba pixels.
+fn decode(png: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|err| err.to_string())?;
+    let pixels = &buffer[..info.buffer_size()];
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+        png::ColorType::Indexed => return Err("palette was not expanded".to_string()),
+    };
+    if info.width == 0 || info.height == 0 {
+        return Err("image is empty".to_string());
+    }
+    Ok((info.width, info.height, rgba))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    fn cache() -> EmoteCache {
+        EmoteCache::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/emotes"))
+    }
+
+    #[test]
+    fn should_detect_protocol_from_environment() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            Protocol::detect_from(env(&[("TERM", "xterm-kitty")])),
+            Some(Protocol::Kitty)
+        );
+        assert_eq!(
+            Protocol::detect_from(env(&[("TERM", "xterm"), ("TERM_PROGRAM", "WezTerm")])),
+            Some(Protocol::ITerm2)
+        );
+        assert_eq!(
+            Protocol::detect_from(env(&[("TERM", "foot")])),
+            Some(Protocol::Sixel)
+        );
+        assert_eq!(Protocol::detect_from(env(&[("TERM", "xterm")])), None);
+    }
+
+    #[test]
+    fn should_chunk_kitty_transmissions() {
+        let png = vec![0; KITTY_CHUNK];
+        let transmit = kitty_transmit(7, &png);
+        assert!(transmit.starts_with("\x1b_Ga=t,i=7,f=100,q=2,m=1;AAAA"));
+        assert_eq!(transmit.matches("\x1b_G").count(), 2);
+        assert!(transmit.contains("\x1b\\\x1b_Gm=0;"));
+        assert_eq!(kitty_place(7), "\x1b_Ga=p,i=7,c=2,r=1,C=1,q=2\x1b\\");
+    }
+
+    #[test]
+    fn should_encode_fixture_as_sixel_and_iterm() {
+        let png = cache().get("25").unwrap();
+        let sixel = sixel(&png).unwrap();
+        assert!(sixel.starts_with("\x1bP0;1;0q"));
+        assert!(sixel.ends_with("-\x1b\\"));
+        // top half is red then green, the transparent quarter is skipped
+        assert!(sixel.contains(
+            "q\"1;1;20;20#5;2;0;0;100#30;2;0;100;0#180;2;100;0;0#30!10?!10~$#180!10~!10?-"
+        ));
+        assert_eq!(sixel.matches('-').count(), 4);
+
+        let iterm = iterm(&png);
+        assert!(iterm.starts_with(
+            "\x1b]1337;File=inline=1;width=2;height=1;preserveAspectRatio=1:iVBORw0KGgo"
+        ));
+        assert!(iterm.ends_with('\x07'));
+    }
+
+    #[test]
+    fn should_only_hand_out_placeholders_for_cached_emotes() {
+        let images = EmoteImages::new(Protocol::Kitty, cache());
+        let placeholder = images.placeholder("25").unwrap();
+        assert_eq!(placeholder, "\u{F0000}\u{a0}");
+        assert_eq!(images.clone().placeholder("25").unwrap(), placeholder);
+        assert_eq!(images.placeholder("1902"), None);
+
+        let image = images.image('\u{F0000}').unwrap();
+        assert_eq!(image.id, "25");
+        assert_eq!(image.draw, kitty_place(1));
+        assert_eq!(images.image('a'), None);
+    }
+}
@@ -0,0 +1,192 @@
+use crossterm::style::Color;
+use regex::Regex;
+
+use super::buffer::Style;
+use crate::parser::command::PrivMsg;
+
+/// What a user defined rule looks for in a message.
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// A whole word, ignoring case.
+    Keyword(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            Matcher::Keyword(keyword) => contains_word(text, keyword),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub matcher: Matcher,
+    pub style: Style,
+}
+
+/// Decides which messages stand out and how. The built in highlights are
+/// checked first, mentions before replies to the broadcaster before first
+/// messages, then the user rules in the order they were added. The first
+/// one that matches wins, a built in highlight set to `None` is skipped.
+#[derive(Debug, Clone)]
+pub struct Highlights {
+    nick: Option<String>,
+    pub mention: Option<Style>,
+    pub broadcaster_reply: Option<Style>,
+    pub first_msg: Option<Style>,
+    rules: Vec<Rule>,
+}
+
+impl Default for Highlights {
+    fn default() -> Self {
+        let on = |bg| Some(Style::new(Some(Color::White), Some(bg)));
+        Self {
+            nick: None,
+            mention: on(Color::DarkRed),
+            broadcaster_reply: on(Color::DarkBlue),
+            first_msg: on(Color::DarkGreen),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Highlights {
+    /// The style user rules get when none is given.
+    pub fn rule_style() -> Style {
+        Style::new(Some(Color::Black), Some(Color::Yellow))
+    }
+
+    /// Our own nick, messages that mention it are highlighted.
+    pub fn set_nick(&mut self, nick: impl Into<String>) {
+        self.nick = Some(nick.into());
+    }
+
+    pub fn add_keyword(&mut self, keyword: impl Into<String>, style: Style) {
+        self.rules.push(Rule {
+            matcher: Matcher::Keyword(keyword.into()),
+            style,
+        });
+    }
+
+    pub fn add_regex(&mut self, pattern: &str, style: Style) -> Result<(), String> {
+        let regex = Regex::new(pattern).map_err(|err| format!("invalid highlight: {}", err))?;
+        self.rules.push(Rule {
+            matcher: Matcher::Regex(regex),
+            style,
+        });
+        Ok(())
+    }
+
+    /// The style a message is highlighted with, if any.
+    pub fn style(&self, message: &PrivMsg) -> Option<Style> {
+        let meta_data = &message.meta_data;
+        let mentioned = self.nick.as_deref().is_some_and(|nick| {
+            contains_word(message.message, nick)
+                || meta_data.reply.user_login == Some(nick.to_lowercase().as_str())
+        });
+        // the room id is the user id of the broadcaster
+        let broadcaster_reply = meta_data.reply.user_id == Some(meta_data.room_id);
+
+        let builtin = [
+            (mentioned, self.mention),
+            (broadcaster_reply, self.broadcaster_reply),
+            (meta_data.first_msg, self.first_msg),
+        ];
+        builtin
+            .into_iter()
+            .find_map(|(applies, style)| style.filter(|_| applies))
+            .or_else(|| {
+                self.rules
+                    .iter()
+                    .find(|rule| rule.matcher.matches(message.message))
+                    .map(|rule| rule.style)
+            })
+    }
+}
+
+/// Lays a highlight over the style of a piece of text. Colours the piece
+/// set itself, like the one of an unmapped emote, are kept.
+pub fn apply(highlight: Style, style: Style) -> Style {
+    let pick = |own: Option<Color>, over: Option<Color>| match own {
+        None | Some(Color::Reset) => over,
+        own => own,
+    };
+    Style {
+        fg: pick(style.fg, highlight.fg),
+        bg: pick(style.bg, highlight.bg),
+        attributes: style.attributes | highlight.attributes,
+    }
+}
+
+/// Whether `word` shows up in `text` on its own, `@word` counts as well.
+fn contains_word(text: &str, word: &str) -> bool {
+    !word.is_empty()
+        && text
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .any(|part| part.to_lowercase() == word.to_lowercase())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{chat_message::ChatMessage, command::Command};
+
+    fn style(highlights: &Highlights, tags: &str, text: &str) -> Option<Style> {
+        let line = format!("@{};id=1;room-id=1;tmi-sent-ts=1500000000;user-id=10 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :{}", tags, text);
+        match ChatMessage::parse(&line).unwrap().command {
+            Command::PrivMsg(message) => highlights.style(&message),
+            command => panic!("expected PRIVMSG, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn should_pick_first_matching_highlight() {
+        let mut highlights = Highlights::default();
+        highlights.set_nick("Toerktumlare");
+        let green = Style::fg(Some(Color::Green));
+        highlights.add_keyword("rust", green);
+        highlights
+            .add_regex(r"^!\w+", Highlights::rule_style())
+            .unwrap();
+
+        assert_eq!(style(&highlights, "first-msg=0", "hi all"), None);
+        assert_eq!(
+            style(&highlights, "first-msg=1", "hi @toerktumlare"),
+            highlights.mention
+        );
+        assert_eq!(
+            style(&highlights, "reply-parent-user-id=1", "yes"),
+            highlights.broadcaster_reply
+        );
+        assert_eq!(
+            style(&highlights, "first-msg=1", "hi"),
+            highlights.first_msg
+        );
+        assert_eq!(
+            style(&highlights, "first-msg=0", "I like Rust!"),
+            Some(green)
+        );
+        assert_eq!(style(&highlights, "first-msg=0", "trusty"), None);
+        assert_eq!(
+            style(&highlights, "first-msg=0", "!uptime"),
+            Some(Highlights::rule_style())
+        );
+
+        highlights.first_msg = None;
+        assert_eq!(style(&highlights, "first-msg=1", "hi"), None);
+        assert!(highlights.add_regex("(", Style::none()).is_err());
+    }
+
+    #[test]
+    fn should_keep_own_colours_under_highlight() {
+        let highlight = Style::new(Some(Color::White), Some(Color::DarkRed));
+        assert_eq!(apply(highlight, Style::none()), highlight);
+        assert_eq!(
+            apply(highlight, Style::fg(Some(Color::Magenta))),
+            Style::new(Some(Color::Magenta), Some(Color::DarkRed))
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::io::Write;
+
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    style::{Attribute, Color},
+};
+use unicode_width::UnicodeWidthChar;
+
+use super::{
+    buffer::{Cell, Style},
+    event_handler::{Action, KeyMap},
+    screen::Screen,
+    Pos, Size,
+};
+
+const MAX_HISTORY: usize = 100;
+const PROMPT: &str = "> ";
+
+/// A single line at the bottom of the screen where messages are typed.
+pub struct InputWidget {
+    pos: Pos,
+    size: Size,
+    line: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    recall: Option<usize>,
+    draft: Vec<char>,
+    focused: bool,
+    prompt: String,
+    hint: String,
+}
+
+impl InputWidget {
+    /// The hint shown in the empty line names the key that focuses it.
+    pub fn new(pos: Pos, size: Size, keys: &KeyMap) -> Self {
+        Self {
+            pos,
+            size,
+            line: Vec::new(),
+            cursor: 0,
+            history: Vec::new(),
+            recall: None,
+            draft: Vec::new(),
+            focused: false,
+            prompt: PROMPT.to_string(),
+            hint: keys
+                .key(&Action::Focus)
+                .map(|key| format!("press {} to write a message", key))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn resize(&mut self, pos: Pos, size: Size) {
+        self.pos = pos;
+        self.size = size;
+    }
+
+    pub fn focus(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Replaces the `> ` in front of the line, for instance with the channel
+    /// the message goes to.
+    pub fn set_prompt(&mut self, prompt: impl Into<String>) {
+        self.prompt = prompt.into();
+    }
+
+    pub fn text(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    /// Edits the line according to the key, returns the text once enter is
+    /// pressed on a line that is not blank.
+    pub fn handle(&mut self, key: KeyEvent) -> Option<String> {
+        match key.code {
+            KeyCode::Char(c)
+                if key.modifiers == KeyModifiers::NONE || key.modifiers == KeyModifiers::SHIFT =>
+            {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.line.len(),
+            KeyCode::Up => self.recall_older(),
+            KeyCode::Down => self.recall_newer(),
+            KeyCode::Enter => return self.submit(),
+            _ => (),
+        }
+        None
+    }
+
+    fn submit(&mut self) -> Option<String> {
+        let text = self.text().trim().to_string();
+        self.line.clear();
+        self.cursor = 0;
+        self.recall = None;
+        if text.is_empty() {
+            return None;
+        }
+
+        if self.history.last() != Some(&text) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(text.clone());
+        }
+        Some(text)
+    }
+
+    fn recall_older(&mut self) {
+        let index = match self.recall {
+            _ if self.history.is_empty() => return,
+            None => {
+                self.draft = self.line.clone();
+                self.history.len() - 1
+            }
+            Some(index) => index.saturating_sub(1),
+        };
+        self.set_recalled(Some(index));
+    }
+
+    fn recall_newer(&mut self) {
+        match self.recall {
+            None => (),
+            Some(index) if index + 1 < self.history.len() => self.set_recalled(Some(index + 1)),
+            Some(_) => self.set_recalled(None),
+        }
+    }
+
+    fn set_recalled(&mut self, index: Option<usize>) {
+        self.recall = index;
+        self.line = match index {
+            Some(index) => self.history[index].chars().collect(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.cursor = self.line.len();
+    }
+
+    pub fn draw(&self, screen: &mut Screen<impl Write>) {
+        screen.erase_region(self.pos, self.size);
+        let width = self.size.width() as usize;
+
+        if !self.focused && self.line.is_empty() {
+            let style = Style::fg(Some(Color::DarkGrey));
+            self.put_str(screen, 0, &self.prompt, style);
+            self.put_str(screen, self.prompt_width(), &self.hint, style);
+            return;
+        }
+
+        let prompt_style = if self.focused {
+            Style::fg(Some(Color::Green))
+        } else {
+            Style::fg(Some(Color::DarkGrey))
+        };
+        self.put_str(screen, 0, &self.prompt, prompt_style);
+
+        // scroll the text sideways so that the cursor is always visible
+        let room = width.saturating_sub(self.prompt_width() + 1);
+        let mut start = self.cursor;
+        let mut used = 0;
+        while start > 0 {
+            let w = self.line[start - 1].width().unwrap_or(1);
+            if used + w > room {
+                break;
+            }
+            used += w;
+            start -= 1;
+        }
+
+        let mut x = self.prompt_width();
+        for (i, &c) in self.line.iter().enumerate().skip(start) {
+            let w = c.width().unwrap_or(1);
+            if x + w > width {
+                break;
+            }
+            self.put(screen, x, c, self.cell_style(i));
+            x += w;
+        }
+        if self.focused && self.cursor == self.line.len() && x < width {
+            self.put(screen, x, ' ', self.cell_style(self.cursor));
+        }
+    }
+
+    fn prompt_width(&self) -> usize {
+        self.prompt.chars().count()
+    }
+
+    fn cell_style(&self, index: usize) -> Style {
+        if self.focused && index == self.cursor {
+            Style::none().attributes(Attribute::Reverse)
+        } else {
+            Style::none()
+        }
+    }
+
+    fn put_str(&self, screen: &mut Screen<impl Write>, x: usize, s: &str, style: Style) {
+        for (i, c) in s.chars().enumerate() {
+            if x + i < self.size.width() as usize {
+                self.put(screen, x + i, c, style);
+            }
+        }
+    }
+
+    fn put(&self, screen: &mut Screen<impl Write>, x: usize, c: char, style: Style) {
+        screen.put(Cell::new(c, style), self.pos + Pos::new(x as u16, 0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gui::buffer::CellState;
+
+    fn typed(input: &mut InputWidget, s: &str) {
+        for c in s.chars() {
+            input.handle(KeyCode::Char(c).into());
+        }
+    }
+
+    #[test]
+    fn should_edit_in_the_middle_of_the_line() {
+        let mut input = InputWidget::new(Pos::zero(), Size::new(20, 1), &KeyMap::default());
+        typed(&mut input, "helo");
+        input.handle(KeyCode::Left.into());
+        typed(&mut input, "l");
+        input.handle(KeyCode::Home.into());
+        input.handle(KeyCode::Delete.into());
+        typed(&mut input, "H");
+        input.handle(KeyCode::End.into());
+        input.handle(KeyCode::Backspace.into());
+        assert_eq!(input.text(), "Hell");
+        assert_eq!(
+            input.handle(KeyCode::Enter.into()),
+            Some("Hell".to_string())
+        );
+        assert_eq!(input.text(), "");
+        assert_eq!(input.handle(KeyCode::Enter.into()), None);
+    }
+
+    #[test]
+    fn should_recall_history_and_keep_draft() {
+        let mut input = InputWidget::new(Pos::zero(), Size::new(20, 1), &KeyMap::default());
+        typed(&mut input, "first");
+        input.handle(KeyCode::Enter.into());
+        typed(&mut input, "second");
+        input.handle(KeyCode::Enter.into());
+        typed(&mut input, "dra");
+
+        input.handle(KeyCode::Up.into());
+        assert_eq!(input.text(), "second");
+        input.handle(KeyCode::Up.into());
+        input.handle(KeyCode::Up.into());
+        assert_eq!(input.text(), "first");
+        input.handle(KeyCode::Down.into());
+        assert_eq!(input.text(), "second");
+        input.handle(KeyCode::Down.into());
+        assert_eq!(input.text(), "dra");
+    }
+
+    #[test]
+    fn should_name_the_bound_key_in_the_hint() {
+        let mut keys = KeyMap::default();
+        keys.bind("input", &["w"]).unwrap();
+        let mut screen = Screen::new(Vec::new(), Size::new(30, 1)).unwrap();
+        InputWidget::new(Pos::zero(), Size::new(30, 1), &keys).draw(&mut screen);
+
+        let row: String = screen
+            .buffer()
+            .inner
+            .iter()
+            .map(|cell| match cell.cell_state {
+                CellState::Occupied(c) => c,
+                _ => ' ',
+            })
+            .collect();
+        assert_eq!(row, "> press w to write a message  ");
+    }
+
+    #[test]
+    fn should_keep_cursor_visible_on_long_lines() {
+        let mut screen = Screen::new(Vec::new(), Size::new(8, 1)).unwrap();
+        let mut input = InputWidget::new(Pos::zero(), Size::new(8, 1), &KeyMap::default());
+        input.focus(true);
+        typed(&mut input, "abcdefghij");
+        input.draw(&mut screen);
+
+        let row: String = screen
+            .buffer()
+            .inner
+            .iter()
+            .map(|cell| match cell.cell_state {
+                CellState::Occupied(c) => c,
+                _ => ' ',
+            })
+            .collect();
+        assert_eq!(row, "> fghij ");
+        assert!(screen.buffer().inner[7]
+            .style
+            .attributes
+            .has(Attribute::Reverse));
+    }
+}
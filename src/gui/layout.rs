@@ -0,0 +1,178 @@
+use std::{collections::HashMap, hash::Hash};
+
+use super::{Pos, Size};
+
+/// How much room a pane gets along the direction of its split.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    /// Exactly this many rows or columns, as long as there is room.
+    Fixed(u16),
+    /// A share of whatever the fixed panes leave, weighted against the other
+    /// ratios of the same split.
+    Ratio(u16),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// Panes are placed next to each other.
+    Horizontal,
+    /// Panes are stacked on top of each other.
+    Vertical,
+}
+
+/// The part of the screen a pane ended up with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub pos: Pos,
+    pub size: Size,
+}
+
+impl Region {
+    pub fn new(pos: Pos, size: Size) -> Self {
+        Self { pos, size }
+    }
+
+    /// A region without any room, for panes that are not shown.
+    pub fn empty() -> Self {
+        Self::new(Pos::zero(), Size::new(0, 0))
+    }
+}
+
+/// A tree of splits with the panes, identified by `K`, as its leaves.
+pub enum Layout<K> {
+    Pane(K),
+    Split(Direction, Vec<(Constraint, Layout<K>)>),
+}
+
+impl<K: Copy + Eq + Hash> Layout<K> {
+    pub fn horizontal(children: Vec<(Constraint, Layout<K>)>) -> Self {
+        Layout::Split(Direction::Horizontal, children)
+    }
+
+    pub fn vertical(children: Vec<(Constraint, Layout<K>)>) -> Self {
+        Layout::Split(Direction::Vertical, children)
+    }
+
+    /// Divides the area between the panes.
+    pub fn regions(&self, pos: Pos, size: Size) -> HashMap<K, Region> {
+        let mut regions = HashMap::new();
+        self.place(Region::new(pos, size), &mut regions);
+        regions
+    }
+
+    fn place(&self, region: Region, regions: &mut HashMap<K, Region>) {
+        let (direction, children) = match self {
+            Layout::Pane(key) => {
+                regions.insert(*key, region);
+                return;
+            }
+            Layout::Split(direction, children) => (direction, children),
+        };
+
+        let length = match direction {
+            Direction::Horizontal => region.size.width(),
+            Direction::Vertical => region.size.height(),
+        };
+        let constraints: Vec<Constraint> = children.iter().map(|(c, _)| *c).collect();
+
+        let mut offset = 0;
+        for (length, (_, child)) in split(length, &constraints).into_iter().zip(children) {
+            let child_region = match direction {
+                Direction::Horizontal => Region::new(
+                    region.pos + Pos::new(offset, 0),
+                    Size::new(length, region.size.height()),
+                ),
+                Direction::Vertical => Region::new(
+                    region.pos + Pos::new(0, offset),
+                    Size::new(region.size.width(), length),
+                ),
+            };
+            child.place(child_region, regions);
+            offset += length;
+        }
+    }
+}
+
+/// Hands out `length` rows or columns, fixed sizes are served first in order
+/// and the rest is shared by the ratios. Rounding leftovers go to the earlier
+/// ratios so that the lengths always add up.
+pub fn split(length: u16, constraints: &[Constraint]) -> Vec<u16> {
+    let mut left = length;
+    let mut lengths: Vec<u16> = constraints
+        .iter()
+        .map(|constraint| match constraint {
+            Constraint::Fixed(fixed) => {
+                let fixed = (*fixed).min(left);
+                left -= fixed;
+                fixed
+            }
+            Constraint::Ratio(_) => 0,
+        })
+        .collect();
+
+    let total: u32 = constraints
+        .iter()
+        .map(|constraint| match constraint {
+            Constraint::Ratio(weight) => *weight as u32,
+            Constraint::Fixed(_) => 0,
+        })
+        .sum();
+    if total == 0 {
+        return lengths;
+    }
+
+    let mut weight_so_far = 0;
+    let mut given = 0;
+    for (length, constraint) in lengths.iter_mut().zip(constraints) {
+        if let Constraint::Ratio(weight) = constraint {
+            weight_so_far += *weight as u32;
+            let end = (left as u32 * weight_so_far).div_ceil(total) as u16;
+            *length = end - given;
+            given = end;
+        }
+    }
+    lengths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_serve_fixed_before_ratios() {
+        use Constraint::*;
+        assert_eq!(split(10, &[Fixed(1), Ratio(1), Fixed(2)]), vec![1, 7, 2]);
+        assert_eq!(split(10, &[Ratio(1), Ratio(1), Ratio(1)]), vec![4, 3, 3]);
+        assert_eq!(split(10, &[Ratio(3), Ratio(1)]), vec![8, 2]);
+        assert_eq!(split(2, &[Fixed(1), Fixed(5), Ratio(1)]), vec![1, 1, 0]);
+        assert_eq!(split(5, &[Ratio(0), Fixed(2)]), vec![0, 2]);
+    }
+
+    #[test]
+    fn should_place_nested_panes() {
+        use Constraint::*;
+        let layout = Layout::vertical(vec![
+            (
+                Ratio(1),
+                Layout::horizontal(vec![
+                    (Ratio(3), Layout::Pane("chat")),
+                    (Fixed(20), Layout::Pane("users")),
+                ]),
+            ),
+            (Fixed(1), Layout::Pane("status")),
+        ]);
+        let regions = layout.regions(Pos::new(0, 1), Size::new(80, 24));
+        assert_eq!(
+            regions["chat"],
+            Region::new(Pos::new(0, 1), Size::new(60, 23))
+        );
+        assert_eq!(
+            regions["users"],
+            Region::new(Pos::new(60, 1), Size::new(20, 23))
+        );
+        assert_eq!(
+            regions["status"],
+            Region::new(Pos::new(0, 24), Size::new(80, 1))
+        );
+    }
+}
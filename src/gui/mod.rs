@@ -1,14 +1,21 @@
-#![allow(dead_code)]
 use std::{
     fmt::Display,
     ops::{Add, AddAssign, Sub, SubAssign},
 };
 
+pub mod badges;
 pub mod buffer;
 pub mod chat_widget;
-pub mod error;
+pub mod emotes;
 pub mod event_handler;
+#[cfg(feature = "images")]
+pub mod graphics;
+pub mod highlight;
+pub mod input_widget;
+pub mod layout;
 pub mod screen;
+pub mod status_bar;
+pub mod tab_bar;
 pub mod window;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -2,7 +2,10 @@ use std::io::{Result, Write};
 
 use crossterm::{
     cursor::{self, MoveTo},
-    style::{Color, Print, SetBackgroundColor, SetForegroundColor},
+    style::{
+        Attribute, Color, Print, SetAttribute, SetAttributes, SetBackgroundColor,
+        SetForegroundColor,
+    },
     terminal::{
         disable_raw_mode, enable_raw_mode, size as term_size, Clear, ClearType,
         EnterAlternateScreen, LeaveAlternateScreen,
@@ -10,8 +13,10 @@ use crossterm::{
     ExecutableCommand, QueueableCommand,
 };
 
+#[cfg(feature = "images")]
+use super::graphics::{self, EmoteImages, Protocol};
 use super::{
-    buffer::{Buffer, Cell, CellState},
+    buffer::{Buffer, Cell, CellState, Style},
     Pos, Size,
 };
 
@@ -22,6 +27,14 @@ pub struct Screen<W: Write> {
     size: Size,
     output: W,
     alt_screen: bool,
+    #[cfg(feature = "images")]
+    images: Option<EmoteImages>,
+    /// Where images were drawn on the last render, with their placeholder.
+    #[cfg(feature = "images")]
+    placed: Vec<(Pos, char)>,
+    /// Emotes the terminal already has the image data of.
+    #[cfg(feature = "images")]
+    transmitted: std::collections::HashSet<String>,
 }
 
 impl Screen<std::io::Stdout> {
@@ -43,13 +56,44 @@ impl<W: Write> Screen<W> {
             old_buffer: Buffer::new(size),
             size,
             alt_screen: false,
+            #[cfg(feature = "images")]
+            images: None,
+            #[cfg(feature = "images")]
+            placed: Vec::new(),
+            #[cfg(feature = "images")]
+            transmitted: std::collections::HashSet::new(),
         })
     }
 
+    /// Draws emote images over the cells that hold their placeholders.
+    #[cfg(feature = "images")]
+    pub fn set_images(&mut self, images: EmoteImages) {
+        self.images = Some(images);
+    }
+
     pub fn size(&self) -> Size {
         self.size
     }
 
+    /// Resizes both buffers and wipes the terminal, the old buffer is left
+    /// empty so that everything gets drawn again on the next render.
+    pub fn resize(&mut self, size: impl Into<Size>) -> Result<()> {
+        let size = size.into();
+        self.new_buffer.resize(size);
+        self.old_buffer = Buffer::new(size);
+        self.size = size;
+        #[cfg(feature = "images")]
+        if !std::mem::take(&mut self.placed).is_empty() {
+            self.output.queue(Print(graphics::kitty_clear()))?;
+        }
+        self.clear_all()
+    }
+
+    #[cfg(test)]
+    pub fn buffer(&self) -> &Buffer {
+        &self.new_buffer
+    }
+
     fn contains(&self, pos: Pos) -> bool {
         pos.x < self.size.width && pos.y < self.size.height
     }
@@ -73,11 +117,6 @@ impl<W: Write> Screen<W> {
         Ok(())
     }
 
-    pub fn disable_raw_mode(&self) -> Result<()> {
-        disable_raw_mode()?;
-        Ok(())
-    }
-
     pub fn clear_all(&mut self) -> Result<()> {
         self.output.execute(MoveTo(0, 0))?;
         self.output.execute(SetForegroundColor(Color::Reset))?;
@@ -97,37 +136,176 @@ impl<W: Write> Screen<W> {
         }
     }
 
-    pub fn scroll_up(&mut self, lines: usize) {
-        let count = lines * self.size.width as usize;
-        self.new_buffer.inner.drain(0..count);
-        let mut empty_line = vec![Cell::empty(); count];
-        self.new_buffer.inner.append(&mut empty_line);
+    /// Scrolls the cells inside a region up, the rows at the bottom of the
+    /// region are left empty.
+    pub fn scroll_region(&mut self, pos: Pos, size: Size, lines: u16) {
+        let to_x = (size.width + pos.x).min(self.size.width);
+        let to_y = (size.height + pos.y).min(self.size.height);
+        let width = self.size.width as usize;
+
+        for y in pos.y..to_y {
+            for x in pos.x..to_x {
+                let from = y + lines;
+                let cell = if from < to_y {
+                    self.new_buffer.inner[from as usize * width + x as usize]
+                } else {
+                    Cell::empty()
+                };
+                self.new_buffer.inner[y as usize * width + x as usize] = cell;
+            }
+        }
     }
 
+    /// Writes the cells that changed since the last render. Cursor moves are
+    /// only emitted when a run of changed cells is broken and colours and
+    /// attributes only when they differ from what the terminal already uses.
     pub fn render(&mut self) -> Result<()> {
-        for (y, column) in self.new_buffer.lines().enumerate() {
-            for (x, cell) in column.iter().enumerate() {
-                self.output.queue(cursor::MoveTo(x as u16, y as u16))?;
+        let width = self.size.width;
+        let mut cursor = None;
+        // the terminal is left in its default style after every render
+        let default = normalize(Style::new(None, None));
+        let mut current = default;
+        #[cfg(feature = "images")]
+        let mut changed = Vec::new();
 
-                if let Some(fg) = cell.style.fg {
-                    self.output.queue(SetForegroundColor(fg))?;
-                }
+        for (i, (new, old)) in self
+            .new_buffer
+            .inner
+            .iter()
+            .zip(self.old_buffer.inner.iter())
+            .enumerate()
+        {
+            if new == old {
+                continue;
+            }
 
-                if let Some(bg) = cell.style.bg {
-                    self.output.queue(SetForegroundColor(bg))?;
-                }
+            let c = match new.cell_state {
+                CellState::Empty => ' ',
+                CellState::Occupied(c) => c,
+                CellState::Continuation => continue,
+            };
 
-                let _ = match cell.cell_state {
-                    CellState::Empty => self.output.queue(Print(' '))?,
-                    CellState::Occupied(c) => self.output.queue(Print(c))?,
-                    CellState::Continuation => continue,
-                };
+            let pos = Pos::new((i % width as usize) as u16, (i / width as usize) as u16);
+            #[cfg(feature = "images")]
+            let c = match self.images.as_ref().and_then(|images| images.image(c)) {
+                // the image is drawn over a blank cell once the text is done
+                Some(_) => {
+                    changed.push((pos, c));
+                    ' '
+                }
+                None => c,
+            };
+            if cursor != Some(pos) {
+                self.output.queue(MoveTo(pos.x, pos.y))?;
             }
+            current = apply_style(&mut self.output, current, new.style)?;
+            self.output.queue(Print(c))?;
+
+            let next = pos.x + new.width().max(1);
+            // the terminal cursor stays on the last column, so the next cell
+            // always needs an explicit move
+            cursor = (next < width).then(|| Pos::new(next, pos.y));
+        }
+
+        if current != default {
+            self.output.queue(SetAttribute(Attribute::Reset))?;
+            self.output.queue(SetForegroundColor(Color::Reset))?;
+            self.output.queue(SetBackgroundColor(Color::Reset))?;
         }
+        #[cfg(feature = "images")]
+        self.render_images(changed)?;
         self.output.flush()?;
         self.old_buffer = self.new_buffer.clone();
         Ok(())
     }
+
+    /// Kitty keeps images apart from the text, so they are all placed again
+    /// whenever one of them moved. Other terminals paint images into the
+    /// cells, those only need drawing where the placeholder was written.
+    #[cfg(feature = "images")]
+    fn render_images(&mut self, changed: Vec<(Pos, char)>) -> Result<()> {
+        let images = match &self.images {
+            Some(images) => images.clone(),
+            None => return Ok(()),
+        };
+        let placements = match images.protocol() {
+            Protocol::Kitty => {
+                let width = self.size.width as usize;
+                let placed: Vec<(Pos, char)> = self
+                    .new_buffer
+                    .inner
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, cell)| match cell.cell_state {
+                        CellState::Occupied(c) if images.image(c).is_some() => {
+                            Some((Pos::new((i % width) as u16, (i / width) as u16), c))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                if placed == self.placed {
+                    return Ok(());
+                }
+                if !self.placed.is_empty() {
+                    self.output.queue(Print(graphics::kitty_clear()))?;
+                }
+                self.placed = placed.clone();
+                placed
+            }
+            Protocol::ITerm2 | Protocol::Sixel => changed,
+        };
+
+        for (pos, c) in placements {
+            let image = match images.image(c) {
+                Some(image) => image,
+                None => continue,
+            };
+            self.output.queue(MoveTo(pos.x, pos.y))?;
+            if let Some(setup) = &image.setup {
+                if self.transmitted.insert(image.id.clone()) {
+                    self.output.queue(Print(setup))?;
+                }
+            }
+            self.output.queue(Print(&image.draw))?;
+        }
+        Ok(())
+    }
+}
+
+/// Switches the terminal from the `current` style to `style` and returns the
+/// style the terminal ends up with.
+/// No colour and the reset colour look the same on the terminal, so styles
+/// are compared with the reset colour filled in.
+fn normalize(style: Style) -> Style {
+    Style::new(
+        Some(style.fg.unwrap_or(Color::Reset)),
+        Some(style.bg.unwrap_or(Color::Reset)),
+    )
+    .attributes(style.attributes)
+}
+
+pub fn apply_style(output: &mut impl Write, current: Style, style: Style) -> Result<Style> {
+    let style = normalize(style);
+    if style == current {
+        return Ok(current);
+    }
+
+    // attributes can only be switched off one by one, a reset also clears
+    // the colours so those have to be written again
+    let reset = style.attributes != current.attributes;
+    if reset {
+        output.queue(SetAttribute(Attribute::Reset))?;
+    }
+    if (reset && style.fg != Some(Color::Reset)) || style.fg != current.fg {
+        output.queue(SetForegroundColor(style.fg.unwrap_or(Color::Reset)))?;
+    }
+    if (reset && style.bg != Some(Color::Reset)) || style.bg != current.bg {
+        output.queue(SetBackgroundColor(style.bg.unwrap_or(Color::Reset)))?;
+    }
+    if reset && !style.attributes.is_empty() {
+        output.queue(SetAttributes(style.attributes))?;
+    }
+    Ok(style)
 }
 
 impl<W: Write> Drop for Screen<W> {
@@ -144,3 +322,236 @@ impl<W: Write> Drop for Screen<W> {
         self.output.flush().expect("Could not flush the toilet");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crossterm::style::Attributes;
+
+    fn screen(size: Size) -> Screen<Vec<u8>> {
+        let mut screen = Screen::new(Vec::new(), size).unwrap();
+        screen.output.clear();
+        screen
+    }
+
+    fn print(screen: &mut Screen<Vec<u8>>, s: &str, style: Style, pos: Pos) {
+        for (i, c) in s.chars().enumerate() {
+            screen.put(Cell::new(c, style), Pos::new(pos.x + i as u16, pos.y));
+        }
+    }
+
+    fn rendered(screen: &mut Screen<Vec<u8>>) -> String {
+        screen.render().unwrap();
+        String::from_utf8(std::mem::take(&mut screen.output)).unwrap()
+    }
+
+    fn expected(commands: impl FnOnce(&mut Vec<u8>) -> Result<()>) -> String {
+        let mut output = Vec::new();
+        commands(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    pub fn should_write_nothing_when_unchanged() {
+        let mut screen = screen(Size::new(10, 3));
+        assert_eq!(rendered(&mut screen), "");
+
+        print(&mut screen, "hello", Style::none(), Pos::new(0, 0));
+        rendered(&mut screen);
+        print(&mut screen, "hello", Style::none(), Pos::new(0, 0));
+        assert_eq!(rendered(&mut screen), "");
+    }
+
+    #[test]
+    pub fn should_not_reset_after_unstyled_cells() {
+        let mut screen = screen(Size::new(10, 1));
+        print(&mut screen, "ab", Style::new(None, None), Pos::new(0, 0));
+        print(
+            &mut screen,
+            "c",
+            Style::fg(Some(Color::Reset)),
+            Pos::new(2, 0),
+        );
+        print(&mut screen, "d", Style::none(), Pos::new(4, 0));
+        assert_eq!(
+            rendered(&mut screen),
+            expected(|out| {
+                out.queue(MoveTo(0, 0))?;
+                out.queue(Print("abc"))?;
+                out.queue(MoveTo(4, 0))?;
+                out.queue(Print('d'))?;
+                Ok(())
+            })
+        );
+
+        print(&mut screen, "e", Style::new(None, None), Pos::new(0, 0));
+        assert_eq!(
+            rendered(&mut screen),
+            expected(|out| {
+                out.queue(MoveTo(0, 0))?;
+                out.queue(Print('e'))?;
+                Ok(())
+            })
+        );
+    }
+
+    #[test]
+    pub fn should_only_write_changed_runs() {
+        let mut screen = screen(Size::new(10, 3));
+        print(&mut screen, "hello", Style::none(), Pos::new(0, 1));
+        rendered(&mut screen);
+
+        print(&mut screen, "jelly", Style::none(), Pos::new(0, 1));
+        assert_eq!(
+            rendered(&mut screen),
+            expected(|out| {
+                out.queue(MoveTo(0, 1))?;
+                out.queue(Print('j'))?;
+                out.queue(MoveTo(4, 1))?;
+                out.queue(Print('y'))?;
+                Ok(())
+            })
+        );
+    }
+
+    #[test]
+    pub fn should_coalesce_cursor_moves_and_colours() {
+        let mut screen = screen(Size::new(10, 3));
+        let red = Style::fg(Some(Color::Red));
+        print(&mut screen, "ab", red, Pos::new(2, 0));
+        print(&mut screen, "cd", red, Pos::new(8, 0));
+        print(&mut screen, "ef", red, Pos::new(0, 1));
+
+        assert_eq!(
+            rendered(&mut screen),
+            expected(|out| {
+                out.queue(MoveTo(2, 0))?;
+                out.queue(SetForegroundColor(Color::Red))?;
+                out.queue(Print('a'))?;
+                out.queue(Print('b'))?;
+                out.queue(MoveTo(8, 0))?;
+                out.queue(Print('c'))?;
+                out.queue(Print('d'))?;
+                out.queue(MoveTo(0, 1))?;
+                out.queue(Print('e'))?;
+                out.queue(Print('f'))?;
+                out.queue(SetAttribute(Attribute::Reset))?;
+                out.queue(SetForegroundColor(Color::Reset))?;
+                out.queue(SetBackgroundColor(Color::Reset))?;
+                Ok(())
+            })
+        );
+    }
+
+    #[test]
+    pub fn should_reset_when_attributes_are_removed() {
+        let mut screen = screen(Size::new(10, 1));
+        let bold = Style::new(Some(Color::Blue), Some(Color::Yellow)).attributes(Attribute::Bold);
+        let plain = Style::new(Some(Color::Blue), Some(Color::Yellow));
+        print(&mut screen, "a", bold, Pos::new(0, 0));
+        print(&mut screen, "b", plain, Pos::new(1, 0));
+
+        assert_eq!(
+            rendered(&mut screen),
+            expected(|out| {
+                out.queue(MoveTo(0, 0))?;
+                out.queue(SetAttribute(Attribute::Reset))?;
+                out.queue(SetForegroundColor(Color::Blue))?;
+                out.queue(SetBackgroundColor(Color::Yellow))?;
+                out.queue(SetAttributes(Attributes::from(Attribute::Bold)))?;
+                out.queue(Print('a'))?;
+                out.queue(SetAttribute(Attribute::Reset))?;
+                out.queue(SetForegroundColor(Color::Blue))?;
+                out.queue(SetBackgroundColor(Color::Yellow))?;
+                out.queue(Print('b'))?;
+                out.queue(SetAttribute(Attribute::Reset))?;
+                out.queue(SetForegroundColor(Color::Reset))?;
+                out.queue(SetBackgroundColor(Color::Reset))?;
+                Ok(())
+            })
+        );
+    }
+
+    #[test]
+    pub fn should_only_scroll_inside_region() {
+        let mut screen = screen(Size::new(2, 3));
+        print(&mut screen, "ab", Style::none(), Pos::new(0, 0));
+        print(&mut screen, "cd", Style::none(), Pos::new(0, 1));
+        print(&mut screen, "ef", Style::none(), Pos::new(0, 2));
+
+        screen.scroll_region(Pos::zero(), Size::new(2, 2), 1);
+        let cells: String = screen
+            .buffer()
+            .inner
+            .iter()
+            .map(|cell| match cell.cell_state {
+                CellState::Occupied(c) => c,
+                _ => ' ',
+            })
+            .collect();
+        assert_eq!(cells, "cd  ef");
+    }
+
+    #[cfg(feature = "images")]
+    #[test]
+    pub fn should_draw_images_over_placeholders() {
+        use crate::emote_cache::EmoteCache;
+
+        let cache = EmoteCache::new(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/emotes"),
+        );
+        let images = EmoteImages::new(Protocol::Kitty, cache);
+        let mut screen = screen(Size::new(10, 1));
+        screen.set_images(images.clone());
+        let placeholder = images.placeholder("25").unwrap();
+        print(
+            &mut screen,
+            &format!("a{}", placeholder),
+            Style::none(),
+            Pos::zero(),
+        );
+
+        let output = rendered(&mut screen);
+        let image = images.image(placeholder.chars().next().unwrap()).unwrap();
+        assert!(output.starts_with(&expected(|out| {
+            out.queue(MoveTo(0, 0))?;
+            out.queue(Print("a \u{a0}"))?;
+            out.queue(MoveTo(1, 0))?;
+            out.queue(Print(image.setup.as_ref().unwrap()))?;
+            out.queue(Print(&image.draw))?;
+            Ok(())
+        })));
+        assert_eq!(rendered(&mut screen), "");
+
+        print(&mut screen, "bc", Style::none(), Pos::zero());
+        assert_eq!(
+            rendered(&mut screen),
+            expected(|out| {
+                out.queue(MoveTo(0, 0))?;
+                out.queue(Print('b'))?;
+                out.queue(Print('c'))?;
+                out.queue(Print(graphics::kitty_clear()))?;
+                Ok(())
+            })
+        );
+    }
+
+    #[test]
+    pub fn should_blank_removed_cells() {
+        let mut screen = screen(Size::new(10, 1));
+        print(&mut screen, "ab", Style::none(), Pos::new(0, 0));
+        rendered(&mut screen);
+
+        screen.erase_region(Pos::zero(), Size::new(10, 1));
+        assert_eq!(
+            rendered(&mut screen),
+            expected(|out| {
+                out.queue(MoveTo(0, 0))?;
+                out.queue(Print(' '))?;
+                out.queue(Print(' '))?;
+                Ok(())
+            })
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crossterm::style::Color;
+use unicode_width::UnicodeWidthChar;
+
+use super::{
+    buffer::{Cell, Style},
+    screen::Screen,
+    Pos, Size,
+};
+use crate::{parser::command::RoomState, twitch_client::ConnectionState};
+
+/// Messages are counted over this window to get the rate.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// The modes a room is in, ROOMSTATE only sends what changed so this keeps
+/// track of the rest.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct RoomModes {
+    pub emote_only: bool,
+    /// Minutes someone has to follow before chatting.
+    pub followers_only: Option<u32>,
+    pub r9k: bool,
+    /// Seconds between messages of a single user.
+    pub slow: Option<u32>,
+    pub subs_only: bool,
+}
+
+impl RoomModes {
+    pub fn update(&mut self, state: &RoomState) {
+        if let Some(emote_only) = state.emote_only {
+            self.emote_only = emote_only;
+        }
+        if let Some(minutes) = state.followers_only {
+            // -1 turns the mode off, 0 lets every follower chat
+            self.followers_only = u32::try_from(minutes).ok();
+        }
+        if let Some(r9k) = state.r9k {
+            self.r9k = r9k;
+        }
+        if let Some(seconds) = state.slow {
+            self.slow = Some(seconds).filter(|&seconds| seconds > 0);
+        }
+        if let Some(subs_only) = state.subs_only {
+            self.subs_only = subs_only;
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        let mut modes = Vec::new();
+        if self.emote_only {
+            modes.push("emote-only".to_string());
+        }
+        match self.followers_only {
+            Some(0) => modes.push("followers-only".to_string()),
+            Some(minutes) => modes.push(format!("followers-only {}m", minutes)),
+            None => (),
+        }
+        if self.r9k {
+            modes.push("r9k".to_string());
+        }
+        if let Some(seconds) = self.slow {
+            modes.push(format!("slow {}s", seconds));
+        }
+        if self.subs_only {
+            modes.push("subs-only".to_string());
+        }
+        modes
+    }
+}
+
+/// A single row telling where we are and how the connection is doing.
+pub struct StatusBar {
+    pos: Pos,
+    width: u16,
+    channels: Vec<String>,
+    view: Option<String>,
+    state: Option<ConnectionState>,
+    modes: HashMap<String, RoomModes>,
+    received: VecDeque<Instant>,
+    latency: Option<Duration>,
+}
+
+impl StatusBar {
+    pub fn new(pos: Pos, width: u16) -> Self {
+        Self {
+            pos,
+            width,
+            channels: Vec::new(),
+            view: None,
+            state: None,
+            modes: HashMap::new(),
+            received: VecDeque::new(),
+            latency: None,
+        }
+    }
+
+    pub fn resize(&mut self, pos: Pos, width: u16) {
+        self.pos = pos;
+        self.width = width;
+    }
+
+    pub fn set_channels(&mut self, channels: Vec<String>) {
+        self.channels = channels;
+    }
+
+    /// The channel that is viewed on its own, `None` for the merged view.
+    pub fn set_view(&mut self, view: Option<&str>) {
+        self.view = view.map(str::to_string);
+    }
+
+    pub fn set_state(&mut self, state: ConnectionState) {
+        self.state = Some(state);
+        if state != ConnectionState::Connected {
+            self.latency = None;
+        }
+    }
+
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = Some(latency);
+    }
+
+    pub fn room_state(&mut self, state: &RoomState) {
+        self.modes
+            .entry(state.channel.to_string())
+            .or_default()
+            .update(state);
+    }
+
+    #[cfg(test)]
+    pub fn modes(&self, channel: &str) -> Option<&RoomModes> {
+        self.modes.get(channel)
+    }
+
+    /// Counts a chat message towards the rate.
+    pub fn message_received(&mut self, now: Instant) {
+        self.received.push_back(now);
+        self.expire(now);
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&oldest) = self.received.front() {
+            if now.duration_since(oldest) < RATE_WINDOW {
+                break;
+            }
+            self.received.pop_front();
+        }
+    }
+
+    /// The channel the modes are shown for, with a single channel that is
+    /// the one, otherwise the one that is viewed.
+    fn shown_channel(&self) -> Option<&str> {
+        match (&self.view, self.channels.as_slice()) {
+            (Some(view), _) => Some(view),
+            (None, [channel]) => Some(channel),
+            _ => None,
+        }
+    }
+
+    /// The pieces of the bar, separated by `|`, some with a colour of their
+    /// own.
+    pub fn segments(&mut self, now: Instant) -> Vec<(String, Option<Color>)> {
+        self.expire(now);
+        let mut segments = Vec::new();
+
+        let channel = match self.shown_channel() {
+            Some(channel) => format!("#{}", channel),
+            None => self
+                .channels
+                .iter()
+                .map(|channel| format!("#{}", channel))
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        segments.push((channel, None));
+
+        let state = match self.state {
+            Some(ConnectionState::Connected) => ("connected".to_string(), Color::Green),
+            Some(ConnectionState::Disconnected) => ("disconnected".to_string(), Color::Red),
+            Some(ConnectionState::Reconnecting { attempt, delay }) => (
+                format!("reconnecting in {}s ({})", delay.as_secs(), attempt),
+                Color::Yellow,
+            ),
+            None => ("connecting".to_string(), Color::Yellow),
+        };
+        segments.push((state.0, Some(state.1)));
+
+        let modes = self
+            .shown_channel()
+            .and_then(|channel| self.modes.get(channel))
+            .map(RoomModes::describe)
+            .unwrap_or_default();
+        if !modes.is_empty() {
+            segments.push((modes.join(" "), Some(Color::Cyan)));
+        }
+
+        segments.push((format!("{} msg/min", self.received.len()), None));
+        if let Some(latency) = self.latency {
+            segments.push((format!("{} ms", latency.as_millis()), None));
+        }
+        segments
+    }
+
+    pub fn draw(&mut self, screen: &mut Screen<impl Write>, now: Instant) {
+        screen.erase_region(self.pos, Size::new(self.width, 1));
+        let background = Style::new(Some(Color::White), Some(Color::DarkGrey));
+        let mut x = 0;
+        for (i, (text, color)) in self.segments(now).into_iter().enumerate() {
+            let separator = if i == 0 { " " } else { " | " };
+            let pieces = [(separator.to_string(), None), (text, color)];
+            for (text, color) in pieces {
+                let style = Style::new(color.or(background.fg), background.bg);
+                for c in text.chars() {
+                    let w = c.width().unwrap_or(1) as u16;
+                    if x + w > self.width {
+                        return;
+                    }
+                    screen.put(Cell::new(c, style), self.pos + Pos::new(x, 0));
+                    x += w;
+                }
+            }
+        }
+        while x < self.width {
+            screen.put(Cell::new(' ', background), self.pos + Pos::new(x, 0));
+            x += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{chat_message::ChatMessage, command::Command};
+
+    fn room_state(input: &str) -> RoomState<'_> {
+        match ChatMessage::parse(input).unwrap().command {
+            Command::RoomState(state) => state,
+            command => panic!("expected ROOMSTATE, got {:?}", command),
+        }
+    }
+
+    fn text(segments: Vec<(String, Option<Color>)>) -> Vec<String> {
+        segments.into_iter().map(|(text, _)| text).collect()
+    }
+
+    #[test]
+    fn should_keep_modes_across_partial_updates() {
+        let mut status = StatusBar::new(Pos::zero(), 80);
+        status.room_state(&room_state("@emote-only=0;followers-only=10;r9k=0;room-id=1;slow=0;subs-only=0 :tmi.twitch.tv ROOMSTATE #dallas"));
+        status.room_state(&room_state(
+            "@room-id=1;slow=30 :tmi.twitch.tv ROOMSTATE #dallas",
+        ));
+        assert_eq!(
+            status.modes("dallas"),
+            Some(&RoomModes {
+                emote_only: false,
+                followers_only: Some(10),
+                r9k: false,
+                slow: Some(30),
+                subs_only: false,
+            })
+        );
+
+        status.room_state(&room_state(
+            "@followers-only=-1;room-id=1 :tmi.twitch.tv ROOMSTATE #dallas",
+        ));
+        assert_eq!(status.modes("dallas").unwrap().followers_only, None);
+    }
+
+    #[test]
+    fn should_describe_channel_state_rate_and_latency() {
+        let mut status = StatusBar::new(Pos::zero(), 80);
+        status.set_channels(vec!["dallas".to_string()]);
+        let start = Instant::now();
+        assert_eq!(
+            text(status.segments(start)),
+            vec!["#dallas", "connecting", "0 msg/min"]
+        );
+
+        status.set_state(ConnectionState::Connected);
+        status.set_latency(Duration::from_millis(42));
+        status.room_state(&room_state(
+            "@emote-only=1;room-id=1;subs-only=1 :tmi.twitch.tv ROOMSTATE #dallas",
+        ));
+        status.message_received(start);
+        status.message_received(start + Duration::from_secs(30));
+        assert_eq!(
+            text(status.segments(start + Duration::from_secs(40))),
+            vec![
+                "#dallas",
+                "connected",
+                "emote-only subs-only",
+                "2 msg/min",
+                "42 ms"
+            ]
+        );
+        assert_eq!(
+            text(status.segments(start + Duration::from_secs(61)))[3],
+            "1 msg/min"
+        );
+    }
+}
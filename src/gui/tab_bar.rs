@@ -0,0 +1,82 @@
+use std::io::Write;
+
+use crossterm::style::{Attribute, Color};
+use unicode_width::UnicodeWidthChar;
+
+use super::{
+    buffer::{Cell, Style},
+    screen::Screen,
+    Pos, Size,
+};
+
+/// A row of tabs, one for the merged view and one for every channel.
+pub struct TabBar {
+    pos: Pos,
+    width: u16,
+}
+
+impl TabBar {
+    pub fn new(pos: Pos, width: u16) -> Self {
+        Self { pos, width }
+    }
+
+    pub fn resize(&mut self, pos: Pos, width: u16) {
+        self.pos = pos;
+        self.width = width;
+    }
+
+    /// Draws the tabs, the active one is reversed. Tabs that do not fit are
+    /// cut off at the edge of the screen.
+    pub fn draw(&self, screen: &mut Screen<impl Write>, tabs: &[String], active: usize) {
+        screen.erase_region(self.pos, Size::new(self.width, 1));
+        let mut x = 0;
+        for (i, tab) in tabs.iter().enumerate() {
+            let style = if i == active {
+                Style::none().attributes(Attribute::Reverse)
+            } else {
+                Style::fg(Some(Color::DarkGrey))
+            };
+            for c in format!(" {} ", tab).chars() {
+                let w = c.width().unwrap_or(1) as u16;
+                if x + w > self.width {
+                    return;
+                }
+                screen.put(Cell::new(c, style), self.pos + Pos::new(x, 0));
+                x += w;
+            }
+            x += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gui::buffer::CellState;
+
+    #[test]
+    fn should_reverse_active_tab() {
+        let mut screen = Screen::new(Vec::new(), Size::new(20, 1)).unwrap();
+        let tabs = TabBar::new(Pos::zero(), 20);
+        let names = vec![
+            "all".to_string(),
+            "#dallas".to_string(),
+            "#forsen".to_string(),
+        ];
+        tabs.draw(&mut screen, &names, 1);
+
+        let cells = &screen.buffer().inner;
+        let row: String = cells
+            .iter()
+            .map(|cell| match cell.cell_state {
+                CellState::Occupied(c) => c,
+                _ => ' ',
+            })
+            .collect();
+        assert_eq!(row, " all   #dallas   #fo");
+        assert!(!cells[1].style.attributes.has(Attribute::Reverse));
+        assert!(cells[6..15]
+            .iter()
+            .all(|cell| cell.style.attributes.has(Attribute::Reverse)));
+    }
+}
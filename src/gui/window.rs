@@ -6,6 +6,8 @@ use super::{
     Pos, Size,
 };
 
+/// A region of the screen that text is written into, nothing is drawn outside
+/// of it and it scrolls on its own.
 pub struct Window {
     pos: Pos,
     pub size: Size,
@@ -28,11 +30,14 @@ impl Window {
     }
 
     pub fn newline(&mut self, screen: &mut Screen<impl Write>) {
-        if self.cursor.y < screen.size().height - 1 {
+        if self.is_empty() {
+            return;
+        }
+        if self.cursor.y + 1 < self.size.height() {
             self.cursor.x = 0;
             self.cursor.y += 1;
         } else {
-            screen.scroll_up(1);
+            screen.scroll_region(self.pos, self.size, 1);
             self.cursor.x = 0;
         }
     }
@@ -43,7 +48,17 @@ impl Window {
             return;
         }
 
+        if self.is_empty() {
+            return;
+        }
         let cell = Cell::new(c, style);
+        if cell.width() > self.size.width() {
+            return;
+        }
+        if self.cursor.x + cell.width() > self.size.width() {
+            // a wide character that would stick out goes on the next row
+            self.newline(screen);
+        }
         screen.put(cell, self.pos + self.cursor);
 
         if self.cursor.x + cell.width() >= self.size.width() {
@@ -53,6 +68,20 @@ impl Window {
         }
     }
 
+    pub fn move_to(&mut self, pos: Pos) {
+        self.pos = pos;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.size.width() == 0 || self.size.height() == 0
+    }
+
+    pub fn resize(&mut self, size: Size) {
+        self.size = size;
+        self.cursor.x = self.cursor.x.min(size.width().saturating_sub(1));
+        self.cursor.y = self.cursor.y.min(size.height().saturating_sub(1));
+    }
+
     pub fn clear(&mut self, screen: &mut Screen<impl Write>) {
         screen.erase_region(self.pos, self.size);
         self.cursor = Pos::zero();
@@ -63,6 +92,7 @@ impl Window {
 mod test {
 
     use super::*;
+    use crate::gui::buffer::CellState;
     use std::io::stdout;
 
     #[test]
@@ -71,4 +101,35 @@ mod test {
         let mut window = Window::new(Pos::new(0, 0), Size::new(2, 2));
         window.print(&mut screen, "Helo", Style::none());
     }
+
+    #[test]
+    pub fn should_wrap_wide_char_instead_of_crossing_the_edge() {
+        let mut screen = Screen::new(Vec::new(), Size::new(6, 2)).unwrap();
+        let mut window = Window::new(Pos::new(0, 0), Size::new(3, 2));
+        window.print(&mut screen, "ab\u{1F600}", Style::none());
+        assert_eq!(window.cursor, Pos::new(2, 1));
+        assert_eq!(screen.buffer().inner[2].cell_state, CellState::Empty);
+        assert_eq!(screen.buffer().inner[3].cell_state, CellState::Empty);
+        assert_eq!(
+            screen.buffer().inner[6].cell_state,
+            CellState::Occupied('\u{1F600}')
+        );
+    }
+
+    #[test]
+    pub fn should_not_draw_without_room() {
+        let mut screen = Screen::new(Vec::new(), Size::new(2, 2)).unwrap();
+        let mut window = Window::new(Pos::new(0, 0), Size::new(0, 2));
+        window.print(&mut screen, "Helo\n", Style::none());
+        assert_eq!(window.cursor, Pos::zero());
+    }
+
+    #[test]
+    pub fn should_keep_cursor_inside_after_resize() {
+        let mut window = Window::new(Pos::new(0, 0), Size::new(10, 10));
+        window.cursor = Pos::new(8, 9);
+        window.resize(Size::new(5, 4));
+        assert_eq!(window.size, Size::new(5, 4));
+        assert_eq!(window.cursor, Pos::new(4, 3));
+    }
 }
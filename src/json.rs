@@ -0,0 +1,457 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::parser::{
+    chat_message::ChatMessage,
+    command::{Command, PrivMsg},
+    meta_data::tags,
+    tags::{self as tag, Tags},
+};
+
+/// Goes up whenever a field changes its meaning or goes away, fields that
+/// are added do not change it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A parsed line from the server as written to JSON Lines, the schema is
+/// described in the readme. Fields that only some commands have sit next
+/// to the common ones, `type` tells which command it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub version: u32,
+    pub received_at: DateTime<Utc>,
+    /// The line exactly as it was received.
+    pub raw: String,
+    pub prefix: Option<String>,
+    /// Every tag of the line, the values unescaped.
+    pub tags: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub command: EventCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum EventCommand {
+    PrivMsg {
+        channel: String,
+        id: String,
+        user: User,
+        message: String,
+        emotes: Vec<EmoteUse>,
+        bits: Option<u32>,
+        first_msg: bool,
+        returning_chatter: bool,
+        reply: Option<ReplyTo>,
+        sent_at: DateTime<Utc>,
+    },
+    ClearChat {
+        channel: String,
+        target_user: Option<String>,
+        target_user_id: Option<u32>,
+        ban_duration: Option<u32>,
+        room_id: Option<u32>,
+        sent_at: Option<DateTime<Utc>>,
+    },
+    ClearMsg {
+        channel: String,
+        login: Option<String>,
+        target_msg_id: Option<String>,
+        message: Option<String>,
+        sent_at: Option<DateTime<Utc>>,
+    },
+    GlobalUserState {
+        user: User,
+        emote_sets: Vec<String>,
+    },
+    HostTarget {
+        channel: String,
+        target: Option<String>,
+        viewers: Option<u32>,
+    },
+    Notice {
+        channel: Option<String>,
+        msg_id: Option<String>,
+        target_user_id: Option<u32>,
+        message: String,
+    },
+    Reconnect,
+    RoomState {
+        channel: String,
+        emote_only: Option<bool>,
+        followers_only: Option<i32>,
+        r9k: Option<bool>,
+        room_id: Option<u32>,
+        slow: Option<u32>,
+        subs_only: Option<bool>,
+    },
+    /// Subs, raids, announcements and the like, the `msg-param-*` tags
+    /// carry the details.
+    UserNotice {
+        channel: String,
+        msg_id: String,
+        id: Option<String>,
+        user: User,
+        system_msg: Option<String>,
+        message: Option<String>,
+        sent_at: Option<DateTime<Utc>>,
+    },
+    UserState {
+        channel: String,
+        user: User,
+        emote_sets: Vec<String>,
+    },
+    Whisper {
+        from: String,
+        to: String,
+        message_id: Option<String>,
+        thread_id: Option<String>,
+        user: User,
+        message: String,
+    },
+    Join {
+        channel: String,
+        nick: String,
+    },
+    Part {
+        channel: String,
+        nick: String,
+    },
+    Cap {
+        subcommand: String,
+        capabilities: Vec<String>,
+    },
+    Ping {
+        token: Option<String>,
+    },
+    Pong {
+        token: Option<String>,
+    },
+    Numeric {
+        code: u16,
+        params: Vec<String>,
+        message: Option<String>,
+    },
+}
+
+/// Who sent a message, or whose state it is, as far as the tags tell.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub id: Option<u32>,
+    pub login: Option<String>,
+    pub display_name: Option<String>,
+    /// `#RRGGBB`, missing for users that never picked a colour.
+    pub color: Option<String>,
+    pub badges: Vec<Badge>,
+    /// Extra details for badges, like the exact number of months subscribed.
+    pub badge_info: Vec<Badge>,
+    pub moderator: bool,
+    pub subscriber: bool,
+    pub turbo: bool,
+}
+
+/// A badge by the name Twitch gives it, like `subscriber` and `12`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Badge {
+    pub name: String,
+    pub version: String,
+}
+
+/// A single use of an emote, `start` and `end` count code points of the
+/// message and include the last one, like Twitch does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmoteUse {
+    pub id: String,
+    pub name: String,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// The message a reply answers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplyTo {
+    pub id: String,
+    pub user_id: Option<u32>,
+    pub login: Option<String>,
+    pub display_name: Option<String>,
+    pub body: Option<String>,
+}
+
+impl Event {
+    pub fn new(line: &str, received_at: DateTime<Utc>) -> Result<Self, String> {
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        let message =
+            ChatMessage::parse(line).map_err(|_| format!("could not parse `{}`", line))?;
+        let tags = tags(line).map(|(_, tags)| tags).unwrap_or_default();
+        let nick = message
+            .prefix
+            .and_then(|prefix| prefix.split('!').next())
+            .map(str::to_string);
+        let command = match message.command {
+            Command::PrivMsg(message) => privmsg(&message, &tags, nick),
+            Command::ClearChat(clear) => EventCommand::ClearChat {
+                channel: clear.channel.to_string(),
+                target_user: owned(clear.target_user),
+                target_user_id: clear.target_user_id,
+                ban_duration: clear.ban_duration,
+                room_id: clear.room_id,
+                sent_at: clear.tmi_sent_ts,
+            },
+            Command::ClearMsg(clear) => EventCommand::ClearMsg {
+                channel: clear.channel.to_string(),
+                login: owned(clear.login),
+                target_msg_id: owned(clear.target_msg_id),
+                message: owned(clear.message),
+                sent_at: clear.tmi_sent_ts,
+            },
+            Command::GlobalUserState(state) => EventCommand::GlobalUserState {
+                user: User::from_tags(&tags, None),
+                emote_sets: all_owned(&state.emote_sets),
+            },
+            Command::HostTarget(host) => EventCommand::HostTarget {
+                channel: host.channel.to_string(),
+                target: owned(host.target),
+                viewers: host.viewers,
+            },
+            Command::Notice(notice) => EventCommand::Notice {
+                channel: owned(notice.channel),
+                msg_id: owned(notice.msg_id),
+                target_user_id: notice.target_user_id,
+                message: notice.message.to_string(),
+            },
+            Command::Reconnect => EventCommand::Reconnect,
+            Command::RoomState(state) => EventCommand::RoomState {
+                channel: state.channel.to_string(),
+                emote_only: state.emote_only,
+                followers_only: state.followers_only,
+                r9k: state.r9k,
+                room_id: state.room_id,
+                slow: state.slow,
+                subs_only: state.subs_only,
+            },
+            Command::UserNotice(notice) => EventCommand::UserNotice {
+                channel: notice.channel.to_string(),
+                msg_id: notice.msg_id.to_string(),
+                id: owned(notice.id),
+                user: User::from_tags(&tags, owned(notice.login)),
+                system_msg: notice.system_msg.map(|msg| msg.into_owned()),
+                message: owned(notice.message),
+                sent_at: notice.tmi_sent_ts,
+            },
+            Command::UserState(state) => EventCommand::UserState {
+                channel: state.channel.to_string(),
+                user: User::from_tags(&tags, None),
+                emote_sets: all_owned(&state.emote_sets),
+            },
+            Command::Whisper(whisper) => EventCommand::Whisper {
+                from: whisper.from.to_string(),
+                to: whisper.to.to_string(),
+                message_id: owned(whisper.message_id),
+                thread_id: owned(whisper.thread_id),
+                user: User::from_tags(&tags, Some(whisper.from.to_string())),
+                message: whisper.message.to_string(),
+            },
+            Command::Join(membership) => EventCommand::Join {
+                channel: membership.channel.to_string(),
+                nick: membership.nick.to_string(),
+            },
+            Command::Part(membership) => EventCommand::Part {
+                channel: membership.channel.to_string(),
+                nick: membership.nick.to_string(),
+            },
+            Command::Cap(cap) => EventCommand::Cap {
+                subcommand: cap.subcommand.to_string(),
+                capabilities: all_owned(&cap.capabilities),
+            },
+            Command::Ping(token) => EventCommand::Ping {
+                token: owned(token),
+            },
+            Command::Pong(token) => EventCommand::Pong {
+                token: owned(token),
+            },
+            Command::Numeric(numeric) => EventCommand::Numeric {
+                code: numeric.code,
+                params: all_owned(&numeric.params),
+                message: owned(numeric.message),
+            },
+        };
+        Ok(Event {
+            version: SCHEMA_VERSION,
+            received_at,
+            raw: line.to_string(),
+            prefix: owned(message.prefix),
+            tags: tags
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.into_owned()))
+                .collect(),
+            command,
+        })
+    }
+}
+
+fn privmsg(message: &PrivMsg, tags: &Tags, nick: Option<String>) -> EventCommand {
+    let meta_data = &message.meta_data;
+    let chars: Vec<char> = message.message.chars().collect();
+    let emotes = meta_data
+        .emotes
+        .iter()
+        .flat_map(|emote| {
+            emote.indexes.iter().map(|&(start, end)| EmoteUse {
+                id: emote.id.to_string(),
+                name: chars
+                    .get(start as usize..=end as usize)
+                    .map(|name| name.iter().collect())
+                    .unwrap_or_default(),
+                start,
+                end,
+            })
+        })
+        .collect();
+    let reply = &meta_data.reply;
+    EventCommand::PrivMsg {
+        channel: message.destination.to_string(),
+        id: meta_data.id.to_string(),
+        user: User::from_tags(tags, nick),
+        message: message.message.to_string(),
+        emotes,
+        bits: meta_data.bits,
+        first_msg: meta_data.first_msg,
+        returning_chatter: meta_data.returning_chatter,
+        reply: reply.msg_id.map(|id| ReplyTo {
+            id: id.to_string(),
+            user_id: reply.user_id,
+            login: owned(reply.user_login),
+            display_name: owned(reply.display_name),
+            body: reply.msg_body.as_ref().map(|body| body.to_string()),
+        }),
+        sent_at: meta_data.tmi_sent_ts,
+    }
+}
+
+impl User {
+    fn from_tags(tags: &Tags, login: Option<String>) -> Self {
+        let badges = |key| {
+            tags.unescaped(key)
+                .map(|list| {
+                    list.split(',')
+                        .filter_map(|badge| badge.split_once('/'))
+                        .map(|(name, version)| Badge {
+                            name: name.to_string(),
+                            version: version.to_string(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Self {
+            id: tags.parse(tag::USER_ID),
+            login,
+            display_name: tags
+                .unescaped(tag::DISPLAY_NAME)
+                .map(|name| name.into_owned()),
+            color: tags.value(tag::COLOR).map(str::to_string),
+            badges: badges(tag::BADGES),
+            badge_info: badges(tag::BADGE_INFO),
+            moderator: tags.flag(tag::MODERATOR).unwrap_or(false),
+            subscriber: tags.flag(tag::SUBSCRIBER).unwrap_or(false),
+            turbo: tags.flag(tag::TURBO).unwrap_or(false),
+        }
+    }
+}
+
+fn owned(value: Option<&str>) -> Option<String> {
+    value.map(str::to_string)
+}
+
+fn all_owned(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    const LINES: [&str; 9] = [
+        "@badge-info=subscriber/14;badges=subscriber/12,premium/1;color=#0000FF;display-name=Ronni;emotes=25:0-4,12-16/1902:6-10;first-msg=1;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=0;reply-parent-display-name=Dallas;reply-parent-msg-body=so\\shello;reply-parent-msg-id=6b13e51b-7ecb-43b5-ba5b-2bb5288df696;reply-parent-user-id=1;reply-parent-user-login=dallas;room-id=1;subscriber=1;tmi-sent-ts=1642696567751;turbo=0;user-id=1337;user-type= :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :Kappa Keepo Kappa",
+        "@badge-info=;badges=staff/1;color=;display-name=Ronni;emotes=;flags=;id=db25007f-7a18-43eb-9379-80131e44d633;login=ronni;mod=0;msg-id=resub;msg-param-cumulative-months=6;msg-param-should-share-streak=1;msg-param-streak-months=2;msg-param-sub-plan=Prime;room-id=1337;subscriber=1;system-msg=ronni\\shas\\ssubscribed\\sfor\\s6\\smonths!;tmi-sent-ts=1507246572675;user-id=1337;user-type=staff :tmi.twitch.tv USERNOTICE #dallas :Great stream -- keep it up!",
+        "@ban-duration=350;room-id=12345678;target-user-id=87654321;tmi-sent-ts=1642715756806 :tmi.twitch.tv CLEARCHAT #dallas :ronni",
+        "@emote-only=0;followers-only=-1;r9k=0;room-id=12345678;slow=10;subs-only=0 :tmi.twitch.tv ROOMSTATE #bar",
+        "@msg-id=slow_on :tmi.twitch.tv NOTICE #bar :This room is now in slow mode.",
+        ":ronni!ronni@ronni.tmi.twitch.tv JOIN #dallas",
+        "PING :tmi.twitch.tv",
+        ":tmi.twitch.tv 001 ronni :Welcome, GLHF!",
+        ":tmi.twitch.tv RECONNECT",
+    ];
+
+    fn received() -> DateTime<Utc> {
+        Utc.timestamp(1642700000, 0)
+    }
+
+    #[test]
+    fn should_survive_a_round_trip() {
+        for line in LINES {
+            let event = Event::new(line, received()).unwrap();
+            let json = serde_json::to_string(&event).unwrap();
+            assert!(!json.contains('\n'));
+            assert_eq!(
+                serde_json::from_str::<Event>(&json).unwrap(),
+                event,
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn should_describe_privmsg() {
+        let event = Event::new(LINES[0], received()).unwrap();
+        let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["type"], "privmsg");
+        assert_eq!(json["received_at"], "2022-01-20T17:33:20Z");
+        assert_eq!(json["sent_at"], "2022-01-20T16:36:07Z");
+        assert_eq!(json["channel"], "dallas");
+        assert_eq!(json["prefix"], "ronni!ronni@ronni.tmi.twitch.tv");
+        assert_eq!(json["tags"]["reply-parent-msg-body"], "so hello");
+        assert_eq!(
+            json["user"],
+            serde_json::json!({
+                "id": 1337,
+                "login": "ronni",
+                "display_name": "Ronni",
+                "color": "#0000FF",
+                "badges": [
+                    { "name": "subscriber", "version": "12" },
+                    { "name": "premium", "version": "1" },
+                ],
+                "badge_info": [{ "name": "subscriber", "version": "14" }],
+                "moderator": false,
+                "subscriber": true,
+                "turbo": false,
+            })
+        );
+        assert_eq!(
+            json["emotes"][2],
+            serde_json::json!({ "id": "1902", "name": "Keepo", "start": 6, "end": 10 })
+        );
+        assert_eq!(json["reply"]["body"], "so hello");
+        assert_eq!(json["reply"]["login"], "dallas");
+        assert_eq!(json["first_msg"], true);
+    }
+
+    #[test]
+    fn should_keep_details_of_other_commands() {
+        let event = |line| serde_json::to_value(Event::new(line, received()).unwrap()).unwrap();
+        let notice = event(LINES[1]);
+        assert_eq!(notice["type"], "usernotice");
+        assert_eq!(notice["msg_id"], "resub");
+        assert_eq!(notice["system_msg"], "ronni has subscribed for 6 months!");
+        assert_eq!(notice["tags"]["msg-param-cumulative-months"], "6");
+        assert_eq!(notice["user"]["color"], serde_json::Value::Null);
+
+        assert_eq!(event(LINES[3])["followers_only"], -1);
+        assert_eq!(event(LINES[8])["type"], "reconnect");
+        assert!(Event::new("not irc", received()).is_err());
+    }
+}
@@ -41,6 +41,7 @@ pub struct Logger {
     sender: Option<Sender<LogEvents>>,
     log_worker: Option<JoinHandle<()>>,
     enabled: bool,
+    file: String,
 }
 
 impl Logger {
@@ -50,9 +51,15 @@ impl Logger {
             sender: None,
             log_worker: None,
             enabled: false,
+            file: "twitch_chat.log".to_string(),
         }
     }
 
+    /// The file logs are appended to once logging is enabled.
+    pub fn set_file(&mut self, file: impl Into<String>) {
+        self.file = file.into();
+    }
+
     pub fn set_level(&mut self, log_level: LogLevel) {
         self.log_level = log_level;
     }
@@ -113,7 +120,7 @@ impl Logger {
         let (sender, receiver) = unbounded::<LogEvents>();
 
         let mut appenders: Vec<Box<dyn Write + Send + Sync>> = Vec::new();
-        let appender = FileAppender::new("twitch_chat.log");
+        let appender = FileAppender::new(&self.file);
         let file_appender = Box::new(appender);
         appenders.push(file_appender);
 
@@ -2,6 +2,7 @@ use std::{
     fmt::Display,
     mem::MaybeUninit,
     ops::{Deref, DerefMut},
+    ptr::{addr_of, addr_of_mut},
     sync::Once,
 };
 
@@ -102,11 +103,11 @@ impl DerefMut for SingletonLogger {
 }
 
 pub fn get_logger() -> &'static SingletonLogger {
-    unsafe { LOGGER.assume_init_ref() }
+    unsafe { (*addr_of!(LOGGER)).assume_init_ref() }
 }
 
 pub fn get_logger_mut() -> &'static mut SingletonLogger {
-    unsafe { LOGGER.assume_init_mut() }
+    unsafe { (*addr_of_mut!(LOGGER)).assume_init_mut() }
 }
 
 pub(crate) fn init() {
@@ -115,7 +116,7 @@ pub(crate) fn init() {
             let logger = SingletonLogger {
                 inner: Logger::new(),
             };
-            LOGGER.write(logger);
+            (*addr_of_mut!(LOGGER)).write(logger);
         });
     }
 }
@@ -1,92 +1,350 @@
-use log::{get_logger_mut, LogLevel};
-use std::{env, process::exit};
-use twitch_chat::TwitchChat;
+use arg_parser::Command;
+use config::{parse_log_level, Config};
+use gui::{emotes::EmoteMap, highlight::Highlights};
+use log::get_logger_mut;
+use recording::Speed;
+use std::{
+    env,
+    io::{stdout, IsTerminal},
+    path::PathBuf,
+    process::exit,
+};
+use tail::Format;
+use twitch_chat::{TwitchChat, TWITCH_IRC_URL};
 
 mod arg_parser;
 mod color_gen;
 mod color_holder;
+mod config;
+#[cfg(feature = "images")]
+mod emote_cache;
 mod gui;
+mod json;
 mod log;
 mod parser;
+mod rate_limiter;
+mod recording;
 mod string_padder;
+mod tail;
+mod transport;
 mod twitch_chat;
 mod twitch_client;
 
 fn main() {
     log::init();
 
-    let args: Vec<String> = env::args().collect();
-    let mut arg_map = arg_parser::parse(&args);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = arg_parser::parse(&args).unwrap_or_else(|err| {
+        eprintln!("ERROR: {}, see `twitch-chat --help`", err);
+        exit(2);
+    });
+    let mut arg_map = args.options;
+    if arg_map.contains_key("help") {
+        print_help();
+        return;
+    }
+    if arg_map.contains_key("version") {
+        println!("twitch-chat {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    let replay = args.command == Command::Replay;
+
+    let config_path = arg_map
+        .remove("config")
+        .map(|path| (PathBuf::from(path), true))
+        .or_else(|| Config::default_path().map(|path| (path, false)));
+    let config = match config_path {
+        Some((path, required)) => Config::load(&path, required).unwrap_or_else(|err| {
+            eprintln!("ERROR: {}", err);
+            exit(1);
+        }),
+        None => Config::default(),
+    };
 
     let log = get_logger_mut();
-    if let Some(value) = arg_map.remove("logging") {
-        if value.parse::<bool>().unwrap() {
-            log.enabled();
-        }
+    if let Some(file) = &config.log.file {
+        log.set_file(file);
+    }
+    let logging = arg_map
+        .remove("logging")
+        .map(|value| value.parse::<bool>().unwrap());
+    if logging.unwrap_or(config.log.enabled) {
+        log.enabled();
     }
 
-    if let Some(&value) = arg_map.get("log_level") {
-        match value {
-            "debug" => log.set_level(LogLevel::Debug),
-            "info" => log.set_level(LogLevel::Info),
-            "warn" => log.set_level(LogLevel::Warn),
-            "error" => log.set_level(LogLevel::Error),
-            "trace" => log.set_level(LogLevel::Trace),
-            _ => (),
-        }
+    let level = arg_map
+        .get("log_level")
+        .and_then(|&level| parse_log_level(level))
+        .or(config.log_level().unwrap());
+    if let Some(level) = level {
+        log.set_level(level);
     }
 
     log.debug(format!("logger values: {:#?}", log), "main");
     log.info("Starting application", "main");
 
-    let nick = arg_map.remove("nick").unwrap_or_else(|| {
-        eprintln!("ERROR: no nick was provided");
-        print_help();
-        exit(1);
-    });
+    // a replay does not connect, it needs neither nick nor token
+    let nick = arg_map
+        .remove("nick")
+        .map(str::to_string)
+        .or_else(|| config.nick.clone())
+        .or_else(|| replay.then(String::new))
+        .unwrap_or_else(|| {
+            eprintln!("ERROR: no nick was provided");
+            print_help();
+            exit(1);
+        });
 
-    let channel = arg_map.remove("channel").unwrap_or_else(|| {
+    let mut channels = arg_map
+        .remove("channel")
+        .map(channels)
+        .unwrap_or_else(|| channels(&config.channels.join(",")));
+    if let (true, true, Some(file)) = (replay, channels.is_empty(), args.file) {
+        channels = recording::channels(file).unwrap_or_else(|err| {
+            eprintln!("ERROR: {}", err);
+            exit(1);
+        });
+    }
+    if channels.is_empty() {
         eprintln!("ERROR: no channel was provided");
         print_help();
         exit(1);
-    });
+    }
 
-    let token = env::var("TWITCH_BOT_TOKEN").unwrap_or_else(|_| {
-        eprintln!("ERROR: TWITCH_BOT_TOKEN env variable not set");
-        print_help();
-        exit(1);
-    });
+    let token = if replay {
+        String::new()
+    } else {
+        config.token().unwrap_or_else(|err| {
+            eprintln!("ERROR: {}", err);
+            print_help();
+            exit(1);
+        })
+    };
 
     log.info("Config:", "main");
     log.info(format!("\t nick: {}", nick), "main");
-    log.info(format!("\t channel: {}", channel), "main");
+    log.info(format!("\t channels: {}", channels.join(", ")), "main");
 
-    let twitch_chat = TwitchChat::new(nick, channel, token);
-    twitch_chat.start();
+    let mut twitch_chat = TwitchChat::new(nick, channels, token);
+    if arg_map.remove("irc").is_some() || config.irc {
+        twitch_chat = twitch_chat.url(TWITCH_IRC_URL);
+    }
+    if let Some(url) = arg_map.remove("url").or(config.url.as_deref()) {
+        log.info(format!("\t url: {}", url), "main");
+        twitch_chat = twitch_chat.url(url);
+    }
+    let emotes = arg_map
+        .remove("emotes")
+        .map(PathBuf::from)
+        .or_else(|| config.theme.emotes.clone());
+    if let Some(path) = emotes {
+        log.info(format!("\t emotes: {}", path.display()), "main");
+        let emotes = EmoteMap::load(path).unwrap_or_else(|err| {
+            eprintln!("ERROR: {}", err);
+            exit(1);
+        });
+        twitch_chat = twitch_chat.emotes(emotes);
+    }
+    // the file has been checked when it was loaded
+    let mut highlights = config.highlights().unwrap();
+    if let Some(keywords) = arg_map.remove("highlight") {
+        log.info(format!("\t highlight: {}", keywords), "main");
+        for keyword in keywords.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+            highlights.add_keyword(keyword, Highlights::rule_style());
+        }
+    }
+    if let Some(pattern) = arg_map.remove("highlight_regex") {
+        log.info(format!("\t highlight regex: {}", pattern), "main");
+        highlights
+            .add_regex(pattern, Highlights::rule_style())
+            .unwrap_or_else(|err| {
+                eprintln!("ERROR: {}", err);
+                exit(1);
+            });
+    }
+    twitch_chat = twitch_chat
+        .highlights(highlights)
+        .badges(config.badge_theme().unwrap())
+        .keys(config.key_map().unwrap());
+    if let Some(format) = &config.timestamp_format {
+        twitch_chat = twitch_chat.timestamp_format(format);
+    }
+    match (args.command, args.file) {
+        (Command::Replay, Some(file)) => {
+            let speed =
+                Speed::parse(arg_map.remove("speed").unwrap_or("1")).unwrap_or_else(|err| {
+                    eprintln!("ERROR: {}", err);
+                    exit(1);
+                });
+            log.info(format!("\t replay: {}", file), "main");
+            twitch_chat = twitch_chat.replay(file, speed);
+        }
+        (Command::Record, Some(file)) => {
+            log.info(format!("\t record: {}", file), "main");
+            twitch_chat = twitch_chat.record(file);
+        }
+        _ => (),
+    }
+    let images_protocol = arg_map.remove("images").or(config.theme.images.as_deref());
+    // a replay is printed like tail does when a format is asked for
+    let result = match (args.command, arg_map.remove("format")) {
+        (Command::Tail, format) | (Command::Replay, format @ Some(_)) => {
+            let format = match format.unwrap_or("text") {
+                "text" if color(arg_map.remove("color").unwrap_or("auto")) => Format::Color,
+                "text" => Format::Plain,
+                "json" => Format::Json,
+                other => {
+                    eprintln!("ERROR: unknown format {}, use text or json", other);
+                    exit(1);
+                }
+            };
+            twitch_chat.tail(format)
+        }
+        _ => {
+            if let Some(protocol) = images_protocol {
+                log.info(format!("\t images: {}", protocol), "main");
+                twitch_chat = images(twitch_chat, protocol);
+            }
+            twitch_chat.start()
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("ERROR: {}", err);
+        log.close();
+        exit(1);
+    }
 
     log.close();
 }
 
+/// Splits a comma separated list of channels, a leading `#` is optional.
+fn channels(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|channel| channel.trim().trim_start_matches('#').to_lowercase())
+        .filter(|channel| !channel.is_empty())
+        .collect()
+}
+
+/// Whether tail colours its output, `auto` does so when writing to a
+/// terminal and NO_COLOR is not set.
+fn color(when: &str) -> bool {
+    match when {
+        "always" => true,
+        "never" => false,
+        "auto" => stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        other => {
+            eprintln!(
+                "ERROR: unknown colour setting {}, use auto, always or never",
+                other
+            );
+            exit(1);
+        }
+    }
+}
+
+/// Turns on emote images for the protocol given, or the one the terminal
+/// seems to support for `auto`. Without support the glyphs are kept.
+#[cfg(feature = "images")]
+fn images(twitch_chat: TwitchChat, protocol: &str) -> TwitchChat {
+    use emote_cache::EmoteCache;
+    use gui::graphics::{EmoteImages, Protocol};
+
+    let protocol = match protocol {
+        "auto" => Protocol::detect(),
+        name => Some(Protocol::parse(name).unwrap_or_else(|| {
+            eprintln!(
+                "ERROR: unknown image protocol {}, use kitty, iterm or sixel",
+                name
+            );
+            exit(1);
+        })),
+    };
+    let (protocol, dir) = match (protocol, EmoteCache::default_dir()) {
+        (Some(protocol), Some(dir)) => (protocol, dir),
+        (None, _) => {
+            eprintln!(
+                "WARNING: the terminal does not seem to support images, showing glyphs instead"
+            );
+            return twitch_chat;
+        }
+        (_, None) => {
+            eprintln!("WARNING: no directory to cache emote images in, showing glyphs instead");
+            return twitch_chat;
+        }
+    };
+    let cache = EmoteCache::new(dir);
+    #[cfg(feature = "tls")]
+    let cache = cache.with_fetcher();
+    twitch_chat.images(EmoteImages::new(protocol, cache))
+}
+
+#[cfg(not(feature = "images"))]
+fn images(twitch_chat: TwitchChat, _protocol: &str) -> TwitchChat {
+    eprintln!("WARNING: emote images need the images feature, showing glyphs instead");
+    twitch_chat
+}
+
 fn print_help() {
     println!("\u{1F608}");
     println!("NAME");
     println!("\t Twitch-Chat an amazing terminal user interface twitch chat listener");
     println!();
     println!("SYNOPSIS");
-    println!("\t twitch-chat [options]");
+    println!("\t twitch-chat [command] [options]");
+    println!();
+    println!("\t twitch-chat is a terminal based twitch chat listener. It is used to conviniently listen to twitch chat in a terminal window while streaming. It has color support and will display timestamps for each message. The OAuth token is read from the TWITCH_BOT_TOKEN env variable unless the config file says otherwise.");
     println!();
-    println!("\t twitch-chat is a terminal based twitch chat listener. It is used to conviniently listen to twitch chat in a terminal window while streaming. It has color support and will display timestamps for each message.");
+    println!("COMMANDS");
+    println!("\t tui");
+    println!("\t\t show the chat in the terminal user interface, the default");
+    println!("\t tail");
+    println!("\t\t print the chat line by line to stdout");
+    println!("\t replay <file>");
+    println!("\t\t play back a recorded session, channels default to those in the recording and neither nick nor token are needed");
+    println!("\t record <file>");
+    println!("\t\t show the chat like tui does and write every line the server sends to a file");
     println!();
     println!("EXAMPLES");
     println!("\t twitch-chat --nick=foobar --channel=flubber");
+    println!("\t twitch-chat --nick foobar --channel flubber,#dallas");
+    println!("\t twitch-chat tail --nick=foobar --channel=flubber");
+    println!("\t twitch-chat record session.log --nick=foobar --channel=flubber");
+    println!("\t twitch-chat replay session.log --speed=instant --format=json");
     println!();
     println!("OPTIONS");
+    println!("\t options taking a value accept both --option=value and --option value");
+    println!("\t -h, --help");
+    println!("\t\t print this help and exit");
+    println!("\t -V, --version");
+    println!("\t\t print the version and exit");
+    println!("\t --config");
+    println!("\t\t config file to read instead of $XDG_CONFIG_HOME/twitch-chat/config.toml, options given here win over the file");
     println!("\t --nick");
     println!("\t\t nick of the account the provided token is associated with.");
     println!("\t --channel");
-    println!("\t\t name of the channel you want to connect to");
+    println!(
+        "\t\t name of the channel you want to connect to, or a comma separated list of channels"
+    );
+    println!("\t --url");
+    println!("\t\t url to connect to instead of Twitch, ws:// and wss:// for websockets, irc:// and ircs:// for IRC over tcp. wss:// and ircs:// need the tls feature");
+    println!("\t --emotes");
+    println!("\t\t file with one `name = glyph` or `id:<emote id> = glyph` per line, the glyphs are shown instead of the emotes");
+    println!("\t --highlight");
+    println!("\t\t comma separated list of words, messages containing one of them are highlighted");
+    println!("\t --highlight-regex");
+    println!("\t\t messages matching this regular expression are highlighted");
+    println!("\t --images");
+    println!("\t\t show emotes as images in the chat window where the terminal supports it, --images=kitty, --images=iterm or --images=sixel picks the protocol. Needs the images feature");
+    println!("\t --color");
+    println!("\t\t colour the output of tail, --color=auto does so when writing to a terminal, --color=never turns it off. Default is auto");
+    println!("\t --format");
+    println!("\t\t what tail prints, --format=text for chat lines or --format=json for every event the server sends as a JSON object per line. Default is text. With replay the recording is printed this way instead of shown in the terminal user interface");
+    println!("\t --speed");
+    println!("\t\t how many times faster than it happened replay plays a recording back, like --speed=2, or --speed=instant for all at once. Default is 1");
+    println!("\t --irc");
+    println!("\t\t connect to Twitch with IRC over tcp instead of websockets");
     println!("\t --log");
-    println!("\t\t enable logging to file");
-    println!("\t --<log_level>");
-    println!("\t\t available levels, debug, info, warn, error trace, default is info");
+    println!("\t\t enable logging to twitch_chat.log, or the file set in the config");
+    println!("\t --trace, --debug, --info, --warn, --error");
+    println!("\t\t level to log at when logging is enabled, default is info");
 }
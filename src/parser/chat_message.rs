@@ -1,13 +1,18 @@
 use nom::{
-    bytes::complete::tag,
-    character::complete::{alpha1, multispace0},
-    combinator::rest,
+    bytes::complete::{is_not, tag},
+    character::complete::{alphanumeric1, multispace0, space1},
+    combinator::{map_res, opt, rest, verify},
     error::{context, ErrorKind, VerboseError},
+    multi::many0,
     sequence::preceded,
     AsChar, IResult, InputTakeAtPosition,
 };
 
-use super::{meta_data::MetaData, MessageType};
+use super::{
+    command::{Command, Parts},
+    meta_data::tags,
+    MessageType,
+};
 
 type Res<T, U> = IResult<T, U, VerboseError<T>>;
 
@@ -30,8 +35,6 @@ where
                 && char_item != '!'
                 && char_item != '@'
                 && char_item != '.'
-                && char_item != '.'
-                && char_item != '_'
                 && !char_item.is_alphanum()
         },
         ErrorKind::AlphaNumeric,
@@ -39,44 +42,54 @@ where
 }
 
 fn message_type(input: &str) -> Res<&str, MessageType> {
-    context("message-type", preceded(multispace0, alpha1))(input)
-        .map(|(next, value)| (next, value.into()))
-}
-
-fn destination(input: &str) -> Res<&str, &str> {
     context(
-        "destination",
-        preceded(multispace0, preceded(tag("#"), prefix_chars)),
+        "message-type",
+        preceded(multispace0, map_res(alphanumeric1, MessageType::try_from)),
     )(input)
 }
 
+fn param(input: &str) -> Res<&str, &str> {
+    verify(is_not(" "), |param: &str| !param.starts_with(':'))(input)
+}
+
+fn params(input: &str) -> Res<&str, Vec<&str>> {
+    context("params", many0(preceded(space1, param)))(input)
+}
+
 fn message(input: &str) -> Res<&str, &str> {
     context("message", preceded(multispace0, preceded(tag(":"), rest)))(input)
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ChatMessage<'a> {
-    pub meta_data: MetaData<'a>,
-    prefix: &'a str,
-    message_type: MessageType,
-    destination: &'a str,
-    pub message: &'a str,
+    pub prefix: Option<&'a str>,
+    pub command: Command<'a>,
 }
 
 impl<'a> ChatMessage<'a> {
-    pub fn parse(input: &str) -> Result<ChatMessage, nom::Err<VerboseError<&str>>> {
-        let (next, meta_data) = MetaData::new(input)?;
-        let (next, prefix) = prefix(next)?;
+    pub fn parse(input: &'a str) -> Result<ChatMessage<'a>, nom::Err<VerboseError<&'a str>>> {
+        let input = input.trim_end_matches(&['\r', '\n'][..]);
+        let (next, tags) = opt(tags)(input)?;
+        let (next, prefix) = opt(prefix)(next)?;
         let (next, message_type) = message_type(next)?;
-        let (next, destination) = destination(next)?;
-        let (_, message) = message(next)?;
-        Ok(ChatMessage {
+        let (next, params) = params(next)?;
+        let (_, trailing) = opt(message)(next)?;
+        let command = Command::new(
             message_type,
-            meta_data,
-            prefix,
-            destination,
-            message,
-        })
+            Parts {
+                input,
+                tags: tags.unwrap_or_default(),
+                prefix,
+                params,
+                trailing,
+            },
+        )?;
+        Ok(ChatMessage { prefix, command })
+    }
+
+    /// The channel the message was sent to, if it belongs to one.
+    pub fn destination(&self) -> Option<&'a str> {
+        self.command.destination()
     }
 }
 
@@ -84,6 +97,26 @@ impl<'a> ChatMessage<'a> {
 mod test {
 
     use super::*;
+    use crate::parser::{command::*, user_notice::UserNoticeEvent};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn should_know_destination() {
+        let destination = |input| ChatMessage::parse(input).unwrap().destination();
+        assert_eq!(
+            destination(":foo!foo@foo.tmi.twitch.tv JOIN #dallas"),
+            Some("dallas")
+        );
+        assert_eq!(
+            destination(":tmi.twitch.tv CLEARCHAT #forsen :foo"),
+            Some("forsen")
+        );
+        assert_eq!(
+            destination(":tmi.twitch.tv NOTICE * :Login authentication failed"),
+            None
+        );
+        assert_eq!(destination("PING :tmi.twitch.tv"), None);
+    }
 
     #[test]
     fn test_prefix() {
@@ -103,4 +136,212 @@ mod test {
             ))
         )
     }
+
+    #[test]
+    fn should_parse_privmsg() {
+        let message = ChatMessage::parse("@badge-info=;badges=;color=;display-name=leprajon;emotes=;first-msg=0;flags=;id=d2-0b;mod=0;returning-chatter=0;room-id=4749;subscriber=0;tmi-sent-ts=1646864986;turbo=0;user-id=149;user-type= :leprajon!leprajon@leprajon.tmi.twitch.tv PRIVMSG #toerktumlare :hello chat\r\n").unwrap();
+        assert_eq!(
+            message.prefix,
+            Some("leprajon!leprajon@leprajon.tmi.twitch.tv")
+        );
+        match message.command {
+            Command::PrivMsg(msg) => {
+                assert_eq!(msg.destination, "toerktumlare");
+                assert_eq!(msg.message, "hello chat");
+                assert_eq!(msg.meta_data.user_info.display_name, Some("leprajon"));
+            }
+            command => panic!("expected PRIVMSG, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn should_parse_colors_in_either_case() {
+        let message = ChatMessage::parse("@color=#ff00aa;display-name=leprajon;id=d2-0b;room-id=4749;tmi-sent-ts=1646864986;user-id=149 :leprajon!leprajon@leprajon.tmi.twitch.tv PRIVMSG #toerktumlare :hello").unwrap();
+        match message.command {
+            Command::PrivMsg(msg) => {
+                assert_eq!(msg.meta_data.user_info.color, Some((255, 0, 170)))
+            }
+            command => panic!("expected PRIVMSG, got {:?}", command),
+        }
+        let message =
+            ChatMessage::parse("@color=#FFF;display-name=Ronni :tmi.twitch.tv USERSTATE #dallas")
+                .unwrap();
+        match message.command {
+            Command::UserState(state) => assert_eq!(state.color, None),
+            command => panic!("expected USERSTATE, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn should_parse_clearchat() {
+        assert_eq!(
+            ChatMessage::parse("@ban-duration=350;room-id=12345678;target-user-id=87654321;tmi-sent-ts=1642715756806 :tmi.twitch.tv CLEARCHAT #dallas :ronni").unwrap(),
+            ChatMessage {
+                prefix: Some("tmi.twitch.tv"),
+                command: Command::ClearChat(ClearChat {
+                    channel: "dallas",
+                    target_user: Some("ronni"),
+                    ban_duration: Some(350),
+                    room_id: Some(12345678),
+                    target_user_id: Some(87654321),
+                    tmi_sent_ts: Some(Utc.timestamp(1642715756, 0)),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn should_parse_clearchat_for_whole_room() {
+        assert_eq!(
+            ChatMessage::parse(
+                "@room-id=12345678;tmi-sent-ts=1642715695392 :tmi.twitch.tv CLEARCHAT #dallas"
+            )
+            .unwrap()
+            .command,
+            Command::ClearChat(ClearChat {
+                channel: "dallas",
+                target_user: None,
+                ban_duration: None,
+                room_id: Some(12345678),
+                target_user_id: None,
+                tmi_sent_ts: Some(Utc.timestamp(1642715695, 0)),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_clearmsg() {
+        assert_eq!(
+            ChatMessage::parse("@login=foo;room-id=;target-msg-id=94e6c7ff-bf98-4faa-af5d-7ad633a158a9;tmi-sent-ts=1642720582342 :tmi.twitch.tv CLEARMSG #bar :what a great day").unwrap().command,
+            Command::ClearMsg(ClearMsg {
+                channel: "bar",
+                login: Some("foo"),
+                target_msg_id: Some("94e6c7ff-bf98-4faa-af5d-7ad633a158a9"),
+                message: Some("what a great day"),
+                tmi_sent_ts: Some(Utc.timestamp(1642720582, 0)),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_roomstate() {
+        assert_eq!(
+            ChatMessage::parse("@emote-only=0;followers-only=-1;r9k=0;room-id=12345678;slow=0;subs-only=0 :tmi.twitch.tv ROOMSTATE #bar").unwrap().command,
+            Command::RoomState(RoomState {
+                channel: "bar",
+                emote_only: Some(false),
+                followers_only: Some(-1),
+                r9k: Some(false),
+                room_id: Some(12345678),
+                slow: Some(0),
+                subs_only: Some(false),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_usernotice() {
+        let message = ChatMessage::parse("@badge-info=;badges=staff/1,broadcaster/1;color=#008000;display-name=ronni;emotes=;id=db25007f-7a18-43eb-9379-80131e44d633;login=ronni;mod=0;msg-id=resub;msg-param-cumulative-months=6;room-id=12345678;subscriber=1;system-msg=ronni\\shas\\ssubscribed;tmi-sent-ts=1507246572675;user-id=87654321;user-type=staff :tmi.twitch.tv USERNOTICE #dallas :Great stream -- keep it up!").unwrap();
+        match message.command {
+            Command::UserNotice(notice) => {
+                assert_eq!(notice.channel, "dallas");
+                assert_eq!(notice.msg_id, "resub");
+                assert!(matches!(notice.event, UserNoticeEvent::Resub(_)));
+                assert_eq!(notice.system_msg.as_deref(), Some("ronni has subscribed"));
+                assert_eq!(notice.login, Some("ronni"));
+                assert_eq!(notice.color, Some((0, 128, 0)));
+                assert_eq!(notice.message, Some("Great stream -- keep it up!"));
+            }
+            command => panic!("expected USERNOTICE, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn should_parse_notice_without_channel() {
+        assert_eq!(
+            ChatMessage::parse(":tmi.twitch.tv NOTICE * :Login authentication failed")
+                .unwrap()
+                .command,
+            Command::Notice(Notice {
+                channel: None,
+                msg_id: None,
+                target_user_id: None,
+                message: "Login authentication failed",
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_hosttarget() {
+        assert_eq!(
+            ChatMessage::parse(":tmi.twitch.tv HOSTTARGET #abc :- 10")
+                .unwrap()
+                .command,
+            Command::HostTarget(HostTarget {
+                channel: "abc",
+                target: None,
+                viewers: Some(10),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_join_and_part() {
+        assert_eq!(
+            ChatMessage::parse(":ronni!ronni@ronni.tmi.twitch.tv JOIN #dallas")
+                .unwrap()
+                .command,
+            Command::Join(Membership {
+                nick: "ronni",
+                channel: "dallas",
+            })
+        );
+        assert_eq!(
+            ChatMessage::parse(":ronni!ronni@ronni.tmi.twitch.tv PART #dallas")
+                .unwrap()
+                .command,
+            Command::Part(Membership {
+                nick: "ronni",
+                channel: "dallas",
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_numeric_replies() {
+        assert_eq!(
+            ChatMessage::parse(":foo.tmi.twitch.tv 353 foo = #bar :foo")
+                .unwrap()
+                .command,
+            Command::Numeric(Numeric {
+                code: 353,
+                params: vec!["foo", "=", "#bar"],
+                message: Some("foo"),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_reconnect_and_cap() {
+        assert_eq!(
+            ChatMessage::parse(":tmi.twitch.tv RECONNECT")
+                .unwrap()
+                .command,
+            Command::Reconnect
+        );
+        assert_eq!(
+            ChatMessage::parse(":tmi.twitch.tv CAP * ACK :twitch.tv/tags")
+                .unwrap()
+                .command,
+            Command::Cap(Cap {
+                subcommand: "ACK",
+                capabilities: vec!["twitch.tv/tags"],
+            })
+        );
+    }
+
+    #[test]
+    fn should_return_error_for_unknown_command() {
+        assert!(ChatMessage::parse(":tmi.twitch.tv FOOBAR #bar :baz").is_err());
+    }
 }
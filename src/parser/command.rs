@@ -0,0 +1,396 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, TimeZone, Utc};
+use nom::{
+    combinator::all_consuming,
+    error::{VerboseError, VerboseErrorKind},
+};
+
+use super::{
+    meta_data::{badge_list, hex_to_rgb, MetaData},
+    tags::{self, Tags},
+    user_notice::UserNoticeEvent,
+    Badges, MessageType,
+};
+
+type ParseErr<'a> = nom::Err<VerboseError<&'a str>>;
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    PrivMsg(PrivMsg<'a>),
+    ClearChat(ClearChat<'a>),
+    ClearMsg(ClearMsg<'a>),
+    GlobalUserState(GlobalUserState<'a>),
+    HostTarget(HostTarget<'a>),
+    Notice(Notice<'a>),
+    Reconnect,
+    RoomState(RoomState<'a>),
+    UserNotice(UserNotice<'a>),
+    UserState(UserState<'a>),
+    Whisper(Whisper<'a>),
+    Join(Membership<'a>),
+    Part(Membership<'a>),
+    Cap(Cap<'a>),
+    Ping(Option<&'a str>),
+    Pong(Option<&'a str>),
+    Numeric(Numeric<'a>),
+}
+
+impl<'a> Command<'a> {
+    /// The channel the command was sent to, if it belongs to one.
+    pub fn destination(&self) -> Option<&'a str> {
+        match self {
+            Command::PrivMsg(message) => Some(message.destination),
+            Command::ClearChat(clear) => Some(clear.channel),
+            Command::ClearMsg(clear) => Some(clear.channel),
+            Command::HostTarget(host) => Some(host.channel),
+            Command::Notice(notice) => notice.channel,
+            Command::RoomState(state) => Some(state.channel),
+            Command::UserNotice(notice) => Some(notice.channel),
+            Command::UserState(state) => Some(state.channel),
+            Command::Join(membership) | Command::Part(membership) => Some(membership.channel),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct PrivMsg<'a> {
+    pub meta_data: MetaData<'a>,
+    pub destination: &'a str,
+    pub message: &'a str,
+    /// Login of the sender, taken from the prefix.
+    pub login: Option<&'a str>,
+}
+
+impl<'a> PrivMsg<'a> {
+    /// The name to show for the sender, the login when the display name is
+    /// missing or empty.
+    pub fn name(&self) -> &'a str {
+        self.meta_data
+            .user_info
+            .display_name
+            .or(self.login)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClearChat<'a> {
+    pub channel: &'a str,
+    pub target_user: Option<&'a str>,
+    pub ban_duration: Option<u32>,
+    pub room_id: Option<u32>,
+    pub target_user_id: Option<u32>,
+    pub tmi_sent_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClearMsg<'a> {
+    pub channel: &'a str,
+    pub login: Option<&'a str>,
+    pub target_msg_id: Option<&'a str>,
+    pub message: Option<&'a str>,
+    pub tmi_sent_ts: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct GlobalUserState<'a> {
+    pub display_name: Option<&'a str>,
+    pub user_id: Option<u32>,
+    pub color: Option<(u8, u8, u8)>,
+    pub badges: Vec<(Badges, &'a str)>,
+    pub emote_sets: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct HostTarget<'a> {
+    pub channel: &'a str,
+    pub target: Option<&'a str>,
+    pub viewers: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Notice<'a> {
+    pub channel: Option<&'a str>,
+    pub msg_id: Option<&'a str>,
+    pub target_user_id: Option<u32>,
+    pub message: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RoomState<'a> {
+    pub channel: &'a str,
+    pub emote_only: Option<bool>,
+    pub followers_only: Option<i32>,
+    pub r9k: Option<bool>,
+    pub room_id: Option<u32>,
+    pub slow: Option<u32>,
+    pub subs_only: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UserNotice<'a> {
+    pub channel: &'a str,
+    pub msg_id: &'a str,
+    pub event: UserNoticeEvent<'a>,
+    pub id: Option<&'a str>,
+    pub login: Option<&'a str>,
+    pub display_name: Option<&'a str>,
+    pub color: Option<(u8, u8, u8)>,
+    pub user_id: Option<u32>,
+    pub system_msg: Option<Cow<'a, str>>,
+    pub tmi_sent_ts: Option<DateTime<Utc>>,
+    pub message: Option<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UserState<'a> {
+    pub channel: &'a str,
+    pub display_name: Option<&'a str>,
+    pub color: Option<(u8, u8, u8)>,
+    pub badges: Vec<(Badges, &'a str)>,
+    pub emote_sets: Vec<&'a str>,
+    pub moderator: bool,
+    pub subscriber: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Whisper<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub display_name: Option<&'a str>,
+    pub user_id: Option<u32>,
+    pub color: Option<(u8, u8, u8)>,
+    pub message_id: Option<&'a str>,
+    pub thread_id: Option<&'a str>,
+    pub message: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Membership<'a> {
+    pub nick: &'a str,
+    pub channel: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cap<'a> {
+    pub subcommand: &'a str,
+    pub capabilities: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Numeric<'a> {
+    pub code: u16,
+    pub params: Vec<&'a str>,
+    pub message: Option<&'a str>,
+}
+
+/// The pieces of an IRC line that every command is built from.
+pub(super) struct Parts<'a> {
+    pub input: &'a str,
+    pub tags: Tags<'a>,
+    pub prefix: Option<&'a str>,
+    pub params: Vec<&'a str>,
+    pub trailing: Option<&'a str>,
+}
+
+impl<'a> Parts<'a> {
+    fn param(&self, index: usize, name: &'static str) -> Result<&'a str, ParseErr<'a>> {
+        self.params
+            .get(index)
+            .copied()
+            .ok_or_else(|| missing(self.input, name))
+    }
+
+    fn channel(&self) -> Result<&'a str, ParseErr<'a>> {
+        self.param(0, "channel").map(channel)
+    }
+
+    fn trailing(&self, name: &'static str) -> Result<&'a str, ParseErr<'a>> {
+        self.trailing.ok_or_else(|| missing(self.input, name))
+    }
+
+    fn nick(&self) -> Result<&'a str, ParseErr<'a>> {
+        self.prefix
+            .and_then(|prefix| prefix.split('!').next())
+            .filter(|nick| !nick.is_empty())
+            .ok_or_else(|| missing(self.input, "nick"))
+    }
+
+    fn color(&self) -> Option<(u8, u8, u8)> {
+        self.tags
+            .value(tags::COLOR)
+            .and_then(|v| all_consuming(hex_to_rgb)(v).ok())
+            .map(|(_, color)| color)
+    }
+
+    fn badges(&self) -> Vec<(Badges, &'a str)> {
+        self.tags
+            .value(tags::BADGES)
+            .and_then(|v| badge_list(v).ok())
+            .map(|(_, badges)| badges)
+            .unwrap_or_default()
+    }
+
+    fn emote_sets(&self) -> Vec<&'a str> {
+        self.tags
+            .value(tags::EMOTE_SETS)
+            .map(|v| v.split(',').collect())
+            .unwrap_or_default()
+    }
+
+    fn tmi_sent_ts(&self) -> Option<DateTime<Utc>> {
+        self.tags.value(tags::TMI_SENT_TS).and_then(timestamp)
+    }
+}
+
+impl<'a> Command<'a> {
+    pub(super) fn new(
+        message_type: MessageType,
+        parts: Parts<'a>,
+    ) -> Result<Command<'a>, ParseErr<'a>> {
+        let command = match message_type {
+            MessageType::PrivMsg => {
+                let destination = parts.channel()?;
+                let message = parts.trailing("message")?;
+                let login = parts.nick().ok();
+                Command::PrivMsg(PrivMsg {
+                    meta_data: MetaData::from_tags(parts.tags)?,
+                    destination,
+                    message,
+                    login,
+                })
+            }
+            MessageType::ClearChat => Command::ClearChat(ClearChat {
+                channel: parts.channel()?,
+                target_user: parts.trailing,
+                ban_duration: parts.tags.parse(tags::BAN_DURATION),
+                room_id: parts.tags.parse(tags::ROOM_ID),
+                target_user_id: parts.tags.parse(tags::TARGET_USER_ID),
+                tmi_sent_ts: parts.tmi_sent_ts(),
+            }),
+            MessageType::ClearMsg => Command::ClearMsg(ClearMsg {
+                channel: parts.channel()?,
+                login: parts.tags.value(tags::LOGIN),
+                target_msg_id: parts.tags.value(tags::TARGET_MSG_ID),
+                message: parts.trailing,
+                tmi_sent_ts: parts.tmi_sent_ts(),
+            }),
+            MessageType::GlobalUserState => Command::GlobalUserState(GlobalUserState {
+                display_name: parts.tags.value(tags::DISPLAY_NAME),
+                user_id: parts.tags.parse(tags::USER_ID),
+                color: parts.color(),
+                badges: parts.badges(),
+                emote_sets: parts.emote_sets(),
+            }),
+            MessageType::HostTarget => {
+                let mut host = parts.trailing("host target")?.split_whitespace();
+                Command::HostTarget(HostTarget {
+                    channel: parts.channel()?,
+                    target: host.next().filter(|target| *target != "-"),
+                    viewers: host.next().and_then(|v| v.parse().ok()),
+                })
+            }
+            MessageType::Notice => Command::Notice(Notice {
+                channel: parts
+                    .params
+                    .first()
+                    .filter(|param| param.starts_with('#'))
+                    .map(|param| channel(param)),
+                msg_id: parts.tags.value(tags::MSG_ID),
+                target_user_id: parts.tags.parse(tags::TARGET_USER_ID),
+                message: parts.trailing("message")?,
+            }),
+            MessageType::Reconnect => Command::Reconnect,
+            MessageType::RoomState => Command::RoomState(RoomState {
+                channel: parts.channel()?,
+                emote_only: parts.tags.flag(tags::EMOTE_ONLY),
+                followers_only: parts.tags.parse(tags::FOLLOWERS_ONLY),
+                r9k: parts.tags.flag(tags::R9K),
+                room_id: parts.tags.parse(tags::ROOM_ID),
+                slow: parts.tags.parse(tags::SLOW),
+                subs_only: parts.tags.flag(tags::SUBS_ONLY),
+            }),
+            MessageType::UserNotice => {
+                let msg_id = parts
+                    .tags
+                    .value(tags::MSG_ID)
+                    .ok_or_else(|| missing(parts.input, tags::MSG_ID))?;
+                Command::UserNotice(UserNotice {
+                    channel: parts.channel()?,
+                    msg_id,
+                    event: UserNoticeEvent::new(msg_id, &parts.tags),
+                    id: parts.tags.value(tags::ID),
+                    login: parts.tags.value(tags::LOGIN),
+                    display_name: parts.tags.value(tags::DISPLAY_NAME),
+                    color: parts.color(),
+                    user_id: parts.tags.parse(tags::USER_ID),
+                    system_msg: parts.tags.unescaped(tags::SYSTEM_MSG),
+                    tmi_sent_ts: parts.tmi_sent_ts(),
+                    message: parts.trailing,
+                })
+            }
+            MessageType::UserState => Command::UserState(UserState {
+                channel: parts.channel()?,
+                display_name: parts.tags.value(tags::DISPLAY_NAME),
+                color: parts.color(),
+                badges: parts.badges(),
+                emote_sets: parts.emote_sets(),
+                moderator: parts.tags.flag(tags::MODERATOR).unwrap_or(false),
+                subscriber: parts.tags.flag(tags::SUBSCRIBER).unwrap_or(false),
+            }),
+            MessageType::Whisper => Command::Whisper(Whisper {
+                from: parts.nick()?,
+                to: parts.param(0, "to")?,
+                display_name: parts.tags.value(tags::DISPLAY_NAME),
+                user_id: parts.tags.parse(tags::USER_ID),
+                color: parts.color(),
+                message_id: parts.tags.value(tags::MESSAGE_ID),
+                thread_id: parts.tags.value(tags::THREAD_ID),
+                message: parts.trailing("message")?,
+            }),
+            MessageType::Join => Command::Join(Membership {
+                nick: parts.nick()?,
+                channel: parts.channel()?,
+            }),
+            MessageType::Part => Command::Part(Membership {
+                nick: parts.nick()?,
+                channel: parts.channel()?,
+            }),
+            MessageType::Cap => Command::Cap(Cap {
+                subcommand: parts.param(1, "cap subcommand")?,
+                capabilities: parts
+                    .trailing
+                    .map(|caps| caps.split_whitespace().collect())
+                    .unwrap_or_default(),
+            }),
+            MessageType::Ping => Command::Ping(parts.trailing.or(parts.params.first().copied())),
+            MessageType::Pong => Command::Pong(parts.trailing.or(parts.params.last().copied())),
+            MessageType::Numeric(code) => Command::Numeric(Numeric {
+                code,
+                params: parts.params,
+                message: parts.trailing,
+            }),
+        };
+        Ok(command)
+    }
+}
+
+fn channel(param: &str) -> &str {
+    param.strip_prefix('#').unwrap_or(param)
+}
+
+fn timestamp(value: &str) -> Option<DateTime<Utc>> {
+    value
+        .get(0..10)
+        .and_then(|seconds| seconds.parse::<i64>().ok())
+        .map(|seconds| Utc.timestamp(seconds, 0))
+}
+
+fn missing<'a>(input: &'a str, name: &'static str) -> ParseErr<'a> {
+    nom::Err::Error(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(name))],
+    })
+}
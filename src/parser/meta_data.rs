@@ -1,18 +1,21 @@
-use super::tags;
+use std::borrow::Cow;
+
+use super::tags::{self, Tags};
 use chrono::{DateTime, TimeZone, Utc};
 use nom::{
-    branch::alt,
-    bytes::complete::{tag, take_till},
-    character::complete::{alphanumeric0, alphanumeric1, digit1, one_of},
-    combinator::opt,
-    error::{context, ErrorKind, ParseError, VerboseError},
-    multi::{many1, separated_list0},
-    sequence::{preceded, separated_pair, tuple},
-    AsChar, IResult, InputTakeAtPosition, Parser,
+    bytes::complete::{is_not, tag, take_till, take_while_m_n},
+    character::complete::digit1,
+    combinator::{all_consuming, map_opt, map_res, opt},
+    error::{context, ErrorKind, VerboseError, VerboseErrorKind},
+    multi::separated_list0,
+    sequence::{pair, preceded, separated_pair, terminated, tuple},
+    AsChar, IResult, InputTakeAtPosition,
 };
 
 use super::{Badges, Emote};
 
+type ParseErr<'a> = nom::Err<VerboseError<&'a str>>;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct MetaData<'a> {
     pub badge_info: Vec<(Badges, &'a str)>,
@@ -30,119 +33,69 @@ pub struct MetaData<'a> {
     pub user_id: u32,
     pub user_type: Option<&'a str>,
     pub user_info: UserInfo<'a>,
+    pub tags: Tags<'a>,
 }
 
 impl<'a> MetaData<'a> {
-    pub fn new(input: &'a str) -> Res<&str, MetaData> {
-        context(
-            "MetaData",
-            tuple((
-                badge_info,
-                badges,
-                opt(client_nonce),
-                opt(bits),
-                opt(color),
-                display_name,
-                opt(emote_only),
-                emotes,
-                first_msg,
-                opt(flags),
-                id,
-                moderator,
-                returning_chatter,
-                tuple((
-                    opt(sep_pair(tags::REPLY_PARENT_DISPLAY_NAME, username)),
-                    opt(sep_pair(tags::REPLY_PARENT_MSG_BODY, parse_msg_body)),
-                    opt(sep_pair(tags::REPLY_PARENT_MSG_ID, alphanumerichyphen1)),
-                    opt(sep_pair(tags::REPLY_PARENT_USER_ID, digit1).map(|v| v.parse().unwrap())),
-                    opt(sep_pair(tags::REPLY_PARENT_USER_LOGIN, username)),
-                )),
-                room_id,
-                subscriber,
-                tmi_sent_ts,
-                turbo,
-                user_id,
-                opt(user_type),
-            )),
-        )(input)
-        .map(
-            |(
-                next,
-                (
-                    badge_info,
-                    badges,
-                    client_nonce,
-                    bits,
-                    color,
-                    display_name,
-                    emote_only,
-                    emotes,
-                    first_msg,
-                    flags,
-                    id,
-                    moderator,
-                    returning_chatter,
-                    (
-                        reply_parent_display_name,
-                        reply_parent_msg_body,
-                        reply_parent_msg_id,
-                        reply_parent_user_id,
-                        reply_parent_user_login,
-                    ),
-                    room_id,
-                    subscriber,
-                    tmi_sent_ts,
-                    turbo,
-                    user_id,
-                    user_type,
-                ),
-            )| {
-                let color = color.flatten();
-                (
-                    next,
-                    MetaData {
-                        badge_info,
-                        client_nonce,
-                        bits,
-                        emote_only,
-                        emotes,
-                        first_msg,
-                        flags,
-                        id,
-                        returning_chatter,
-                        reply: Reply {
-                            display_name: reply_parent_display_name,
-                            msg_body: reply_parent_msg_body,
-                            msg_id: reply_parent_msg_id,
-                            user_id: reply_parent_user_id,
-                            user_login: reply_parent_user_login,
-                        },
-                        room_id,
-                        tmi_sent_ts,
-                        user_id,
-                        user_type,
-                        user_info: UserInfo {
-                            display_name,
-                            subscriber,
-                            moderator,
-                            color,
-                            badges,
-                            turbo,
-                        },
-                    },
-                )
+    /// Parses the tags at the start of a line, only the tests start there,
+    /// `ChatMessage` splits the line itself.
+    #[cfg(test)]
+    pub fn new(input: &'a str) -> Res<&'a str, MetaData<'a>> {
+        let (next, tags) = tags(input)?;
+        let meta_data = MetaData::from_tags(tags)?;
+        Ok((next, meta_data))
+    }
+
+    /// Builds the PRIVMSG meta data from an already split tag map, the order of
+    /// the tags does not matter and tags we do not know about are kept in `tags`.
+    pub fn from_tags(tags: Tags<'a>) -> Result<MetaData<'a>, ParseErr<'a>> {
+        Ok(MetaData {
+            badge_info: field(&tags, tags::BADGE_INFO, badge_list)?.unwrap_or_default(),
+            client_nonce: tags.value(tags::CLIENT_NONCE),
+            bits: number(&tags, tags::BITS)?,
+            emote_only: flag(&tags, tags::EMOTE_ONLY)?,
+            emotes: field(&tags, tags::EMOTES, emote_list)?.unwrap_or_default(),
+            first_msg: flag(&tags, tags::FIRST_MSG)?.unwrap_or(false),
+            flags: tags
+                .raw(tags::FLAGS)
+                .map(|value| Some(value).filter(|v| !v.is_empty())),
+            id: required(&tags, tags::ID, tags.value(tags::ID))?,
+            returning_chatter: flag(&tags, tags::RETURNING_CHATTER)?.unwrap_or(false),
+            reply: Reply {
+                display_name: tags.value(tags::REPLY_PARENT_DISPLAY_NAME),
+                msg_body: tags.unescaped(tags::REPLY_PARENT_MSG_BODY),
+                msg_id: tags.value(tags::REPLY_PARENT_MSG_ID),
+                user_id: number(&tags, tags::REPLY_PARENT_USER_ID)?,
+                user_login: tags.value(tags::REPLY_PARENT_USER_LOGIN),
             },
-        )
+            room_id: required(&tags, tags::ROOM_ID, number(&tags, tags::ROOM_ID)?)?,
+            tmi_sent_ts: required(
+                &tags,
+                tags::TMI_SENT_TS,
+                field(&tags, tags::TMI_SENT_TS, tmi_sent_ts)?,
+            )?,
+            user_id: required(&tags, tags::USER_ID, number(&tags, tags::USER_ID)?)?,
+            user_type: tags.raw(tags::USER_TYPE),
+            user_info: UserInfo {
+                display_name: tags.value(tags::DISPLAY_NAME),
+                subscriber: flag(&tags, tags::SUBSCRIBER)?.unwrap_or(false),
+                moderator: flag(&tags, tags::MODERATOR)?.unwrap_or(false),
+                color: field(&tags, tags::COLOR, hex_to_rgb)?,
+                badges: field(&tags, tags::BADGES, badge_list)?.unwrap_or_default(),
+                turbo: flag(&tags, tags::TURBO)?.unwrap_or(false),
+            },
+            tags,
+        })
     }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Reply<'a> {
-    display_name: Option<&'a str>,
-    msg_body: Option<&'a str>,
-    msg_id: Option<&'a str>,
-    user_id: Option<u32>,
-    user_login: Option<&'a str>,
+    pub display_name: Option<&'a str>,
+    pub msg_body: Option<Cow<'a, str>>,
+    pub msg_id: Option<&'a str>,
+    pub user_id: Option<u32>,
+    pub user_login: Option<&'a str>,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -157,184 +110,68 @@ pub struct UserInfo<'a> {
 
 type Res<T, U> = IResult<T, U, VerboseError<T>>;
 
-fn alphanumerichyphen1<T>(i: T) -> Res<T, T>
-where
-    T: InputTakeAtPosition,
-    <T as InputTakeAtPosition>::Item: AsChar,
-{
-    i.split_at_position1_complete(
-        |item| {
-            let char_item = item.as_char();
-            char_item != '-' && char_item != '_' && char_item != '.' && !char_item.is_alphanum()
-        },
-        ErrorKind::AlphaNumeric,
-    )
-}
-
-fn username<T>(i: T) -> Res<T, T>
-where
-    T: InputTakeAtPosition,
-    <T as InputTakeAtPosition>::Item: AsChar,
-{
-    i.split_at_position1_complete(
-        |item| {
-            let char_item = item.as_char();
-            char_item != '_' && !char_item.is_alphanum()
-        },
-        ErrorKind::AlphaNumeric,
-    )
-}
-fn alphanumerichyphenbackslash1<T>(i: T) -> Res<T, T>
-where
-    T: InputTakeAtPosition,
-    <T as InputTakeAtPosition>::Item: AsChar,
-{
-    i.split_at_position1_complete(
-        |item| {
-            let char_item = item.as_char();
-            char_item != '-' && char_item != '_' && char_item != '\\' && !char_item.is_alphanum()
-        },
-        ErrorKind::AlphaNumeric,
-    )
-}
-
-fn badge_info(input: &str) -> Res<&str, Vec<(Badges, &str)>> {
-    context(
-        tags::BADGE_INFO,
-        preceded(
-            opt(alt((tag("@"), tag(";")))),
-            separated_pair(
-                tag(tags::BADGE_INFO),
-                tag("="),
-                separated_list0(tag(","), badge_format),
-            ),
-        ),
-    )(input)
-    .map(|(next, (_, value))| (next, value))
-}
-
-fn badges(input: &str) -> Res<&str, Vec<(Badges, &str)>> {
+/// Splits the `@key=value;key=value` tag section of a message into a map, the
+/// remaining input starts at the space in front of the prefix.
+pub fn tags(input: &str) -> Res<&str, Tags<'_>> {
     context(
-        tags::BADGES,
+        "tags",
         preceded(
-            opt(alt((tag("@"), tag(";")))),
-            separated_pair(
-                tag(tags::BADGES),
-                tag("="),
-                separated_list0(tag(","), badge_format),
-            ),
+            tag("@"),
+            terminated(separated_list0(tag(";"), tag_pair), opt(tag(";"))),
         ),
     )(input)
-    .map(|(next, (_, value))| (next, value))
-}
-
-fn badge_format(input: &str) -> Res<&str, (Badges, &str)> {
-    context(
-        tags::BADGE_FORMAT,
-        separated_pair(alphanumerichyphen1, tag("/"), alphanumerichyphenbackslash1),
-    )(input)
-    .map(|(next, (badge, version))| (next, (badge.into(), version)))
-}
-
-fn color(input: &str) -> Res<&str, Option<(u8, u8, u8)>> {
-    context(
-        tags::COLOR,
-        preceded(
-            alt((tag("@"), tag(";"))),
-            separated_pair(tag(tags::COLOR), tag("="), opt(hex_to_rgb)),
-        ),
-    )(input)
-    .map(|(next, (_, value))| (next, value))
-}
-
-fn hex_to_rgb(input: &str) -> Res<&str, (u8, u8, u8)> {
-    context(
-        "parse hex to rgb",
-        preceded(tag("#"), many1(one_of("0123456789ABCDEF"))),
-    )(input)
-    .map(|(next, result)| {
-        let r = format!("{}{}", result[0], result[1]);
-        let g = format!("{}{}", result[2], result[3]);
-        let b = format!("{}{}", result[4], result[5]);
-
-        let r = u8::from_str_radix(r.as_str(), 16).unwrap();
-        let g = u8::from_str_radix(g.as_str(), 16).unwrap();
-        let b = u8::from_str_radix(b.as_str(), 16).unwrap();
-
-        (next, (r, g, b))
+    .map(|(next, pairs)| {
+        let raw = &input[1..input.len() - next.len()];
+        (next, Tags::new(raw, pairs))
     })
 }
 
-fn bits(input: &str) -> Res<&str, u32> {
-    sep_pair(tags::BITS, digit1)
-        .parse(input)
-        .map(|(next, value)| (next, value.parse::<u32>().unwrap()))
-}
-
-fn display_name(input: &str) -> Res<&str, Option<&str>> {
+fn tag_pair(input: &str) -> Res<&str, (&str, &str)> {
     context(
-        tags::DISPLAY_NAME,
-        preceded(
-            tag(";"),
-            separated_pair(tag(tags::DISPLAY_NAME), tag("="), opt(username)),
-        ),
+        "tag",
+        pair(is_not("=; "), opt(preceded(tag("="), tag_value))),
     )(input)
-    .map(|(next, (_, value))| (next, value))
+    .map(|(next, (key, value))| (next, (key, value.unwrap_or_default())))
 }
 
-fn emote_only(input: &str) -> Res<&str, bool> {
-    sep_pair_to_bool(tags::EMOTE_ONLY, digit1).parse(input)
+fn tag_value(input: &str) -> Res<&str, &str> {
+    take_till(|c| c == ';' || c == ' ')(input)
 }
 
-fn client_nonce(input: &str) -> Res<&str, &str> {
-    sep_pair(tags::CLIENT_NONCE, alphanumeric1)
-        .parse(input)
-        .map(|(next, result)| (next, result))
+fn field<'a, T>(
+    tags: &Tags<'a>,
+    key: &'static str,
+    parser: impl FnMut(&'a str) -> Res<&'a str, T>,
+) -> Result<Option<T>, ParseErr<'a>> {
+    tags.value(key)
+        .map(|value| context(key, all_consuming(parser))(value).map(|(_, result)| result))
+        .transpose()
 }
 
-fn room_id(input: &str) -> Res<&str, u32> {
-    context(
-        tags::ROOM_ID,
-        preceded(
-            tag(";"),
-            separated_pair(tag(tags::ROOM_ID), tag("="), digit1),
-        ),
-    )(input)
-    .map(|(next, (_, result))| (next, result.parse::<u32>().unwrap()))
+fn number<'a, T: std::str::FromStr>(
+    tags: &Tags<'a>,
+    key: &'static str,
+) -> Result<Option<T>, ParseErr<'a>> {
+    field(tags, key, map_res(digit1, str::parse::<T>))
 }
 
-fn user_id(input: &str) -> Res<&str, u32> {
-    context(
-        tags::USER_ID,
-        preceded(
-            tag(";"),
-            separated_pair(tag(tags::USER_ID), tag("="), digit1),
-        ),
-    )(input)
-    .map(|(next, (_, result))| (next, result.parse::<u32>().unwrap()))
+fn flag<'a>(tags: &Tags<'a>, key: &'static str) -> Result<Option<bool>, ParseErr<'a>> {
+    number::<u32>(tags, key).map(|value| value.map(|v| v != 0))
 }
 
-fn tmi_sent_ts(input: &str) -> Res<&str, DateTime<Utc>> {
-    context(
-        "tmi-sent-ts",
-        preceded(
-            tag(";"),
-            separated_pair(tag("tmi-sent-ts"), tag("="), digit1),
-        ),
-    )(input)
-    .map(|(next, (_, result))| {
-        (
-            next,
-            Utc.timestamp(result[0..10].parse::<i64>().unwrap(), 0),
-        )
+fn required<'a, T>(
+    tags: &Tags<'a>,
+    key: &'static str,
+    value: Option<T>,
+) -> Result<T, ParseErr<'a>> {
+    value.ok_or_else(|| {
+        nom::Err::Error(VerboseError {
+            errors: vec![(tags.as_str(), VerboseErrorKind::Context(key))],
+        })
     })
 }
 
-fn user_type(input: &str) -> Res<&str, &str> {
-    sep_pair(tags::USER_TYPE, alphanumeric0).parse(input)
-}
-
-fn alphanumerichyphencolon1<T>(i: T) -> Res<T, T>
+fn alphanumerichyphen1<T>(i: T) -> Res<T, T>
 where
     T: InputTakeAtPosition,
     <T as InputTakeAtPosition>::Item: AsChar,
@@ -342,59 +179,55 @@ where
     i.split_at_position1_complete(
         |item| {
             let char_item = item.as_char();
-            char_item != '-' && char_item != ':' && char_item != '.' && !char_item.is_alphanum()
+            char_item != '-' && char_item != '_' && char_item != '.' && !char_item.is_alphanum()
         },
         ErrorKind::AlphaNumeric,
     )
 }
 
-fn flags(input: &str) -> Res<&str, Option<&str>> {
-    context(
-        tags::FLAGS,
-        preceded(
-            tag(";"),
-            separated_pair(tag(tags::FLAGS), tag("="), opt(alphanumerichyphencolon1)),
-        ),
-    )(input)
-    .map(|(next, (_, value))| (next, value))
-}
-
-fn moderator(input: &str) -> Res<&str, bool> {
-    sep_pair_to_bool(tags::MODERATOR, digit1).parse(input)
-}
-
-fn returning_chatter(input: &str) -> Res<&str, bool> {
-    sep_pair_to_bool(tags::RETURNING_CHATTER, digit1).parse(input)
+pub(super) fn badge_list(input: &str) -> Res<&str, Vec<(Badges, &str)>> {
+    separated_list0(tag(","), badge_format)(input)
 }
 
-fn subscriber(input: &str) -> Res<&str, bool> {
-    sep_pair_to_bool(tags::SUBSCRIBER, digit1).parse(input)
+fn badge_format(input: &str) -> Res<&str, (Badges, &str)> {
+    context(
+        tags::BADGE_FORMAT,
+        separated_pair(alphanumerichyphen1, tag("/"), is_not(",")),
+    )(input)
+    .map(|(next, (badge, version))| (next, (badge.into(), version)))
 }
 
-fn turbo(input: &str) -> Res<&str, bool> {
-    sep_pair_to_bool(tags::TURBO, digit1).parse(input)
+/// `#RRGGBB`, the digits in either case.
+pub(super) fn hex_to_rgb(input: &str) -> Res<&str, (u8, u8, u8)> {
+    context(
+        "parse hex to rgb",
+        preceded(tag("#"), tuple((hex_byte, hex_byte, hex_byte))),
+    )(input)
 }
 
-fn first_msg(input: &str) -> Res<&str, bool> {
-    sep_pair_to_bool(tags::FIRST_MSG, digit1).parse(input)
+fn hex_byte(input: &str) -> Res<&str, u8> {
+    map_res(
+        take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()),
+        |hex| u8::from_str_radix(hex, 16),
+    )(input)
 }
 
-fn emotes(input: &str) -> Res<&str, Vec<Emote>> {
+/// Twitch sends milliseconds, only the seconds are kept.
+fn tmi_sent_ts(input: &str) -> Res<&str, DateTime<Utc>> {
     context(
-        tags::EMOTES,
-        preceded(
-            tag(";"),
-            separated_pair(
-                tag(tags::EMOTES),
-                tag("="),
-                separated_list0(tag("/"), single_emote),
-            ),
-        ),
+        tags::TMI_SENT_TS,
+        map_opt(digit1, |digits: &str| {
+            let seconds = digits.get(0..10)?.parse::<i64>().ok()?;
+            Utc.timestamp_opt(seconds, 0).single()
+        }),
     )(input)
-    .map(|(next, (_, result))| (next, result))
 }
 
-fn single_emote(input: &str) -> Res<&str, Emote> {
+fn emote_list(input: &str) -> Res<&str, Vec<Emote<'_>>> {
+    context(tags::EMOTES, separated_list0(tag("/"), single_emote))(input)
+}
+
+fn single_emote(input: &str) -> Res<&str, Emote<'_>> {
     context(
         tags::EMOTE,
         separated_pair(alphanumerichyphen1, tag(":"), emote_indexes),
@@ -403,62 +236,17 @@ fn single_emote(input: &str) -> Res<&str, Emote> {
 }
 
 fn emote_indexes(input: &str) -> Res<&str, Vec<(u32, u32)>> {
+    let index = || map_res(digit1, str::parse::<u32>);
     context(
         tags::EMOTE_INDEXES,
-        separated_list0(tag(","), separated_pair(digit1, tag("-"), digit1)),
+        separated_list0(tag(","), separated_pair(index(), tag("-"), index())),
     )(input)
-    .map(|(next, result)| {
-        let result = result
-            .iter()
-            .map(|(v1, v2)| (v1.parse::<u32>().unwrap(), v2.parse::<u32>().unwrap()))
-            .collect::<Vec<(u32, u32)>>();
-        (next, result)
-    })
-}
-
-fn id(input: &str) -> Res<&str, &str> {
-    sep_pair(tags::ID, alphanumerichyphen1).parse(input)
-}
-
-fn parse_msg_body<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
-    take_till(|c| c == ';')(i)
-}
-
-fn sep_pair<'a, T>(
-    param_string: &'static str,
-    p: impl Parser<&'a str, T, VerboseError<&'a str>> + Copy,
-) -> impl Parser<&'a str, T, VerboseError<&'a str>> {
-    move |input| {
-        context(
-            param_string,
-            preceded(tag(";"), separated_pair(tag(param_string), tag("="), p)),
-        )(input)
-        .map(|(next, (_, result))| (next, result))
-    }
-}
-
-fn sep_pair_to_bool<'a>(
-    param_string: &'static str,
-    p: impl Parser<&'a str, &'a str, VerboseError<&'a str>> + Copy,
-) -> impl Parser<&'a str, bool, VerboseError<&'a str>> {
-    move |input| {
-        context(
-            param_string,
-            preceded(tag(";"), separated_pair(tag(param_string), tag("="), p)),
-        )(input)
-        .map(|(next, (_, value))| {
-            let value = value.parse::<u32>().unwrap();
-            let value = value != 0;
-            (next, value)
-        })
-    }
 }
 
 #[cfg(test)]
 mod test {
 
     use super::*;
-    use nom::error::VerboseErrorKind;
     use nom::Err as NomErr;
 
     // @badge-info=;
@@ -471,7 +259,7 @@ mod test {
     // flags=;
     // id=d238119b-ebe3-41a7-b177-55d145402d0b;
     // mod=0;
-    // returning_chatter=0;
+    // returning-chatter=0;
     // reply-parent-display-name=Toerktumlare;
     // reply-parent-msg-body=hello\schat!;
     // reply-parent-msg-id=9bf7210b-e249-4d32-a240-fc0a6bb762a8;
@@ -513,7 +301,7 @@ mod test {
 
     #[test]
     fn parse_reply_message_meta_data() {
-        let meta_data_string = "@badge-info=;badges=;client-nonce=abc123;color=#FFFFFF;display-name=kirglow;emotes=;first-msg=0;flags=;id=2f-7e;mod=0;returning_chatter=0;reply-parent-display-name=Toerktumlare;reply-parent-msg-body=take\\s2;reply-parent-msg-id=87-f3;reply-parent-user-id=4749;reply-parent-user-login=toerktumlare;room-id=4749;subscriber=0;tmi-sent-ts=1500000000;turbo=0;user-id=60;user-type=";
+        let meta_data_string = "@badge-info=;badges=;client-nonce=abc123;color=#FFFFFF;display-name=kirglow;emotes=;first-msg=0;flags=;id=2f-7e;mod=0;returning-chatter=0;reply-parent-display-name=Toerktumlare;reply-parent-msg-body=take\\s2;reply-parent-msg-id=87-f3;reply-parent-user-id=4749;reply-parent-user-login=toerktumlare;room-id=4749;subscriber=0;tmi-sent-ts=1500000000;turbo=0;user-id=60;user-type=";
         let meta_data = MetaData {
             badge_info: vec![],
             client_nonce: Some("abc123"),
@@ -526,7 +314,7 @@ mod test {
             returning_chatter: false,
             reply: Reply {
                 display_name: Some("Toerktumlare"),
-                msg_body: Some("take\\s2"),
+                msg_body: Some(Cow::Borrowed("take 2")),
                 msg_id: Some("87-f3"),
                 user_id: Some(4749),
                 user_login: Some("toerktumlare"),
@@ -543,67 +331,76 @@ mod test {
                 badges: vec![],
                 turbo: false,
             },
+            tags: tags(meta_data_string).unwrap().1,
         };
 
         assert_eq!(MetaData::new(meta_data_string), Ok(("", meta_data)));
     }
 
     #[test]
-    fn test_reply_parent_display_name() {
-        assert_eq!(
-            sep_pair(tags::REPLY_PARENT_DISPLAY_NAME, username)
-                .parse(";reply-parent-display-name=Toerktumlare"),
-            Ok(("", "Toerktumlare"))
-        );
-    }
-
-    #[test]
-    fn test_reply_parent_msg_body() {
-        assert_eq!(
-            sep_pair(tags::REPLY_PARENT_MSG_BODY, parse_msg_body)
-                .parse(";reply-parent-msg-body=hello\\schat!"),
-            Ok(("", "hello\\schat!"))
-        );
+    fn should_split_tags_into_map() {
+        let (next, tags) =
+            tags("@badge-info=;display-name=kirglow;vip;msg-param-color=PRIMARY :tmi.twitch.tv")
+                .unwrap();
+        assert_eq!(next, " :tmi.twitch.tv");
+        assert_eq!(tags.iter().count(), 4);
+        assert_eq!(tags.raw(tags::BADGE_INFO), Some(""));
+        assert_eq!(tags.raw("vip"), Some(""));
+        assert_eq!(tags.value(tags::DISPLAY_NAME), Some("kirglow"));
+        assert_eq!(tags.value("msg-param-color"), Some("PRIMARY"));
     }
 
     #[test]
-    fn test_reply_parent_msg_id() {
+    fn should_parse_tags_in_any_order_and_keep_unknown_tags() {
+        let (_, meta_data) = MetaData::new("@user-id=60;vip=1;tmi-sent-ts=1500000000;id=2f-7e;room-id=4749;display-name=kirglow;pinned-chat-paid-amount=500;badges=vip/1,subscriber/12").unwrap();
+        assert_eq!(meta_data.id, "2f-7e");
+        assert_eq!(meta_data.room_id, 4749);
+        assert_eq!(meta_data.user_id, 60);
+        assert_eq!(meta_data.user_info.display_name, Some("kirglow"));
+        assert_eq!(meta_data.user_info.color, None);
+        assert_eq!(meta_data.flags, None);
         assert_eq!(
-            sep_pair(tags::REPLY_PARENT_MSG_ID, alphanumerichyphen1)
-                .parse(";reply-parent-msg-id=9bf7210b-e249-4d32-a240-fc0a6bb762a8h"),
-            Ok(("", "9bf7210b-e249-4d32-a240-fc0a6bb762a8h"))
+            meta_data.user_info.badges,
+            vec![(Badges::Vip, "1"), (Badges::Subscriber, "12")]
         );
+        assert_eq!(meta_data.tags.value("pinned-chat-paid-amount"), Some("500"));
+        assert_eq!(meta_data.tags.value("vip"), Some("1"));
     }
 
     #[test]
-    fn test_reply_parent_user_id() {
+    fn should_fail_when_required_tag_is_missing() {
         assert_eq!(
-            sep_pair(tags::REPLY_PARENT_USER_ID, digit1).parse(";reply-parent-user-id=47496925"),
-            Ok(("", "47496925"))
+            MetaData::new("@room-id=4749;tmi-sent-ts=1500000000;user-id=60"),
+            Err(NomErr::Error(VerboseError {
+                errors: vec![(
+                    "room-id=4749;tmi-sent-ts=1500000000;user-id=60",
+                    VerboseErrorKind::Context("id")
+                )]
+            }))
         );
     }
 
     #[test]
-    fn test_reply_parent_user_login() {
+    fn should_fail_on_malformed_known_tag() {
         assert_eq!(
-            sep_pair(tags::REPLY_PARENT_USER_LOGIN, username)
-                .parse(";reply-parent-user-login=toerktumlare"),
-            Ok(("", "toerktumlare"))
+            MetaData::new("@id=1;room-id=abc;tmi-sent-ts=1500000000;user-id=60"),
+            Err(NomErr::Error(VerboseError {
+                errors: vec![
+                    ("abc", VerboseErrorKind::Nom(ErrorKind::Digit)),
+                    ("abc", VerboseErrorKind::Context("room-id"))
+                ]
+            }))
         );
     }
 
     #[test]
-    fn test_badge_info() {
-        assert_eq!(badge_info("badge-info="), Ok(("", vec![])));
-        assert_eq!(
-            badge_info("@badge-info=global_mod/1"),
-            Ok(("", vec![(Badges::GlobalMod, "1")]))
-        );
-        assert_eq!(
-            badge_info(";badge-info=admin/1,staff/1"),
-            Ok(("", vec![(Badges::Admin, "1"), (Badges::Staff, "1")]))
-        );
-        assert_eq!(badge_info(";badge-info="), Ok(("", vec![])));
+    fn should_allow_backslash_in_id_and_comma_in_flags() {
+        let (_, meta_data) = MetaData::new(
+            "@id=ab\\cd;flags=0-5:P.5,7-12:A.3/P.6;room-id=1;tmi-sent-ts=1500000000;user-id=60",
+        )
+        .unwrap();
+        assert_eq!(meta_data.id, "ab\\cd");
+        assert_eq!(meta_data.flags, Some(Some("0-5:P.5,7-12:A.3/P.6")));
     }
 
     #[test]
@@ -623,23 +420,32 @@ mod test {
             badge_format("foobar/1"),
             Ok(("", (Badges::Unimplemented, "1")))
         );
+        assert_eq!(
+            badge_list("vip/1,founder/0,sub-gift-leader/2,no_audio/1"),
+            Ok((
+                "",
+                vec![
+                    (Badges::Vip, "1"),
+                    (Badges::Founder, "0"),
+                    (Badges::SubGiftLeader, "2"),
+                    (Badges::NoAudio, "1")
+                ]
+            ))
+        );
     }
 
     #[test]
     fn test_badges() {
+        assert_eq!(badge_list("admin/1"), Ok(("", vec![(Badges::Admin, "1")])));
         assert_eq!(
-            badges("badges=admin/1"),
-            Ok(("", vec![(Badges::Admin, "1")]))
-        );
-        assert_eq!(
-            badges(";badges=admin/1,subscriber/2"),
+            badge_list("admin/1,subscriber/2"),
             Ok(("", vec![(Badges::Admin, "1"), (Badges::Subscriber, "2")]))
         );
         assert_eq!(
-            badges("badges=admin/1"),
-            Ok(("", vec![(Badges::Admin, "1")]))
+            badge_list("predictions/blue\\s1"),
+            Ok(("", vec![(Badges::Predictions, "blue\\s1")]))
         );
-        assert_eq!(badges("badges="), Ok(("", vec![])));
+        assert_eq!(badge_list(""), Ok(("", vec![])));
     }
 
     #[test]
@@ -658,47 +464,26 @@ mod test {
             hex_to_rgb("#HFFFFF"),
             Err(NomErr::Error(VerboseError {
                 errors: vec![
-                    ("HFFFFF", VerboseErrorKind::Nom(ErrorKind::OneOf)),
-                    ("HFFFFF", VerboseErrorKind::Nom(ErrorKind::Many1)),
+                    ("HFFFFF", VerboseErrorKind::Nom(ErrorKind::TakeWhileMN)),
                     ("#HFFFFF", VerboseErrorKind::Context("parse hex to rgb"))
                 ]
             }))
         );
+        assert_eq!(hex_to_rgb("#ff00aa"), Ok(("", (255, 0, 170))));
+        assert!(hex_to_rgb("#FFF").is_err());
     }
 
     #[test]
     fn test_color() {
-        assert_eq!(color(";color=#FFFFFF"), Ok(("", Some((255, 255, 255)))));
-        assert_eq!(color(";color="), Ok(("", None)));
+        let (_, tags) = tags("@color=#FFFFFF;empty=;broken=#;short=#FFF;long=#FFFFFFF").unwrap();
         assert_eq!(
-            color(";color#"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    ("#", VerboseErrorKind::Nom(ErrorKind::Tag)),
-                    (";color#", VerboseErrorKind::Context("color"))
-                ]
-            }))
-        );
-    }
-
-    #[test]
-    fn test_display_name() {
-        assert_eq!(
-            display_name(";display-name=toerktumlare"),
-            Ok(("", Some("toerktumlare")))
-        );
-        assert_eq!(
-            display_name(";display-name#toerktumlare"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    ("#toerktumlare", VerboseErrorKind::Nom(ErrorKind::Tag)),
-                    (
-                        ";display-name#toerktumlare",
-                        VerboseErrorKind::Context("display-name")
-                    ),
-                ]
-            }))
+            field(&tags, tags::COLOR, hex_to_rgb),
+            Ok(Some((255, 255, 255)))
         );
+        assert_eq!(field(&tags, "empty", hex_to_rgb), Ok(None));
+        assert!(field(&tags, "broken", hex_to_rgb).is_err());
+        assert!(field(&tags, "short", hex_to_rgb).is_err());
+        assert!(field(&tags, "long", hex_to_rgb).is_err());
     }
 
     #[test]
@@ -744,7 +529,7 @@ mod test {
     #[test]
     fn test_emotes() {
         assert_eq!(
-            emotes(";emotes=25:0-4,12-16/1902:6-10"),
+            emote_list("25:0-4,12-16/1902:6-10"),
             Ok((
                 "",
                 vec![
@@ -759,114 +544,19 @@ mod test {
                 ]
             ))
         );
-        assert_eq!(
-            emotes("=25:0-4,12-16/1902:6-10"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    (
-                        "=25:0-4,12-16/1902:6-10",
-                        VerboseErrorKind::Nom(ErrorKind::Tag)
-                    ),
-                    (
-                        "=25:0-4,12-16/1902:6-10",
-                        VerboseErrorKind::Context("emotes")
-                    ),
-                ]
-            }))
-        )
-    }
-
-    #[test]
-    fn test_id() {
-        assert_eq!(
-            id(";id=b34ccfc7-4977-403a-8a94-33c6bac34fb8"),
-            Ok(("", "b34ccfc7-4977-403a-8a94-33c6bac34fb8"))
-        );
-        assert_eq!(
-            id("=b34ccfc7-4977-403a-8a94-33c6bac34fb8"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    (
-                        "=b34ccfc7-4977-403a-8a94-33c6bac34fb8",
-                        VerboseErrorKind::Nom(ErrorKind::Tag)
-                    ),
-                    (
-                        "=b34ccfc7-4977-403a-8a94-33c6bac34fb8",
-                        VerboseErrorKind::Context("id")
-                    ),
-                ]
-            }))
-        )
-    }
-
-    #[test]
-    fn test_mod() {
-        assert_eq!(moderator(";mod=0"), Ok(("", false)));
-        assert_eq!(moderator(";mod=1"), Ok(("", true)));
-        assert_eq!(
-            moderator("=0"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    ("=0", VerboseErrorKind::Nom(ErrorKind::Tag)),
-                    ("=0", VerboseErrorKind::Context("mod")),
-                ]
-            }))
-        )
-    }
-
-    #[test]
-    fn test_subscriber() {
-        assert_eq!(subscriber(";subscriber=0"), Ok(("", false)));
-        assert_eq!(subscriber(";subscriber=1"), Ok(("", true)));
-        assert_eq!(
-            subscriber("=0"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    ("=0", VerboseErrorKind::Nom(ErrorKind::Tag)),
-                    ("=0", VerboseErrorKind::Context("subscriber")),
-                ]
-            }))
-        )
-    }
-
-    #[test]
-    fn test_turbo() {
-        assert_eq!(turbo(";turbo=0"), Ok(("", false)));
-        assert_eq!(turbo(";turbo=1"), Ok(("", true)));
-        assert_eq!(
-            turbo("=0"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    ("=0", VerboseErrorKind::Nom(ErrorKind::Tag)),
-                    ("=0", VerboseErrorKind::Context("turbo")),
-                ]
-            }))
-        )
-    }
-
-    #[test]
-    fn test_client_nonce() {
-        assert_eq!(
-            client_nonce(";client-nonce=076d1c4a051be09a506e0ad9c26e6ea6"),
-            Ok(("", "076d1c4a051be09a506e0ad9c26e6ea6"))
-        );
-        assert_eq!(
-            client_nonce("=076"),
-            Err(NomErr::Error(VerboseError {
-                errors: vec![
-                    ("=076", VerboseErrorKind::Nom(ErrorKind::Tag)),
-                    ("=076", VerboseErrorKind::Context("client-nonce")),
-                ]
-            }))
-        );
+        let (_, overflowing) = tags("@emotes=25:0-99999999999").unwrap();
+        assert!(field(&overflowing, tags::EMOTES, emote_list).is_err());
+        let (_, tags) = tags("@emotes=25:0-4,=12").unwrap();
+        assert!(field(&tags, tags::EMOTES, emote_list).is_err());
     }
 
     #[test]
     fn test_tmi_sent_ts() {
         assert_eq!(
-            tmi_sent_ts(";tmi-sent-ts=1500000000"),
+            tmi_sent_ts("1500000000"),
             Ok(("", Utc.timestamp(1500000000, 0)))
-        )
+        );
+        assert!(tmi_sent_ts("150000").is_err());
     }
 
     #[test]
@@ -1,26 +1,43 @@
 pub mod chat_message;
+pub mod command;
 pub mod meta_data;
 pub mod tags;
+pub mod user_notice;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Emote<'a> {
-    id: &'a str,
-    indexes: Vec<(u32, u32)>,
+    pub id: &'a str,
+    /// First and last position of every use of the emote, counted in
+    /// unicode code points.
+    pub indexes: Vec<(u32, u32)>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Badges {
     Admin,
+    Ambassador,
+    Artist,
     Bits,
+    BitsCharity,
+    BitsLeader,
     Broadcaster,
+    Founder,
     GlobalMod,
+    HypeTrain,
     Moderator,
+    Moments,
+    NoAudio,
+    NoVideo,
+    Partner,
+    Predictions,
     Subscriber,
     Staff,
     Turbo,
     Premium,
     GlitchCon2020,
     SubGifter,
+    SubGiftLeader,
+    Vip,
     Unimplemented,
 }
 
@@ -28,32 +45,82 @@ impl From<&str> for Badges {
     fn from(i: &str) -> Self {
         match i.to_lowercase().as_str() {
             "admin" => Badges::Admin,
+            "ambassador" => Badges::Ambassador,
+            "artist-badge" => Badges::Artist,
             "bits" => Badges::Bits,
+            "bits-charity" => Badges::BitsCharity,
+            "bits-leader" => Badges::BitsLeader,
             "broadcaster" => Badges::Broadcaster,
+            "founder" => Badges::Founder,
             "global_mod" => Badges::GlobalMod,
+            "hype-train" => Badges::HypeTrain,
             "moderator" => Badges::Moderator,
+            "moments" => Badges::Moments,
+            "no_audio" => Badges::NoAudio,
+            "no_video" => Badges::NoVideo,
+            "partner" => Badges::Partner,
+            "predictions" => Badges::Predictions,
             "subscriber" => Badges::Subscriber,
             "staff" => Badges::Staff,
             "turbo" => Badges::Turbo,
             "premium" => Badges::Premium,
             "glitchcon2020" => Badges::GlitchCon2020,
             "sub-gifter" => Badges::SubGifter,
+            "sub-gift-leader" => Badges::SubGiftLeader,
+            "vip" => Badges::Vip,
             _ => Badges::Unimplemented,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum MessageType {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageType {
     PrivMsg,
+    ClearChat,
+    ClearMsg,
+    GlobalUserState,
+    HostTarget,
+    Notice,
+    Reconnect,
+    RoomState,
+    UserNotice,
+    UserState,
+    Whisper,
+    Join,
+    Part,
+    Cap,
+    Ping,
+    Pong,
+    Numeric(u16),
 }
 
-impl From<&str> for MessageType {
-    fn from(i: &str) -> Self {
-        match i.to_lowercase().as_str() {
+impl TryFrom<&str> for MessageType {
+    type Error = String;
+
+    fn try_from(i: &str) -> Result<Self, Self::Error> {
+        let message_type = match i.to_lowercase().as_str() {
             "privmsg" => MessageType::PrivMsg,
-            _ => unimplemented!("wuut?"),
-        }
+            "clearchat" => MessageType::ClearChat,
+            "clearmsg" => MessageType::ClearMsg,
+            "globaluserstate" => MessageType::GlobalUserState,
+            "hosttarget" => MessageType::HostTarget,
+            "notice" => MessageType::Notice,
+            "reconnect" => MessageType::Reconnect,
+            "roomstate" => MessageType::RoomState,
+            "usernotice" => MessageType::UserNotice,
+            "userstate" => MessageType::UserState,
+            "whisper" => MessageType::Whisper,
+            "join" => MessageType::Join,
+            "part" => MessageType::Part,
+            "cap" => MessageType::Cap,
+            "ping" => MessageType::Ping,
+            "pong" => MessageType::Pong,
+            code if code.len() == 3 && code.chars().all(|c| c.is_ascii_digit()) => {
+                MessageType::Numeric(code.parse().unwrap())
+            }
+            _ => return Err(format!("unknown command: {}", i)),
+        };
+        Ok(message_type)
     }
 }
 
@@ -64,6 +131,20 @@ mod test {
 
     #[test]
     fn should_give_correct_enum() {
-        assert_eq!(MessageType::from("PRIVMSG"), MessageType::PrivMsg);
+        assert_eq!(MessageType::try_from("PRIVMSG"), Ok(MessageType::PrivMsg));
+        assert_eq!(
+            MessageType::try_from("USERNOTICE"),
+            Ok(MessageType::UserNotice)
+        );
+        assert_eq!(MessageType::try_from("353"), Ok(MessageType::Numeric(353)));
+    }
+
+    #[test]
+    fn should_return_error_for_unknown_command() {
+        assert_eq!(
+            MessageType::try_from("FOOBAR"),
+            Err("unknown command: FOOBAR".to_string())
+        );
+        assert!(MessageType::try_from("1234").is_err());
     }
 }
@@ -1,3 +1,5 @@
+use std::{borrow::Cow, collections::HashMap, str::FromStr};
+
 pub const BADGE_INFO: &str = "badge-info";
 pub const BADGES: &str = "badges";
 pub const BADGE_FORMAT: &str = "badge-format";
@@ -24,3 +26,140 @@ pub const EMOTE_ONLY: &str = "emote-only";
 pub const DISPLAY_NAME: &str = "display-name";
 pub const ROOM_ID: &str = "room-id";
 pub const USER_ID: &str = "user-id";
+pub const BAN_DURATION: &str = "ban-duration";
+pub const TARGET_USER_ID: &str = "target-user-id";
+pub const TARGET_MSG_ID: &str = "target-msg-id";
+pub const LOGIN: &str = "login";
+pub const TMI_SENT_TS: &str = "tmi-sent-ts";
+pub const EMOTE_SETS: &str = "emote-sets";
+pub const MSG_ID: &str = "msg-id";
+pub const SYSTEM_MSG: &str = "system-msg";
+pub const FOLLOWERS_ONLY: &str = "followers-only";
+pub const R9K: &str = "r9k";
+pub const SLOW: &str = "slow";
+pub const SUBS_ONLY: &str = "subs-only";
+pub const MESSAGE_ID: &str = "message-id";
+pub const THREAD_ID: &str = "thread-id";
+pub const MSG_PARAM_CUMULATIVE_MONTHS: &str = "msg-param-cumulative-months";
+pub const MSG_PARAM_SHOULD_SHARE_STREAK: &str = "msg-param-should-share-streak";
+pub const MSG_PARAM_STREAK_MONTHS: &str = "msg-param-streak-months";
+pub const MSG_PARAM_SUB_PLAN: &str = "msg-param-sub-plan";
+pub const MSG_PARAM_SUB_PLAN_NAME: &str = "msg-param-sub-plan-name";
+pub const MSG_PARAM_MONTHS: &str = "msg-param-months";
+pub const MSG_PARAM_GIFT_MONTHS: &str = "msg-param-gift-months";
+pub const MSG_PARAM_RECIPIENT_DISPLAY_NAME: &str = "msg-param-recipient-display-name";
+pub const MSG_PARAM_RECIPIENT_ID: &str = "msg-param-recipient-id";
+pub const MSG_PARAM_RECIPIENT_USER_NAME: &str = "msg-param-recipient-user-name";
+pub const MSG_PARAM_MASS_GIFT_COUNT: &str = "msg-param-mass-gift-count";
+pub const MSG_PARAM_SENDER_COUNT: &str = "msg-param-sender-count";
+pub const MSG_PARAM_DISPLAY_NAME: &str = "msg-param-displayName";
+pub const MSG_PARAM_LOGIN: &str = "msg-param-login";
+pub const MSG_PARAM_VIEWER_COUNT: &str = "msg-param-viewerCount";
+pub const MSG_PARAM_RITUAL_NAME: &str = "msg-param-ritual-name";
+pub const MSG_PARAM_THRESHOLD: &str = "msg-param-threshold";
+pub const MSG_PARAM_COLOR: &str = "msg-param-color";
+
+/// The `@key=value;key=value` tag section of an IRC message split into a map.
+/// Values are kept as sent and unescaped on request, so identifiers can be
+/// borrowed straight from the input while free text gets proper IRCv3 escaping.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Tags<'a> {
+    raw: &'a str,
+    inner: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Tags<'a> {
+    pub fn new(raw: &'a str, pairs: Vec<(&'a str, &'a str)>) -> Self {
+        Self {
+            raw,
+            inner: pairs.into_iter().collect(),
+        }
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.raw
+    }
+
+    /// The escaped value exactly as it was sent, possibly empty.
+    pub fn raw(&self, key: &str) -> Option<&'a str> {
+        self.inner.get(key).copied()
+    }
+
+    /// Same as `raw` but treats an empty value as missing.
+    pub fn value(&self, key: &str) -> Option<&'a str> {
+        self.raw(key).filter(|v| !v.is_empty())
+    }
+
+    pub fn unescaped(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.value(key).map(unescape)
+    }
+
+    pub fn parse<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.value(key).and_then(|v| v.parse().ok())
+    }
+
+    pub fn flag(&self, key: &str) -> Option<bool> {
+        self.parse::<u32>(key).map(|v| v != 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, Cow<'a, str>)> + '_ {
+        self.inner.iter().map(|(k, v)| (*k, unescape(v)))
+    }
+}
+
+pub fn unescape(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => (),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::meta_data::tags;
+
+    #[test]
+    fn should_look_up_tag_values() {
+        let (_, tags) = tags("@ban-duration=;room-id=12345;target-user-id=87654321").unwrap();
+        assert_eq!(tags.raw(BAN_DURATION), Some(""));
+        assert_eq!(tags.value(BAN_DURATION), None);
+        assert_eq!(tags.parse::<u32>(ROOM_ID), Some(12345));
+        assert_eq!(tags.raw("missing"), None);
+    }
+
+    #[test]
+    fn should_parse_flags() {
+        let (_, tags) = tags("@emote-only=0;r9k=1").unwrap();
+        assert_eq!(tags.flag(EMOTE_ONLY), Some(false));
+        assert_eq!(tags.flag(R9K), Some(true));
+        assert_eq!(tags.flag(SUBS_ONLY), None);
+    }
+
+    #[test]
+    fn should_unescape_values() {
+        assert_eq!(unescape("hello\\schat!"), "hello chat!");
+        assert_eq!(unescape("a\\:b\\\\c"), "a;b\\c");
+        assert_eq!(unescape("line\\r\\n"), "line\r\n");
+        assert_eq!(unescape("drop\\x"), "dropx");
+        assert_eq!(unescape("trailing\\"), "trailing");
+        assert!(matches!(unescape("plain"), Cow::Borrowed("plain")));
+    }
+}
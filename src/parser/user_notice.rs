@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+
+use super::tags::{self, Tags};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SubPlan {
+    Prime,
+    Tier1,
+    Tier2,
+    Tier3,
+    Unknown,
+}
+
+impl From<&str> for SubPlan {
+    fn from(i: &str) -> Self {
+        match i.to_lowercase().as_str() {
+            "prime" => SubPlan::Prime,
+            "1000" => SubPlan::Tier1,
+            "2000" => SubPlan::Tier2,
+            "3000" => SubPlan::Tier3,
+            _ => SubPlan::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Subscription<'a> {
+    pub cumulative_months: Option<u32>,
+    pub streak_months: Option<u32>,
+    pub should_share_streak: bool,
+    pub sub_plan: SubPlan,
+    pub sub_plan_name: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SubGift<'a> {
+    pub months: Option<u32>,
+    pub gift_months: Option<u32>,
+    pub recipient_display_name: Option<&'a str>,
+    pub recipient_id: Option<u32>,
+    pub recipient_user_name: Option<&'a str>,
+    pub sub_plan: SubPlan,
+    pub sub_plan_name: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SubMysteryGift {
+    pub mass_gift_count: Option<u32>,
+    pub sender_count: Option<u32>,
+    pub sub_plan: SubPlan,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Raid<'a> {
+    pub display_name: Option<&'a str>,
+    pub login: Option<&'a str>,
+    pub viewer_count: Option<u32>,
+}
+
+/// The typed version of the `msg-id` tag of a USERNOTICE together with the
+/// `msg-param-*` tags that belong to it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum UserNoticeEvent<'a> {
+    Sub(Subscription<'a>),
+    Resub(Subscription<'a>),
+    SubGift(SubGift<'a>),
+    SubMysteryGift(SubMysteryGift),
+    Raid(Raid<'a>),
+    Unraid,
+    Ritual { name: Option<&'a str> },
+    BitsBadgeTier { threshold: Option<u32> },
+    Announcement { color: Option<&'a str> },
+    Other(&'a str),
+}
+
+impl<'a> UserNoticeEvent<'a> {
+    pub fn new(msg_id: &'a str, tags: &Tags<'a>) -> Self {
+        match msg_id {
+            "sub" => UserNoticeEvent::Sub(subscription(tags)),
+            "resub" => UserNoticeEvent::Resub(subscription(tags)),
+            "subgift" => UserNoticeEvent::SubGift(SubGift {
+                months: tags.parse(tags::MSG_PARAM_MONTHS),
+                gift_months: tags.parse(tags::MSG_PARAM_GIFT_MONTHS),
+                recipient_display_name: tags.value(tags::MSG_PARAM_RECIPIENT_DISPLAY_NAME),
+                recipient_id: tags.parse(tags::MSG_PARAM_RECIPIENT_ID),
+                recipient_user_name: tags.value(tags::MSG_PARAM_RECIPIENT_USER_NAME),
+                sub_plan: sub_plan(tags),
+                sub_plan_name: tags.unescaped(tags::MSG_PARAM_SUB_PLAN_NAME),
+            }),
+            "submysterygift" => UserNoticeEvent::SubMysteryGift(SubMysteryGift {
+                mass_gift_count: tags.parse(tags::MSG_PARAM_MASS_GIFT_COUNT),
+                sender_count: tags.parse(tags::MSG_PARAM_SENDER_COUNT),
+                sub_plan: sub_plan(tags),
+            }),
+            "raid" => UserNoticeEvent::Raid(Raid {
+                display_name: tags.value(tags::MSG_PARAM_DISPLAY_NAME),
+                login: tags.value(tags::MSG_PARAM_LOGIN),
+                viewer_count: tags.parse(tags::MSG_PARAM_VIEWER_COUNT),
+            }),
+            "unraid" => UserNoticeEvent::Unraid,
+            "ritual" => UserNoticeEvent::Ritual {
+                name: tags.value(tags::MSG_PARAM_RITUAL_NAME),
+            },
+            "bitsbadgetier" => UserNoticeEvent::BitsBadgeTier {
+                threshold: tags.parse(tags::MSG_PARAM_THRESHOLD),
+            },
+            "announcement" => UserNoticeEvent::Announcement {
+                color: tags.value(tags::MSG_PARAM_COLOR),
+            },
+            other => UserNoticeEvent::Other(other),
+        }
+    }
+}
+
+fn subscription<'a>(tags: &Tags<'a>) -> Subscription<'a> {
+    Subscription {
+        cumulative_months: tags.parse(tags::MSG_PARAM_CUMULATIVE_MONTHS),
+        streak_months: tags.parse(tags::MSG_PARAM_STREAK_MONTHS),
+        should_share_streak: tags
+            .flag(tags::MSG_PARAM_SHOULD_SHARE_STREAK)
+            .unwrap_or(false),
+        sub_plan: sub_plan(tags),
+        sub_plan_name: tags.unescaped(tags::MSG_PARAM_SUB_PLAN_NAME),
+    }
+}
+
+fn sub_plan(tags: &Tags) -> SubPlan {
+    tags.value(tags::MSG_PARAM_SUB_PLAN)
+        .map(SubPlan::from)
+        .unwrap_or(SubPlan::Unknown)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::meta_data::tags;
+
+    #[test]
+    fn should_parse_resub() {
+        let (_, tags) = tags("@msg-id=resub;msg-param-cumulative-months=6;msg-param-streak-months=2;msg-param-should-share-streak=1;msg-param-sub-plan=Prime;msg-param-sub-plan-name=Prime").unwrap();
+        assert_eq!(
+            UserNoticeEvent::new("resub", &tags),
+            UserNoticeEvent::Resub(Subscription {
+                cumulative_months: Some(6),
+                streak_months: Some(2),
+                should_share_streak: true,
+                sub_plan: SubPlan::Prime,
+                sub_plan_name: Some(Cow::Borrowed("Prime")),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_subgift() {
+        let (_, tags) = tags("@msg-id=subgift;msg-param-months=1;msg-param-recipient-display-name=Mr_Woodchuck;msg-param-recipient-id=55554444;msg-param-recipient-user-name=mr_woodchuck;msg-param-sub-plan-name=House\\sof\\sNyoro~n;msg-param-sub-plan=1000").unwrap();
+        assert_eq!(
+            UserNoticeEvent::new("subgift", &tags),
+            UserNoticeEvent::SubGift(SubGift {
+                months: Some(1),
+                gift_months: None,
+                recipient_display_name: Some("Mr_Woodchuck"),
+                recipient_id: Some(55554444),
+                recipient_user_name: Some("mr_woodchuck"),
+                sub_plan: SubPlan::Tier1,
+                sub_plan_name: Some(Cow::Owned("House of Nyoro~n".to_string())),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_raid() {
+        let (_, tags) = tags("@msg-id=raid;msg-param-displayName=TestChannel;msg-param-login=testchannel;msg-param-viewerCount=15").unwrap();
+        assert_eq!(
+            UserNoticeEvent::new("raid", &tags),
+            UserNoticeEvent::Raid(Raid {
+                display_name: Some("TestChannel"),
+                login: Some("testchannel"),
+                viewer_count: Some(15),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_remaining_events() {
+        let (_, tags) = tags("@msg-param-mass-gift-count=5;msg-param-sub-plan=2000;msg-param-ritual-name=new_chatter;msg-param-threshold=1000;msg-param-color=PRIMARY").unwrap();
+        assert_eq!(
+            UserNoticeEvent::new("submysterygift", &tags),
+            UserNoticeEvent::SubMysteryGift(SubMysteryGift {
+                mass_gift_count: Some(5),
+                sender_count: None,
+                sub_plan: SubPlan::Tier2,
+            })
+        );
+        assert_eq!(
+            UserNoticeEvent::new("ritual", &tags),
+            UserNoticeEvent::Ritual {
+                name: Some("new_chatter")
+            }
+        );
+        assert_eq!(
+            UserNoticeEvent::new("bitsbadgetier", &tags),
+            UserNoticeEvent::BitsBadgeTier {
+                threshold: Some(1000)
+            }
+        );
+        assert_eq!(
+            UserNoticeEvent::new("announcement", &tags),
+            UserNoticeEvent::Announcement {
+                color: Some("PRIMARY")
+            }
+        );
+        assert_eq!(
+            UserNoticeEvent::new("unraid", &tags),
+            UserNoticeEvent::Unraid
+        );
+        assert_eq!(
+            UserNoticeEvent::new("primepaidupgrade", &tags),
+            UserNoticeEvent::Other("primepaidupgrade")
+        );
+    }
+}
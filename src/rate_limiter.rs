@@ -0,0 +1,67 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Keeps track of when things were sent and allows at most `limit` of them
+/// within any window of `period`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: usize,
+    period: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: usize, period: Duration) -> Self {
+        Self {
+            limit,
+            period,
+            sent: VecDeque::with_capacity(limit),
+        }
+    }
+
+    /// Returns true and counts the send if the limit allows it right now.
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        self.expire(now);
+        if self.sent.len() < self.limit {
+            self.sent.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&first) = self.sent.front() {
+            if first + self.period <= now {
+                self.sent.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_allow_up_to_limit_within_period() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(30));
+        assert!(limiter.try_acquire(now));
+        assert!(limiter.try_acquire(now + Duration::from_secs(1)));
+        assert!(!limiter.try_acquire(now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn should_allow_again_once_period_has_passed() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(1, Duration::from_secs(30));
+        assert!(limiter.try_acquire(now));
+        assert!(!limiter.try_acquire(now + Duration::from_secs(29)));
+        assert!(limiter.try_acquire(now + Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    any::type_name,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use crossbeam::channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
+
+use crate::{
+    log::get_logger,
+    parser::chat_message::ChatMessage,
+    twitch_client::{ChatSource, Message},
+};
+
+/// Writes every line the server sends to a file, one per line behind the
+/// time it was received and a tab:
+///
+/// `2022-01-20T17:33:20.123Z\t@badge-info=;badges=... PRIVMSG #dallas :hi`
+pub struct Recorder {
+    output: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|err| format!("could not create {}: {}", path.display(), err))?;
+        Ok(Self {
+            output: BufWriter::new(file),
+        })
+    }
+
+    /// Records every line of what was received at once, the file is flushed
+    /// right away so that nothing is lost when the application dies.
+    pub fn record(&mut self, received_at: DateTime<Utc>, msg: &str) -> std::io::Result<()> {
+        let received_at = received_at.to_rfc3339_opts(SecondsFormat::Millis, true);
+        for line in msg.lines().filter(|line| !line.is_empty()) {
+            writeln!(self.output, "{}\t{}", received_at, line)?;
+        }
+        self.output.flush()
+    }
+}
+
+/// A line of a recording, the time it was received and the line itself.
+pub fn parse_entry(entry: &str) -> Result<(DateTime<Utc>, &str), String> {
+    let (received_at, line) = entry
+        .split_once('\t')
+        .ok_or_else(|| format!("expected `<time>\\t<line>`, got `{}`", entry))?;
+    let received_at = DateTime::parse_from_rfc3339(received_at)
+        .map_err(|err| format!("invalid time `{}`: {}", received_at, err))?;
+    Ok((received_at.with_timezone(&Utc), line))
+}
+
+const MIN_FACTOR: f64 = 0.01;
+
+/// How fast a recording is played back.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Speed {
+    /// All lines at once.
+    Instant,
+    /// The time between lines divided by this, `1.0` is as it happened.
+    Factor(f64),
+}
+
+impl Speed {
+    /// `instant`, or how many times faster than real time, like `1` or `2.5`.
+    /// Anything slower than a hundredth of real time is refused.
+    pub fn parse(speed: &str) -> Result<Self, String> {
+        if speed == "instant" {
+            return Ok(Speed::Instant);
+        }
+        match speed.parse::<f64>() {
+            Ok(factor) if factor.is_finite() && factor >= MIN_FACTOR => Ok(Speed::Factor(factor)),
+            _ => Err(format!(
+                "invalid speed `{}`, expected a number of at least {} or instant",
+                speed, MIN_FACTOR
+            )),
+        }
+    }
+
+    /// Saturates instead of overflowing, a long gap played slowly just
+    /// waits for a very long time.
+    fn delay(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Duration {
+        match self {
+            Speed::Instant => Duration::ZERO,
+            Speed::Factor(factor) => {
+                let gap = (to - from).to_std().unwrap_or_default();
+                Duration::try_from_secs_f64(gap.as_secs_f64() / factor).unwrap_or(Duration::MAX)
+            }
+        }
+    }
+}
+
+/// Stands in for `TwitchClient`, the lines of a recording come out of the
+/// receiver spaced out like they were received. The receiver is closed once
+/// the recording ends, messages we send go nowhere.
+pub struct Replay {
+    receiver: Receiver<Message>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Replay {
+    pub fn start(path: impl AsRef<Path>, speed: Speed) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        let (tx, receiver) = unbounded();
+        let (stop, stopped) = bounded::<()>(0);
+        let thread = thread::Builder::new()
+            .name("replay".to_string())
+            .spawn(move || play(BufReader::new(file), speed, tx, stopped))
+            .map_err(|err| format!("could not start replay: {}", err))?;
+        Ok(Self {
+            receiver,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+fn play(input: impl BufRead, speed: Speed, tx: Sender<Message>, stopped: Receiver<()>) {
+    let log = get_logger();
+    let mut previous = None;
+    for (n, entry) in input.lines().enumerate() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                log.error(
+                    format!("Could not read recording: {}", err),
+                    type_name::<Replay>(),
+                );
+                break;
+            }
+        };
+        if entry.is_empty() {
+            continue;
+        }
+        let (received_at, line) = match parse_entry(&entry) {
+            Ok(entry) => entry,
+            Err(err) => {
+                log.warn(format!("line {}: {}", n + 1, err), type_name::<Replay>());
+                continue;
+            }
+        };
+        let delay = speed.delay(previous.unwrap_or(received_at), received_at);
+        previous = Some(received_at);
+        if !delay.is_zero() {
+            let waited = match Instant::now().checked_add(delay) {
+                Some(deadline) => stopped.recv_deadline(deadline),
+                // too far off to ever be reached, only stopping ends the wait
+                None => stopped.recv().map_err(RecvTimeoutError::from),
+            };
+            match waited {
+                Err(RecvTimeoutError::Timeout) => (),
+                _ => return,
+            }
+        }
+        if ChatMessage::parse(line).is_err() {
+            log.warn(
+                format!("line {} does not parse: {}", n + 1, line),
+                type_name::<Replay>(),
+            );
+        }
+        if tx.send(Message::Text(line.to_string())).is_err() {
+            return;
+        }
+    }
+    log.info("End of recording", type_name::<Replay>());
+}
+
+impl ChatSource for Replay {
+    fn receiver(&self) -> &Receiver<Message> {
+        &self.receiver
+    }
+
+    fn send_message(&self, channel: &str, text: &str) {
+        get_logger().info(
+            format!("Not sending to #{} while replaying: {}", channel, text),
+            type_name::<Replay>(),
+        );
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        // wakes the thread up if it is waiting for the next line
+        self.stop.take();
+        self.thread.take().map(JoinHandle::join);
+    }
+}
+
+/// The channels a recording has messages from, in the order they show up.
+pub fn channels(path: impl AsRef<Path>) -> Result<Vec<String>, String> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+    let mut channels: Vec<String> = Vec::new();
+    for entry in BufReader::new(file).lines().map_while(Result::ok) {
+        let channel = parse_entry(&entry)
+            .ok()
+            .and_then(|(_, line)| ChatMessage::parse(line).ok()?.destination());
+        if let Some(channel) = channel {
+            if !channels.iter().any(|known| known == channel) {
+                channels.push(channel.to_string());
+            }
+        }
+    }
+    Ok(channels)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::log;
+    use chrono::TimeZone;
+    use std::{env, fs};
+
+    fn recording(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("twitch-chat-{}-{}.log", name, std::process::id()))
+    }
+
+    fn texts(replay: &Replay) -> Vec<String> {
+        replay
+            .receiver()
+            .iter()
+            .map(|message| match message {
+                Message::Text(text) => text,
+                message => panic!("expected text, got {:?}", message),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_play_back_what_was_recorded() {
+        log::init();
+        let path = recording("roundtrip");
+        let mut recorder = Recorder::create(&path).unwrap();
+        let start = Utc.timestamp(1642700000, 0);
+        recorder
+            .record(
+                start,
+                ":ronni!ronni@ronni.tmi.twitch.tv JOIN #dallas\r\n:ronni!ronni@ronni.tmi.twitch.tv JOIN #forsen\r\n",
+            )
+            .unwrap();
+        recorder
+            .record(
+                start + chrono::Duration::milliseconds(200),
+                "@id=1 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :hi\tthere",
+            )
+            .unwrap();
+        drop(recorder);
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("2022-01-20T17:33:20.000Z\t:ronni!"));
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(channels(&path).unwrap(), vec!["dallas", "forsen"]);
+
+        let begun = Instant::now();
+        let replay = Replay::start(&path, Speed::Factor(2.0)).unwrap();
+        assert_eq!(
+            texts(&replay),
+            vec![
+                ":ronni!ronni@ronni.tmi.twitch.tv JOIN #dallas",
+                ":ronni!ronni@ronni.tmi.twitch.tv JOIN #forsen",
+                "@id=1 :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :hi\tthere",
+            ]
+        );
+        // 200ms twice as fast
+        assert!(begun.elapsed() >= Duration::from_millis(100));
+
+        let replay = Replay::start(&path, Speed::Instant).unwrap();
+        assert_eq!(texts(&replay).len(), 3);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_skip_broken_entries() {
+        log::init();
+        let path = recording("broken");
+        fs::write(
+            &path,
+            "no tab here\nyesterday\tPING :a\n\n2022-01-20T17:33:20Z\tPING :b\n",
+        )
+        .unwrap();
+        let replay = Replay::start(&path, Speed::Instant).unwrap();
+        assert_eq!(texts(&replay), vec!["PING :b"]);
+        fs::remove_file(path).unwrap();
+        assert!(Replay::start("/does/not/exist", Speed::Instant).is_err());
+    }
+
+    #[test]
+    fn should_stop_while_waiting_for_the_next_line() {
+        log::init();
+        let path = recording("waiting");
+        fs::write(
+            &path,
+            "2022-01-20T17:33:20Z\tPING :a\n3022-01-20T17:33:20Z\tPING :b\n",
+        )
+        .unwrap();
+        let replay = Replay::start(&path, Speed::Factor(1e-300)).unwrap();
+        assert_eq!(
+            replay.receiver().recv(),
+            Ok(Message::Text("PING :a".to_string()))
+        );
+        drop(replay);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_parse_speed() {
+        assert_eq!(Speed::parse("instant"), Ok(Speed::Instant));
+        assert_eq!(Speed::parse("2.5"), Ok(Speed::Factor(2.5)));
+        assert!(Speed::parse("0").is_err());
+        assert!(Speed::parse("1e-300").is_err());
+        assert_eq!(Speed::parse("0.01"), Ok(Speed::Factor(0.01)));
+        let start = Utc.timestamp(1642700000, 0);
+        let gap = start + chrono::Duration::days(365 * 1000);
+        assert_eq!(Speed::Factor(1e-300).delay(start, gap), Duration::MAX);
+        assert_eq!(
+            Speed::Factor(2.0).delay(start, start + chrono::Duration::seconds(3)),
+            Duration::from_millis(1500)
+        );
+        assert!(Speed::parse("fast").is_err());
+    }
+}
@@ -1,5 +1,4 @@
 #[allow(dead_code)]
-
 pub struct StringPadder {
     pub current_max: u32,
 }
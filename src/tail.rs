@@ -0,0 +1,273 @@
+use std::{
+    any::type_name,
+    io::{self, Write},
+};
+
+use chrono::Utc;
+use crossterm::style::{Attribute, Color};
+
+use crate::{
+    color_holder::ColorCache,
+    gui::{
+        badges::BadgeTheme,
+        buffer::Style,
+        chat_widget::{
+            format_timestamp, message_text, notice_style, notice_text, Line,
+            DEFAULT_TIMESTAMP_FORMAT,
+        },
+        emotes::EmoteMap,
+        highlight::Highlights,
+        screen::apply_style,
+    },
+    json::Event,
+    log::get_logger,
+    parser::{chat_message::ChatMessage, command::Command},
+};
+
+/// How tail writes what it receives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Plain,
+    /// Plain text with the styles of the chat window.
+    Color,
+    /// Every line the server sends as a JSON object, see `json::Event`.
+    Json,
+}
+
+/// Prints chat one line per message, for pipes, OBS text sources and the
+/// like. With colour the styles of the chat window are kept, without it the
+/// output is plain text.
+pub struct Tail<W: Write> {
+    output: W,
+    format: Format,
+    channels: Vec<String>,
+    color_cache: ColorCache,
+    badges: BadgeTheme,
+    emotes: EmoteMap,
+    highlights: Highlights,
+    timestamp_format: String,
+}
+
+impl<W: Write> Tail<W> {
+    pub fn new(output: W, format: Format) -> Self {
+        Self {
+            output,
+            format,
+            channels: Vec::new(),
+            color_cache: ColorCache::new(),
+            badges: BadgeTheme::default(),
+            emotes: EmoteMap::default(),
+            highlights: Highlights::default(),
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+        }
+    }
+
+    /// With more than one channel every line tells which one it is from.
+    pub fn set_channels(&mut self, channels: Vec<String>) {
+        self.channels = channels;
+    }
+
+    pub fn set_badge_theme(&mut self, badges: BadgeTheme) {
+        self.badges = badges;
+    }
+
+    pub fn set_emotes(&mut self, emotes: EmoteMap) {
+        self.emotes = emotes;
+    }
+
+    pub fn set_highlights(&mut self, highlights: Highlights) {
+        self.highlights = highlights;
+    }
+
+    pub fn set_timestamp_format(&mut self, format: &str) {
+        self.timestamp_format = format.to_string();
+    }
+
+    /// Prints a PRIVMSG, USERNOTICE or NOTICE line, anything else is ignored
+    /// unless the output is JSON. Fails once the output is gone, for instance
+    /// when the reader of a pipe quit.
+    pub fn print(&mut self, line: &str) -> io::Result<()> {
+        if self.format == Format::Json {
+            return self.print_json(line);
+        }
+        let message = match ChatMessage::parse(line) {
+            Ok(message) => message,
+            Err(_) => return Ok(()),
+        };
+        let channel = message.destination();
+        match message.command {
+            Command::PrivMsg(message) => {
+                let user_info = &message.meta_data.user_info;
+                let name = message.name();
+                let (r, g, b) = self.color_cache.user_color(name, user_info.color);
+                let mut line = self.prefix(
+                    format_timestamp(message.meta_data.tmi_sent_ts, &self.timestamp_format),
+                    channel,
+                );
+                let badges = self
+                    .badges
+                    .render(&user_info.badges, &message.meta_data.badge_info);
+                if !badges.is_empty() {
+                    line.extend(badges);
+                    line.push((" ".to_string(), Style::none()));
+                }
+                line.push((name.to_string(), Style::fg(Some(Color::Rgb { r, g, b }))));
+                line.push((": ".to_string(), Style::none()));
+                line.extend(message_text(
+                    &self.emotes,
+                    &self.highlights,
+                    &message,
+                    false,
+                ));
+                self.write(line)
+            }
+            Command::UserNotice(notice) => {
+                let timestamp = format_timestamp(
+                    notice.tmi_sent_ts.unwrap_or_else(Utc::now),
+                    &self.timestamp_format,
+                );
+                let mut line = self.prefix(timestamp.clone(), channel);
+                line.push((notice_text(&notice), notice_style(&notice.event)));
+                self.write(line)?;
+
+                if let Some(msg) = notice.message {
+                    let name = notice.display_name.or(notice.login).unwrap_or_default();
+                    let (r, g, b) = self.color_cache.user_color(name, notice.color);
+                    let mut line = self.prefix(timestamp, channel);
+                    line.push((name.to_string(), Style::fg(Some(Color::Rgb { r, g, b }))));
+                    line.push((format!(": {}", msg), Style::none()));
+                    self.write(line)?;
+                }
+                Ok(())
+            }
+            Command::Notice(notice) => {
+                let mut line = self.prefix(
+                    format_timestamp(Utc::now(), &self.timestamp_format),
+                    channel,
+                );
+                line.push((
+                    format!("\u{25CF} {}", notice.message),
+                    Style::fg(Some(Color::DarkGrey)).attributes(Attribute::Italic),
+                ));
+                self.write(line)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Lines that can not be parsed are logged and left out.
+    fn print_json(&mut self, line: &str) -> io::Result<()> {
+        let event = match Event::new(line, Utc::now()) {
+            Ok(event) => event,
+            Err(err) => {
+                get_logger().warn(err, type_name::<Self>());
+                return Ok(());
+            }
+        };
+        serde_json::to_writer(&mut self.output, &event)?;
+        self.output.write_all(b"\n")?;
+        self.output.flush()
+    }
+
+    /// The time, followed by the channel when there is more than one.
+    fn prefix(&mut self, timestamp: String, channel: Option<&str>) -> Line {
+        let mut line = vec![(timestamp, Style::none()), (" ".to_string(), Style::none())];
+        if let (true, Some(channel)) = (self.channels.len() > 1, channel) {
+            let (r, g, b) = self.color_cache.get(channel);
+            line.push((
+                format!("#{}", channel),
+                Style::fg(Some(Color::Rgb { r, g, b })),
+            ));
+            line.push((" ".to_string(), Style::none()));
+        }
+        line
+    }
+
+    /// Every line is flushed right away, whoever reads the output should not
+    /// have to wait for a buffer to fill up.
+    fn write(&mut self, line: Line) -> io::Result<()> {
+        let mut current = Style::none();
+        for (text, style) in line {
+            if self.format == Format::Color {
+                current = apply_style(&mut self.output, current, style)?;
+            }
+            self.output.write_all(text.as_bytes())?;
+        }
+        if self.format == Format::Color {
+            apply_style(&mut self.output, current, Style::none())?;
+        }
+        self.output.write_all(b"\n")?;
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::log;
+
+    const MESSAGE: &str = "@badge-info=;badges=moderator/1;color=#0000FF;display-name=Ronni;emotes=25:6-10;first-msg=0;id=1;mod=1;room-id=1;subscriber=0;tmi-sent-ts=1500000000000;turbo=0;user-id=10;user-type=mod :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #dallas :hello Kappa";
+
+    fn output(tail: Tail<Vec<u8>>) -> String {
+        String::from_utf8(tail.output).unwrap()
+    }
+
+    #[test]
+    fn should_print_plain_lines() {
+        log::init();
+        let mut tail = Tail::new(Vec::new(), Format::Plain);
+        tail.set_timestamp_format("%Y");
+        tail.print(MESSAGE).unwrap();
+        tail.print(":tmi.twitch.tv NOTICE #dallas :This room is in slow mode.")
+            .unwrap();
+        tail.print("PING :tmi.twitch.tv").unwrap();
+        tail.print("@display-name=;id=2;room-id=1;tmi-sent-ts=1500000000000;user-id=40 :lurker!lurker@lurker.tmi.twitch.tv PRIVMSG #dallas :hi")
+            .unwrap();
+
+        let badge = BadgeTheme::default()
+            .render(&[(crate::parser::Badges::Moderator, "1")], &[])
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect::<String>();
+        let output = output(tail);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], format!("2017 {} Ronni: hello \u{1F608}", badge));
+        assert!(lines[1].ends_with(" \u{25CF} This room is in slow mode."));
+        assert_eq!(lines[2], "2017 lurker: hi");
+    }
+
+    #[test]
+    fn should_name_channel_and_keep_colours() {
+        log::init();
+        let mut tail = Tail::new(Vec::new(), Format::Color);
+        tail.set_timestamp_format("%Y");
+        tail.set_badge_theme(BadgeTheme::empty());
+        tail.set_channels(vec!["dallas".to_string(), "forsen".to_string()]);
+        tail.print(MESSAGE).unwrap();
+
+        let output = output(tail);
+        assert!(output.starts_with("2017 \x1b["));
+        assert!(output.contains("#dallas"));
+        assert!(output.contains("\x1b[38;2;0;0;255mRonni"));
+        assert!(output.ends_with("Ronni\x1b[39m: hello \u{1F608}\n"));
+    }
+
+    #[test]
+    fn should_print_every_event_as_json() {
+        log::init();
+        let mut tail = Tail::new(Vec::new(), Format::Json);
+        tail.print(MESSAGE).unwrap();
+        tail.print("PING :tmi.twitch.tv").unwrap();
+        tail.print("not irc").unwrap();
+
+        let output = output(tail);
+        let events: Vec<Event> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].raw, MESSAGE);
+        assert_eq!(events[1].raw, "PING :tmi.twitch.tv");
+    }
+}
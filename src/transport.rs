@@ -0,0 +1,342 @@
+use std::{
+    any::type_name,
+    borrow::Cow,
+    error::Error,
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use tungstenite::{
+    connect,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    stream::MaybeTlsStream,
+    Error as WebClientErr, Message as SocketMessage, WebSocket,
+};
+use url::Url;
+
+use crate::log::get_logger;
+
+const IRC_PORT: u16 = 6667;
+#[cfg(feature = "tls")]
+const IRCS_PORT: u16 = 6697;
+
+/// Reads wait at most this long for data, which bounds how long the worker
+/// takes to notice something on its control channel.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// A connection to Twitch chat that hands us text and takes single lines.
+/// Reading waits at most `READ_TIMEOUT`, `Ok(None)` means nothing arrived.
+pub trait Transport: Send {
+    fn read(&mut self) -> Result<Option<String>, Box<dyn Error>>;
+    fn write(&mut self, line: &str) -> Result<(), Box<dyn Error>>;
+    fn close(&mut self);
+}
+
+/// Opens a connection depending on the scheme of the url, `ws` and `wss` use
+/// websockets while `irc` and `ircs` speak IRC directly over tcp.
+pub fn open(url: &str) -> Result<Box<dyn Transport>, Box<dyn Error>> {
+    let parsed = Url::parse(url)?;
+    match parsed.scheme() {
+        "ws" | "wss" => Ok(Box::new(WebSocketTransport::connect(url)?)),
+        "irc" => {
+            let stream = tcp(&parsed, IRC_PORT)?;
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            Ok(Box::new(IrcTransport::new(stream)))
+        }
+        #[cfg(feature = "tls")]
+        "ircs" => {
+            let stream = tls::connect(tcp(&parsed, IRCS_PORT)?, host(&parsed)?)?;
+            stream.sock.set_read_timeout(Some(READ_TIMEOUT))?;
+            Ok(Box::new(IrcTransport::new(stream)))
+        }
+        #[cfg(not(feature = "tls"))]
+        "ircs" => Err("ircs:// needs the tls feature".into()),
+        scheme => Err(format!("unsupported scheme: {}", scheme).into()),
+    }
+}
+
+fn host(url: &Url) -> Result<&str, Box<dyn Error>> {
+    url.host_str()
+        .ok_or_else(|| format!("no host in url: {}", url).into())
+}
+
+fn tcp(url: &Url, default_port: u16) -> Result<TcpStream, Box<dyn Error>> {
+    let address = (host(url)?, url.port().unwrap_or(default_port));
+    get_logger().debug(
+        format!("Connecting to: {}:{}", address.0, address.1),
+        type_name::<IrcTransport<TcpStream>>(),
+    );
+    Ok(TcpStream::connect(address)?)
+}
+
+pub struct WebSocketTransport {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+}
+
+impl WebSocketTransport {
+    fn connect(url: &str) -> Result<Self, Box<dyn Error>> {
+        let log = get_logger();
+        let (mut socket, response) = connect(url)?;
+        set_read_timeout(socket.get_mut())?;
+
+        log.info(
+            format!("Response https code: {}", &response.status()),
+            type_name::<WebSocketTransport>(),
+        );
+        for (header, value) in response.headers() {
+            log.info(
+                format!("* {}: {:?}", header, value),
+                type_name::<WebSocketTransport>(),
+            );
+        }
+        Ok(Self { socket })
+    }
+}
+
+/// The handshakes are done by now, the timeout goes on the underlying tcp
+/// stream and TLS streams hand the timeout back up just like plain ones.
+fn set_read_timeout(stream: &mut MaybeTlsStream<TcpStream>) -> std::io::Result<()> {
+    match stream {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(READ_TIMEOUT)),
+        #[cfg(feature = "tls")]
+        MaybeTlsStream::Rustls(stream) => stream.sock.set_read_timeout(Some(READ_TIMEOUT)),
+        _ => Ok(()),
+    }
+}
+
+/// A read timeout shows up as WouldBlock on unix and TimedOut on windows.
+fn timed_out(err: &std::io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+impl Transport for WebSocketTransport {
+    fn read(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        match self.socket.read_message() {
+            Ok(SocketMessage::Text(text)) => Ok(Some(text)),
+            Ok(_) => Ok(None),
+            Err(WebClientErr::Io(ref err)) if timed_out(err) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        Ok(self
+            .socket
+            .write_message(SocketMessage::Text(line.to_string()))?)
+    }
+
+    fn close(&mut self) {
+        self.socket
+            .close(Some(CloseFrame {
+                code: CloseCode::Normal,
+                reason: Cow::Borrowed(""),
+            }))
+            .unwrap_or(());
+    }
+}
+
+/// IRC over a byte stream, messages are separated by `\r\n` and may arrive
+/// split up in any way so partial lines are kept until the rest shows up.
+pub struct IrcTransport<S> {
+    stream: S,
+    buffer: Vec<u8>,
+    closed: bool,
+}
+
+impl<S: Read + Write> IrcTransport<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// Takes the complete lines out of the buffer.
+    fn lines(&mut self) -> Option<String> {
+        let end = self.buffer.iter().rposition(|&b| b == b'\n')? + 1;
+        let rest = self.buffer.split_off(end);
+        let lines = std::mem::replace(&mut self.buffer, rest);
+        let lines = String::from_utf8_lossy(&lines);
+        let lines: Vec<&str> = lines.lines().filter(|line| !line.is_empty()).collect();
+        Some(lines.join("\r\n")).filter(|lines| !lines.is_empty())
+    }
+}
+
+impl<S: Read + Write + Send> Transport for IrcTransport<S> {
+    fn read(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        if self.closed {
+            return Err("connection closed".into());
+        }
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    // hand out what came in before the server hung up, the
+                    // next read reports the closed connection
+                    self.closed = true;
+                    return match self.lines() {
+                        Some(lines) => Ok(Some(lines)),
+                        None => Err("connection closed by server".into()),
+                    };
+                }
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                    if let Some(lines) = self.lines() {
+                        return Ok(Some(lines));
+                    }
+                }
+                Err(err) if timed_out(&err) => return Ok(None),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn write(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        let mut data = format!("{}\r\n", line).into_bytes();
+        while !data.is_empty() {
+            match self.stream.write(&data) {
+                Ok(0) => return Err("connection closed by server".into()),
+                Ok(n) => {
+                    data.drain(..n);
+                }
+                Err(err) if timed_out(&err) => thread::sleep(Duration::from_millis(10)),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        loop {
+            match self.stream.flush() {
+                Ok(()) => return Ok(()),
+                Err(err) if timed_out(&err) => thread::sleep(Duration::from_millis(10)),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+    }
+}
+
+#[cfg(feature = "tls")]
+pub mod tls {
+    use std::{convert::TryFrom, error::Error, net::TcpStream, sync::Arc};
+
+    use rustls::{
+        ClientConfig, ClientConnection, OwnedTrustAnchor, RootCertStore, ServerName, StreamOwned,
+    };
+
+    pub type TlsStream = StreamOwned<ClientConnection, TcpStream>;
+
+    /// Wraps the stream and completes the handshake before any read timeout
+    /// is set.
+    pub fn connect(mut stream: TcpStream, host: &str) -> Result<TlsStream, Box<dyn Error>> {
+        let mut roots = RootCertStore::empty();
+        roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let mut connection = ClientConnection::new(Arc::new(config), ServerName::try_from(host)?)?;
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream)?;
+        }
+        Ok(StreamOwned::new(connection, stream))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{collections::VecDeque, io::Result as IoResult};
+
+    /// Hands out the chunks one read at a time and then would block.
+    struct Chunks {
+        chunks: VecDeque<&'static str>,
+        written: Vec<u8>,
+    }
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+            match self.chunks.pop_front() {
+                Some("") => Ok(0),
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(chunk.as_bytes());
+                    Ok(chunk.len())
+                }
+                None => Err(ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    impl Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> IoResult<()> {
+            Ok(())
+        }
+    }
+
+    fn transport(chunks: &[&'static str]) -> IrcTransport<Chunks> {
+        IrcTransport::new(Chunks {
+            chunks: chunks.iter().copied().collect(),
+            written: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn should_keep_partial_lines_until_complete() {
+        let mut transport = transport(&[":a PRIVMSG #b :he", "llo\r\nPING :tmi"]);
+        assert_eq!(
+            transport.read().unwrap(),
+            Some(":a PRIVMSG #b :hello".to_string())
+        );
+        assert_eq!(transport.read().unwrap(), None);
+
+        transport
+            .stream
+            .chunks
+            .push_back(".twitch.tv\r\n:c JOIN #b\r\n");
+        assert_eq!(
+            transport.read().unwrap(),
+            Some("PING :tmi.twitch.tv\r\n:c JOIN #b".to_string())
+        );
+    }
+
+    #[test]
+    fn should_return_last_lines_before_failing_on_close() {
+        let mut transport = transport(&["PING\r\n", ""]);
+        assert_eq!(transport.read().unwrap(), Some("PING".to_string()));
+        assert!(transport.read().is_err());
+    }
+
+    #[test]
+    fn should_terminate_written_lines() {
+        let mut transport = transport(&[]);
+        transport.write("NICK foo").unwrap();
+        transport.write("JOIN #bar").unwrap();
+        assert_eq!(transport.stream.written, b"NICK foo\r\nJOIN #bar\r\n");
+    }
+
+    #[test]
+    fn should_reject_unknown_scheme() {
+        assert_eq!(
+            open("http://localhost").err().unwrap().to_string(),
+            "unsupported scheme: http"
+        );
+    }
+}
@@ -1,87 +1,341 @@
-use std::{any::type_name, io::stdout};
+use std::{
+    any::type_name,
+    io::{stdout, ErrorKind, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use crate::{
     gui::{
-        chat_widget::ChatWidget,
-        event_handler::{Action, EventHandler},
+        badges::BadgeTheme,
+        chat_widget::{ChatWidget, DEFAULT_TIMESTAMP_FORMAT},
+        emotes::EmoteMap,
+        event_handler::{Action, EventHandler, KeyMap},
+        highlight::Highlights,
+        input_widget::InputWidget,
+        layout::{Constraint, Layout, Region},
         screen::Screen,
+        status_bar::StatusBar,
+        tab_bar::TabBar,
         window::Window,
         Pos, Size,
     },
     log::get_logger,
-    parser::chat_message::ChatMessage,
-    twitch_client::TwitchClient,
+    parser::{chat_message::ChatMessage, command::Command},
+    recording::{Recorder, Replay, Speed},
+    tail::{Format, Tail},
+    twitch_client::{ChatSource, TwitchClient},
 };
 
-use crossbeam::select;
-use crossterm::{
-    execute,
-    terminal::{size, EnterAlternateScreen},
-};
+#[cfg(feature = "images")]
+use crate::gui::graphics::EmoteImages;
 
 use crate::twitch_client::Message;
+use crossbeam::{
+    channel::{bounded, never, tick},
+    select,
+};
+
+/// The parts the screen is divided into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Pane {
+    Tabs,
+    Chat,
+    Events,
+    Status,
+    Input,
+}
 
 pub struct TwitchChat {
     nick: String,
-    channel: String,
+    channels: Vec<String>,
     token: String,
+    url: String,
+    emotes: EmoteMap,
+    highlights: Highlights,
+    badges: BadgeTheme,
+    timestamp_format: String,
+    keys: KeyMap,
+    record: Option<PathBuf>,
+    replay: Option<(PathBuf, Speed)>,
+    #[cfg(feature = "images")]
+    images: Option<EmoteImages>,
 }
 
+/// How often the status bar is drawn when nothing else happens.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(feature = "tls")]
+static TWITCH_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+#[cfg(not(feature = "tls"))]
 static TWITCH_URL: &str = "ws://irc-ws.chat.twitch.tv:80";
 
+/// Plain IRC, for when websockets are not an option.
+#[cfg(feature = "tls")]
+pub static TWITCH_IRC_URL: &str = "ircs://irc.chat.twitch.tv:6697";
+#[cfg(not(feature = "tls"))]
+pub static TWITCH_IRC_URL: &str = "irc://irc.chat.twitch.tv:6667";
+
 impl TwitchChat {
-    pub fn new(
-        nick: impl Into<String>,
-        channel: impl Into<String>,
-        token: impl Into<String>,
-    ) -> Self {
+    pub fn new(nick: impl Into<String>, channels: Vec<String>, token: impl Into<String>) -> Self {
         Self {
             nick: nick.into(),
-            channel: channel.into(),
+            channels,
             token: token.into(),
+            url: TWITCH_URL.to_string(),
+            emotes: EmoteMap::default(),
+            highlights: Highlights::default(),
+            badges: BadgeTheme::default(),
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+            keys: KeyMap::default(),
+            record: None,
+            replay: None,
+            #[cfg(feature = "images")]
+            images: None,
         }
     }
 
-    pub fn start(&self) {
+    /// Connect somewhere else than Twitch, for instance a local server.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Show emotes with other glyphs than the default ones.
+    pub fn emotes(mut self, emotes: EmoteMap) -> Self {
+        self.emotes = emotes;
+        self
+    }
+
+    /// Highlight other messages than the default ones, mentions of our nick
+    /// are always looked for.
+    pub fn highlights(mut self, highlights: Highlights) -> Self {
+        self.highlights = highlights;
+        self
+    }
+
+    /// Show other glyphs in front of names.
+    pub fn badges(mut self, badges: BadgeTheme) -> Self {
+        self.badges = badges;
+        self
+    }
+
+    /// A chrono format string for the time messages were sent.
+    pub fn timestamp_format(mut self, format: impl Into<String>) -> Self {
+        self.timestamp_format = format.into();
+        self
+    }
+
+    pub fn keys(mut self, keys: KeyMap) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Write every line received to a file, so it can be replayed later.
+    pub fn record(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record = Some(path.into());
+        self
+    }
+
+    /// Play a recording back instead of connecting to Twitch.
+    pub fn replay(mut self, path: impl Into<PathBuf>, speed: Speed) -> Self {
+        self.replay = Some((path.into(), speed));
+        self
+    }
+
+    /// Show emotes as images, where they are cached, instead of glyphs.
+    #[cfg(feature = "images")]
+    pub fn images(mut self, images: EmoteImages) -> Self {
+        self.images = Some(images);
+        self
+    }
+
+    pub fn start(&self) -> Result<(), String> {
         let log = get_logger();
         log.info("starting Twitch Chat", type_name::<TwitchChat>());
+        let client = self.connect()?;
 
-        let output = stdout();
-        let size = size().expect("Failed to fetch terminal size");
+        // the screen leaves the alternate screen again once it is dropped
+        let mut screen = Screen::stdout()
+            .expect("Failed to fetch terminal size")
+            .alternate_screen(true);
 
-        execute!(stdout(), EnterAlternateScreen).unwrap();
+        let mut size = screen.size();
+        let mut show_events = false;
+        let regions = self.layout(show_events).regions(Pos::zero(), size);
+        let region = |pane| regions.get(&pane).copied().unwrap_or_else(Region::empty);
 
-        let mut screen = Screen::new(output, Size::new(size.0, size.1)).unwrap();
-        let mut window = Window::new(Pos::new(0, 0), Size::new(size.0, size.1));
-        let mut chat = ChatWidget::new(&mut window, Pos::new(0, 0), Size::new(size.0, size.1));
+        #[allow(unused_mut)]
+        let mut emotes = self.emotes.clone();
+        #[cfg(feature = "images")]
+        if let Some(images) = &self.images {
+            screen.set_images(images.clone());
+            emotes.set_images(images.clone());
+        }
+        let chat_region = region(Pane::Chat);
+        let mut window = Window::new(chat_region.pos, chat_region.size);
+        let mut chat = ChatWidget::new(&mut window, chat_region.pos, chat_region.size);
+        chat.set_channels(self.channels.clone());
+        chat.set_emotes(emotes);
+        let mut highlights = self.highlights.clone();
+        highlights.set_nick(&self.nick);
+        chat.set_highlights(highlights);
+        chat.set_badge_theme(self.badges.clone());
+        chat.set_timestamp_format(&self.timestamp_format);
+        let events_region = region(Pane::Events);
+        let mut events_window = Window::new(events_region.pos, events_region.size);
+        let mut events = ChatWidget::new(&mut events_window, events_region.pos, events_region.size);
+        events.set_channels(self.channels.clone());
+        events.set_timestamp_format(&self.timestamp_format);
+        let mut tabs = TabBar::new(region(Pane::Tabs).pos, size.width());
+        let mut status = StatusBar::new(region(Pane::Status).pos, size.width());
+        status.set_channels(self.channels.clone());
+        let mut input = InputWidget::new(
+            region(Pane::Input).pos,
+            region(Pane::Input).size,
+            &self.keys,
+        );
         screen.enable_raw_mode().expect("could not enable raw mode");
+        self.draw_view(&mut screen, &chat, &tabs, &mut status, &mut input);
+        screen.render().unwrap();
 
-        let client = TwitchClient::new(TWITCH_URL, &self.token, &self.channel, &self.nick).unwrap();
-        let event_handler = EventHandler::new();
+        let mut receiver = client.receiver().clone();
+        let event_handler = EventHandler::new(self.keys.clone());
+        // the message rate goes down on its own, so the status bar is drawn
+        // again every now and then
+        let ticker = tick(STATUS_INTERVAL);
 
         loop {
             select! {
-                recv(client.receiver) -> chat_event => {
-                    if let Ok(chat_event) = chat_event {
-                        if let Message::Text(message) = chat_event {
-
-                            if message.starts_with('@') {
-                                match ChatMessage::parse(&message) {
-                                    Ok(message) => chat.print(&mut screen, message),
-                                    Err(message) => log.error(format!("{:#?}", message), type_name::<TwitchChat>()),
-                                }
-                            } else {
-                                 log.info(&message, type_name::<TwitchChat>());
+                recv(receiver) -> chat_event => {
+                    if let Ok(Message::Text(message)) = chat_event {
+                        for line in message.lines() {
+                            match ChatMessage::parse(line) {
+                                Ok(ChatMessage { command: Command::PrivMsg(_), .. }) => {
+                                    chat.print(&mut screen, line);
+                                    status.message_received(Instant::now());
+                                },
+                                Ok(ChatMessage { command: Command::Notice(_), .. }) => chat.print(&mut screen, line),
+                                Ok(ChatMessage { command: Command::RoomState(state), .. }) => status.room_state(&state),
+                                Ok(ChatMessage { command: Command::UserNotice(_), .. }) => {
+                                    chat.print(&mut screen, line);
+                                    events.print(&mut screen, line);
+                                },
+                                Ok(ChatMessage { command: Command::ClearChat(clear), .. }) => {
+                                    chat.clear_chat(&mut screen, &clear);
+                                    events.clear_chat(&mut screen, &clear);
+                                },
+                                Ok(ChatMessage { command: Command::ClearMsg(clear), .. }) => {
+                                    chat.clear_msg(&mut screen, &clear);
+                                    events.clear_msg(&mut screen, &clear);
+                                },
+                                Ok(message) => log.info(format!("{:?}", message), type_name::<TwitchChat>()),
+                                Err(message) => log.error(format!("{:#?}", message), type_name::<TwitchChat>()),
                             }
                         }
+                        status.draw(&mut screen, Instant::now());
+                        screen.render().unwrap();
+                    } else if let Ok(Message::State(state)) = chat_event {
+                        log.info(format!("Connection state: {:?}", state), type_name::<TwitchChat>());
+                        chat.system_line(&mut screen, &state.to_string());
+                        status.set_state(state);
+                        status.draw(&mut screen, Instant::now());
+                        screen.render().unwrap();
+                    } else if let Ok(Message::Latency(latency)) = chat_event {
+                        status.set_latency(latency);
+                        status.draw(&mut screen, Instant::now());
+                        screen.render().unwrap();
+                    } else if chat_event.is_err() {
+                        // a replay ran out or the client is gone, what was
+                        // shown stays until we are told to quit
+                        receiver = never();
+                        chat.system_line(&mut screen, "End of chat");
                         screen.render().unwrap();
                     }
                 },
+                recv(ticker) -> _ => {
+                    status.draw(&mut screen, Instant::now());
+                    screen.render().unwrap();
+                },
                 recv(event_handler.receiver) -> action => {
                     if let Ok(action) = action {
                         match action {
                             Action::Clear => {
                                 chat.clear(&mut screen);
+                                events.clear(&mut screen);
+                                screen.render().unwrap();
+                            },
+                            Action::ToggleDeleted => {
+                                chat.toggle_deleted(&mut screen);
+                                events.toggle_deleted(&mut screen);
+                                screen.render().unwrap();
+                            },
+                            Action::ScrollUp => {
+                                chat.scroll_up(&mut screen, 1);
+                                screen.render().unwrap();
+                            },
+                            Action::JumpToParent => {
+                                chat.jump_to_parent(&mut screen);
+                                screen.render().unwrap();
+                            },
+                            Action::ScrollDown => {
+                                chat.scroll_down(&mut screen, 1);
+                                screen.render().unwrap();
+                            },
+                            Action::PageUp => {
+                                let lines = chat.page_size();
+                                chat.scroll_up(&mut screen, lines);
+                                screen.render().unwrap();
+                            },
+                            Action::PageDown => {
+                                let lines = chat.page_size();
+                                chat.scroll_down(&mut screen, lines);
+                                screen.render().unwrap();
+                            },
+                            Action::ScrollTop => {
+                                chat.scroll_to_top(&mut screen);
+                                screen.render().unwrap();
+                            },
+                            Action::ScrollBottom => {
+                                chat.scroll_to_bottom(&mut screen);
+                                screen.render().unwrap();
+                            },
+                            Action::NextTab | Action::PrevTab => {
+                                if matches!(action, Action::NextTab) {
+                                    chat.next_view(&mut screen);
+                                } else {
+                                    chat.prev_view(&mut screen);
+                                }
+                                self.draw_view(&mut screen, &chat, &tabs, &mut status, &mut input);
+                                screen.render().unwrap();
+                            },
+                            Action::Resize(..) | Action::ToggleEvents => {
+                                match action {
+                                    Action::Resize(width, height) => size = Size::new(width, height),
+                                    _ => show_events = !show_events,
+                                }
+                                let regions = self.layout(show_events).regions(Pos::zero(), size);
+                                let region = |pane| regions.get(&pane).copied().unwrap_or_else(Region::empty);
+                                // clears everything, the panes are drawn again right after
+                                screen.resize(size).unwrap();
+                                chat.resize(&mut screen, region(Pane::Chat).pos, region(Pane::Chat).size);
+                                events.resize(&mut screen, region(Pane::Events).pos, region(Pane::Events).size);
+                                tabs.resize(region(Pane::Tabs).pos, size.width());
+                                status.resize(region(Pane::Status).pos, size.width());
+                                input.resize(region(Pane::Input).pos, region(Pane::Input).size);
+                                self.draw_view(&mut screen, &chat, &tabs, &mut status, &mut input);
+                                screen.render().unwrap();
+                            },
+                            Action::Focus | Action::Unfocus => {
+                                input.focus(matches!(action, Action::Focus));
+                                input.draw(&mut screen);
+                                screen.render().unwrap();
+                            },
+                            Action::Input(key) => {
+                                if let Some(text) = input.handle(key) {
+                                    client.send_message(self.target(&chat), &text);
+                                }
+                                input.draw(&mut screen);
                                 screen.render().unwrap();
                             },
                             Action::Exit => break,
@@ -92,5 +346,120 @@ impl TwitchChat {
             }
         }
         log.info("Exiting twitch chat", type_name::<TwitchChat>());
+        Ok(())
+    }
+
+    /// Prints the chat to stdout instead of showing it, until stdout goes
+    /// away, the connection is dropped for good or we are interrupted.
+    pub fn tail(&self, format: Format) -> Result<(), String> {
+        let log = get_logger();
+        log.info("starting tail", type_name::<TwitchChat>());
+
+        let mut tail = Tail::new(stdout(), format);
+        tail.set_channels(self.channels.clone());
+        tail.set_emotes(self.emotes.clone());
+        let mut highlights = self.highlights.clone();
+        highlights.set_nick(&self.nick);
+        tail.set_highlights(highlights);
+        tail.set_badge_theme(self.badges.clone());
+        tail.set_timestamp_format(&self.timestamp_format);
+
+        let (interrupt_tx, interrupt) = bounded(1);
+        ctrlc::set_handler(move || interrupt_tx.try_send(()).unwrap_or(()))
+            .map_err(|err| format!("could not listen for ctrl-c: {}", err))?;
+
+        let client = self.connect()?;
+        loop {
+            select! {
+                recv(client.receiver()) -> chat_event => match chat_event {
+                    Ok(Message::Text(message)) => {
+                        for line in message.lines() {
+                            match tail.print(line) {
+                                Ok(()) => (),
+                                Err(err) if err.kind() == ErrorKind::BrokenPipe => {
+                                    log.info("stdout was closed", type_name::<TwitchChat>());
+                                    return Ok(());
+                                }
+                                Err(err) => return Err(format!("could not print: {}", err)),
+                            }
+                        }
+                    },
+                    // stdout only gets chat, so that it can be piped on
+                    Ok(Message::State(state)) => eprintln!("{}", state),
+                    Ok(_) => (),
+                    Err(_) => break,
+                },
+                recv(interrupt) -> _ => {
+                    log.info("interrupted", type_name::<TwitchChat>());
+                    break;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Connects to Twitch, recording what comes in if asked to, or plays a
+    /// recording back.
+    fn connect(&self) -> Result<Box<dyn ChatSource>, String> {
+        if let Some((path, speed)) = &self.replay {
+            return Ok(Box::new(Replay::start(path, *speed)?));
+        }
+        let recorder = self.record.as_ref().map(Recorder::create).transpose()?;
+        let client = TwitchClient::new(
+            &self.url,
+            &self.token,
+            self.channels.clone(),
+            &self.nick,
+            recorder,
+        )
+        .map_err(|err| format!("could not connect: {}", err))?;
+        Ok(Box::new(client))
+    }
+
+    /// Tabs on top when there is more than one channel, the status bar and
+    /// the input line at the bottom and the event feed next to the chat when
+    /// it is shown.
+    fn layout(&self, show_events: bool) -> Layout<Pane> {
+        let tabs = if self.channels.len() > 1 { 1 } else { 0 };
+        let mut body = vec![(Constraint::Ratio(3), Layout::Pane(Pane::Chat))];
+        if show_events {
+            body.push((Constraint::Ratio(1), Layout::Pane(Pane::Events)));
+        }
+        Layout::vertical(vec![
+            (Constraint::Fixed(tabs), Layout::Pane(Pane::Tabs)),
+            (Constraint::Ratio(1), Layout::horizontal(body)),
+            (Constraint::Fixed(1), Layout::Pane(Pane::Status)),
+            (Constraint::Fixed(1), Layout::Pane(Pane::Input)),
+        ])
+    }
+
+    /// Messages go to the channel that is viewed, or to the first one in the
+    /// merged view.
+    fn target<'c>(&'c self, chat: &'c ChatWidget) -> &'c str {
+        chat.view().unwrap_or(&self.channels[0])
+    }
+
+    fn draw_view(
+        &self,
+        screen: &mut Screen<impl Write>,
+        chat: &ChatWidget,
+        tabs: &TabBar,
+        status: &mut StatusBar,
+        input: &mut InputWidget,
+    ) {
+        if self.channels.len() > 1 {
+            let names: Vec<String> = std::iter::once("all".to_string())
+                .chain(self.channels.iter().map(|channel| format!("#{}", channel)))
+                .collect();
+            let active = match chat.view() {
+                Some(view) => 1 + self.channels.iter().position(|c| c == view).unwrap_or(0),
+                None => 0,
+            };
+            tabs.draw(screen, &names, active);
+            input.set_prompt(format!("#{}> ", self.target(chat)));
+        }
+        status.set_view(chat.view());
+        status.draw(screen, Instant::now());
+        input.draw(screen);
     }
 }
@@ -1,23 +1,25 @@
-#![allow(dead_code)]
-use crate::log::get_logger;
-use crossbeam::channel::{unbounded, Receiver, Sender};
+use crate::{
+    log::get_logger,
+    parser::{chat_message::ChatMessage, command::Command},
+    rate_limiter::RateLimiter,
+    recording::Recorder,
+    transport::{self, Transport},
+};
+use chrono::Utc;
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use rand::Rng;
 use std::{
     any::type_name,
-    borrow::Cow,
+    collections::VecDeque,
     error::Error,
-    io::ErrorKind,
-    net::TcpStream,
+    fmt::Display,
     ops::{Deref, DerefMut},
     thread::{self, JoinHandle},
-    time::Duration,
-};
-use tungstenite::{
-    connect,
-    protocol::{frame::coding::CloseCode, CloseFrame},
-    stream::MaybeTlsStream,
-    Error as WebClientErr, Message as SocketMessage, WebSocket,
+    time::{Duration, Instant},
 };
 
+type Socket = Box<dyn Transport>;
+
 pub struct TwitchClient {
     worker: Worker,
     sender: Sender<Message>,
@@ -26,15 +28,77 @@ pub struct TwitchClient {
 #[derive(Debug, PartialEq, Eq)]
 pub enum Message {
     Text(String),
+    Send {
+        channel: String,
+        text: String,
+    },
+    State(ConnectionState),
+    /// Round trip time of our last PING.
+    Latency(Duration),
     Terminate,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+    Reconnecting { attempt: u32, delay: Duration },
+}
+
+impl Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionState::Connected => write!(f, "Connected to Twitch chat"),
+            ConnectionState::Disconnected => write!(f, "Lost connection to Twitch chat"),
+            ConnectionState::Reconnecting { attempt, delay } => write!(
+                f,
+                "Reconnecting in {:.1}s, attempt {}",
+                delay.as_secs_f32(),
+                attempt
+            ),
+        }
+    }
+}
+
+/// Twitch allows regular users 20 messages per 30 seconds, going over that
+/// gets the account locked out of chat for a while.
+const MESSAGE_LIMIT: usize = 20;
+const MESSAGE_PERIOD: Duration = Duration::from_secs(30);
+/// And 20 join attempts per 10 seconds.
+const JOIN_LIMIT: usize = 20;
+const JOIN_PERIOD: Duration = Duration::from_secs(10);
+
+/// Frames read before looking at the control channel again, so a busy chat
+/// can not hold up sending or shutting down.
+const MAX_FRAMES_PER_WAKEUP: usize = 64;
+/// Sent along with our PING, the server hands it back in the PONG.
+const PING_TOKEN: &str = "tmi.twitch.tv";
+/// How often we PING the server to measure the latency.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// How long to wait for the server to answer our close.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Everything needed to dial Twitch again after the connection was lost.
+#[derive(Debug, Clone)]
+struct Credentials {
+    url: String,
+    token: String,
+    channels: Vec<String>,
+    nick: String,
+}
+
 impl TwitchClient {
+    /// Connects and starts the worker, every line received is written to the
+    /// recorder too when there is one.
     pub fn new(
         url: impl Into<String>,
         token: impl Into<String>,
-        channel: impl Into<String>,
+        channels: Vec<String>,
         nick: impl Into<String>,
+        recorder: Option<Recorder>,
     ) -> Result<TwitchClient, Box<dyn Error>> {
         let log = get_logger();
         log.debug(
@@ -43,62 +107,143 @@ impl TwitchClient {
         );
         log.info("Starting Twitch-Client", type_name::<TwitchClient>());
 
-        let url = url.into();
-        let token = token.into();
-        let channel = channel.into();
-        let nick = nick.into();
+        let credentials = Credentials {
+            url: url.into(),
+            token: token.into(),
+            channels,
+            nick: nick.into(),
+        };
+        let socket = dial(&credentials)?;
 
-        log.debug(
-            format!("Connecting to url: {}", &url),
-            type_name::<TwitchClient>(),
-        );
+        let (sender, receiver) = unbounded();
+        let worker = Worker::run("ws-worker", receiver, socket, credentials, recorder)?;
+        Ok(TwitchClient { sender, worker })
+    }
 
-        let (mut socket, response) = connect(&url)?;
+    /// Queues a chat message, the worker sends it as soon as the rate limit
+    /// allows.
+    pub fn send_message(&self, channel: impl Into<String>, text: impl Into<String>) {
+        let text: String = text.into();
+        let text = text.replace(['\r', '\n'], " ");
+        self.sender
+            .send(Message::Send {
+                channel: channel.into(),
+                text,
+            })
+            .unwrap_or(());
+    }
+}
 
-        if let MaybeTlsStream::Plain(socket) = socket.get_mut() {
-            socket.set_nonblocking(true)?;
-        }
+/// Where chat comes from, Twitch itself or a recording of it.
+pub trait ChatSource {
+    fn receiver(&self) -> &Receiver<Message>;
 
-        log.info(
-            format!("Connected to: {}", &url),
-            type_name::<TwitchClient>(),
-        );
-        log.info(
-            format!("Response https code: {}", &response.status()),
-            type_name::<TwitchClient>(),
-        );
+    fn send_message(&self, channel: &str, text: &str);
+}
 
-        for (header, value) in response.headers() {
-            log.info(
-                format!("* {}: {:?}", header, value),
-                type_name::<TwitchClient>(),
-            );
-        }
+impl ChatSource for TwitchClient {
+    fn receiver(&self) -> &Receiver<Message> {
+        &self.worker.receiver
+    }
 
-        let token = format!("PASS oauth:{}", &token);
-        let nick_message = format!("NICK {}", &nick);
-        let join_message = format!("JOIN #{}", &channel);
-        let tag_message = "CAP REQ :twitch.tv/tags";
-
-        socket.write_message(SocketMessage::Text(token)).unwrap();
-        socket
-            .write_message(SocketMessage::Text(nick_message))
-            .unwrap();
-        socket
-            .write_message(SocketMessage::Text(join_message))
-            .unwrap();
-        socket
-            .write_message(SocketMessage::Text(tag_message.to_string()))
-            .unwrap();
+    fn send_message(&self, channel: &str, text: &str) {
+        TwitchClient::send_message(self, channel, text)
+    }
+}
 
-        log.debug(
-            format!("Joined channel: {}, with nick: {}", &channel, &nick),
-            type_name::<TwitchClient>(),
-        );
+/// Opens the connection and logs in, authentication and capabilities are
+/// sent on every connect. Channels are joined by the worker afterwards.
+fn dial(credentials: &Credentials) -> Result<Socket, Box<dyn Error>> {
+    let log = get_logger();
+    log.debug(
+        format!("Connecting to url: {}", &credentials.url),
+        type_name::<TwitchClient>(),
+    );
 
-        let (sender, receiver) = unbounded();
-        let worker = Worker::run("ws-worker", receiver, socket)?;
-        Ok(TwitchClient { sender, worker })
+    let mut socket = transport::open(&credentials.url)?;
+
+    log.info(
+        format!("Connected to: {}", &credentials.url),
+        type_name::<TwitchClient>(),
+    );
+
+    socket.write(&format!("PASS oauth:{}", &credentials.token))?;
+    socket.write(&format!("NICK {}", &credentials.nick))?;
+    socket.write("CAP REQ :twitch.tv/tags")?;
+
+    log.debug(
+        format!("Logged in with nick: {}", &credentials.nick),
+        type_name::<TwitchClient>(),
+    );
+    Ok(socket)
+}
+
+/// Exponential backoff with jitter, each failed attempt doubles the delay up
+/// to a maximum and a random part of it is taken off so that many clients
+/// dropped at once do not all come back at the same time.
+#[derive(Debug, Default)]
+struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    fn next(&mut self) -> Duration {
+        let delay = backoff_delay(self.attempt, rand::thread_rng().gen());
+        self.attempt += 1;
+        delay
+    }
+}
+
+/// `jitter` is between 0 and 1 and picks a delay between half and all of the
+/// exponential delay.
+fn backoff_delay(attempt: u32, jitter: f64) -> Duration {
+    let delay = BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(BACKOFF_MAX);
+    delay.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+}
+
+/// Dials until a connection is made, waiting longer after every failure.
+/// Chat messages queued meanwhile are kept, returns None if we were told to
+/// terminate while waiting.
+fn redial(
+    credentials: &Credentials,
+    receiver: &Receiver<Message>,
+    tx: &Sender<Message>,
+    outbox: &mut VecDeque<(String, String)>,
+) -> Option<Socket> {
+    let log = get_logger();
+    let mut backoff = Backoff::default();
+    loop {
+        match dial(credentials) {
+            Ok(socket) => {
+                log.info("Reconnected to Twitch", type_name::<Worker>());
+                tx.send(Message::State(ConnectionState::Connected))
+                    .unwrap_or(());
+                return Some(socket);
+            }
+            Err(err) => log.warn(
+                format!("Could not reconnect: {}", err),
+                type_name::<Worker>(),
+            ),
+        }
+
+        let delay = backoff.next();
+        tx.send(Message::State(ConnectionState::Reconnecting {
+            attempt: backoff.attempt + 1,
+            delay,
+        }))
+        .unwrap_or(());
+
+        let deadline = Instant::now() + delay;
+        loop {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Message::Send { channel, text }) => outbox.push_back((channel, text)),
+                Ok(Message::Terminate) | Err(RecvTimeoutError::Disconnected) => return None,
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) => break,
+            }
+        }
     }
 }
 
@@ -118,72 +263,441 @@ impl DerefMut for TwitchClient {
 
 impl Drop for TwitchClient {
     fn drop(&mut self) {
-        self.sender.send(Message::Terminate).unwrap();
+        self.sender.send(Message::Terminate).unwrap_or(());
         self.thread.take().map(JoinHandle::join);
     }
 }
 
 pub struct Worker {
-    name: String,
     thread: Option<JoinHandle<()>>,
     pub receiver: Receiver<Message>,
 }
 
 impl Worker {
-    pub fn run(
+    fn run(
         name: impl Into<String>,
         receiver: Receiver<Message>,
-        mut socket: WebSocket<MaybeTlsStream<TcpStream>>,
+        mut socket: Socket,
+        credentials: Credentials,
+        mut recorder: Option<Recorder>,
     ) -> Result<Worker, Box<dyn Error>> {
         let (tx, rx) = unbounded();
-        let name = name.into();
-        let handle = thread::Builder::new().name(name.clone()).spawn(move || {
+        let handle = thread::Builder::new().name(name.into()).spawn(move || {
             let log = get_logger();
-            loop {
-                thread::sleep(Duration::from_millis(300));
+            let mut limiter = RateLimiter::new(MESSAGE_LIMIT, MESSAGE_PERIOD);
+            let mut join_limiter = RateLimiter::new(JOIN_LIMIT, JOIN_PERIOD);
+            let mut joins: VecDeque<String> = credentials.channels.iter().cloned().collect();
+            let mut outbox: VecDeque<(String, String)> = VecDeque::new();
+            let mut sent = 0;
+            let mut next_ping = Instant::now();
+            let mut ping_sent = None;
+            let mut closing = false;
+            let mut close_deadline = None;
+            tx.send(Message::State(ConnectionState::Connected))
+                .unwrap_or(());
+            'worker: loop {
                 log.trace("Working", type_name::<Worker>());
-                match socket.read_message() {
-                    Ok(msg) => {
-                        let msg = msg.to_text().unwrap();
-
-                        if msg.contains("PING :tmi.twitch.tv") {
-                            socket
-                                .write_message(tungstenite::Message::Text(
-                                    "PONG :tmi.twitch.tv".into(),
-                                ))
-                                .unwrap();
-                        } else {
-                            tx.send(Message::Text(msg.into())).unwrap();
+                let mut reconnect = false;
+                // reads wait for data for a short while, this is where the
+                // worker sleeps when there is nothing to do
+                for _ in 0..MAX_FRAMES_PER_WAKEUP {
+                    match socket.read() {
+                        Ok(Some(msg)) => {
+                            if let Some(Err(err)) = recorder
+                                .as_mut()
+                                .map(|recorder| recorder.record(Utc::now(), &msg))
+                            {
+                                log.error(
+                                    format!("Could not record, stopped recording: {}", err),
+                                    type_name::<Worker>(),
+                                );
+                                recorder = None;
+                            }
+                            if forward(&mut socket, &msg, &tx, &mut ping_sent) {
+                                reconnect = true;
+                                break;
+                            }
                         }
-                    }
-                    Err(WebClientErr::Io(ref err)) if err.kind() == ErrorKind::WouldBlock => {}
-                    Err(err) => match err {
-                        WebClientErr::ConnectionClosed => {
-                            log.debug("Connection closed", type_name::<Worker>());
+                        Ok(None) => break,
+                        Err(err) if closing => {
+                            log.debug(format!("Connection closed: {}", err), type_name::<Worker>());
+                            break 'worker;
+                        }
+                        Err(err) => {
+                            log.warn(format!("Connection lost: {}", err), type_name::<Worker>());
+                            reconnect = true;
                             break;
                         }
-                        _ => log.error(
-                            format!("something very unexpected happened: {}", err),
+                    }
+                }
+                if close_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    log.debug(
+                        "Gave up waiting for the connection to close",
+                        type_name::<Worker>(),
+                    );
+                    break;
+                }
+                while let Ok(message) = receiver.try_recv() {
+                    match message {
+                        Message::Send { channel, text } => outbox.push_back((channel, text)),
+                        Message::Terminate => {
+                            log.debug("Closing connection to Twitch", type_name::<Worker>());
+                            closing = true;
+                            close_deadline = Some(Instant::now() + CLOSE_TIMEOUT);
+                            socket.close();
+                        }
+                        Message::Text(_) | Message::State(_) | Message::Latency(_) => (),
+                    }
+                }
+                while !closing
+                    && !reconnect
+                    && !joins.is_empty()
+                    && join_limiter.try_acquire(Instant::now())
+                {
+                    let channel = joins.pop_front().unwrap();
+                    log.debug(
+                        format!("Joining channel: {}", channel),
+                        type_name::<Worker>(),
+                    );
+                    if let Err(err) = socket.write(&format!("JOIN #{}", channel)) {
+                        log.error(
+                            format!("Could not join channel: {}", err),
                             type_name::<Worker>(),
-                        ),
-                    },
+                        );
+                        joins.push_front(channel);
+                        reconnect = true;
+                    }
+                }
+                if !closing && !reconnect && Instant::now() >= next_ping {
+                    next_ping = Instant::now() + PING_INTERVAL;
+                    match socket.write(&format!("PING :{}", PING_TOKEN)) {
+                        Ok(_) => ping_sent = Some(Instant::now()),
+                        Err(err) => {
+                            log.error(format!("Could not ping: {}", err), type_name::<Worker>());
+                            reconnect = true;
+                        }
+                    }
+                }
+                while !closing
+                    && !reconnect
+                    && !outbox.is_empty()
+                    && limiter.try_acquire(Instant::now())
+                {
+                    let (channel, text) = outbox.pop_front().unwrap();
+                    let line = format!("PRIVMSG #{} :{}", channel, text);
+                    log.debug(format!("Sending: {}", line), type_name::<Worker>());
+                    match socket.write(&line) {
+                        Ok(_) => {
+                            // twitch does not echo our own messages back
+                            sent += 1;
+                            tx.send(Message::Text(echo(
+                                &credentials.nick,
+                                &channel,
+                                &text,
+                                sent,
+                            )))
+                            .unwrap_or(());
+                        }
+                        Err(err) => {
+                            log.error(
+                                format!("Could not send message: {}", err),
+                                type_name::<Worker>(),
+                            );
+                            outbox.push_front((channel, text));
+                            reconnect = true;
+                        }
+                    }
                 }
-                if let Ok(Message::Terminate) = receiver.try_recv() {
-                    log.debug("Closing connection to Twitch", type_name::<Worker>());
-                    socket
-                        .close(Some(CloseFrame {
-                            code: CloseCode::Normal,
-                            reason: Cow::Borrowed(""),
-                        }))
-                        .unwrap();
+                if reconnect && !closing {
+                    socket.close();
+                    tx.send(Message::State(ConnectionState::Disconnected))
+                        .unwrap_or(());
+                    match redial(&credentials, &receiver, &tx, &mut outbox) {
+                        Some(new_socket) => socket = new_socket,
+                        None => break,
+                    }
+                    joins = credentials.channels.iter().cloned().collect();
+                    next_ping = Instant::now();
+                    ping_sent = None;
                 }
             }
         })?;
 
         Ok(Worker {
-            name,
             thread: Some(handle),
             receiver: rx,
         })
     }
 }
+
+/// Answers PINGs, times the PONGs to our own and hands everything else to
+/// the UI, returns true if we should reconnect, either because Twitch asked
+/// us to or the PONG failed.
+fn forward(
+    socket: &mut Socket,
+    msg: &str,
+    tx: &Sender<Message>,
+    ping_sent: &mut Option<Instant>,
+) -> bool {
+    let log = get_logger();
+    let mut reconnect = false;
+    let mut lines = Vec::new();
+    for line in msg.lines() {
+        match ChatMessage::parse(line).map(|message| message.command) {
+            Ok(Command::Ping(_)) => {
+                let pong = line.replacen("PING", "PONG", 1);
+                if socket.write(&pong).is_err() {
+                    reconnect = true;
+                }
+            }
+            Ok(Command::Pong(_)) => {
+                if let Some(sent) = ping_sent.take() {
+                    tx.send(Message::Latency(sent.elapsed())).unwrap_or(());
+                }
+            }
+            Ok(Command::Reconnect) => {
+                log.info("Twitch asked us to reconnect", type_name::<Worker>());
+                reconnect = true;
+            }
+            _ => lines.push(line),
+        }
+    }
+    if !lines.is_empty() {
+        tx.send(Message::Text(lines.join("\r\n"))).unwrap_or(());
+    }
+    reconnect
+}
+
+/// Builds the line Twitch would have sent other viewers for a message we
+/// sent ourselves, so it can be shown in the chat like any other.
+fn echo(nick: &str, channel: &str, text: &str, n: usize) -> String {
+    format!(
+        "@display-name={nick};id=local-{n};room-id=0;tmi-sent-ts={ts};user-id=0 :{nick}!{nick}@{nick}.tmi.twitch.tv PRIVMSG #{channel} :{text}",
+        ts = Utc::now().timestamp_millis()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::log;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+    use tungstenite::Message as SocketMessage;
+
+    #[test]
+    fn should_echo_as_privmsg() {
+        let line = echo("foo", "dallas", "hello there", 3);
+        match ChatMessage::parse(&line).unwrap().command {
+            Command::PrivMsg(message) => {
+                assert_eq!(message.meta_data.id, "local-3");
+                assert_eq!(message.meta_data.user_info.display_name, Some("foo"));
+                assert_eq!(message.destination, "dallas");
+                assert_eq!(message.message, "hello there");
+            }
+            command => panic!("expected PRIVMSG, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn should_back_off_exponentially_with_jitter() {
+        assert_eq!(backoff_delay(0, 1.0), Duration::from_secs(1));
+        assert_eq!(backoff_delay(0, 0.0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(3, 1.0), Duration::from_secs(8));
+        assert_eq!(backoff_delay(3, 0.5), Duration::from_secs(6));
+        assert_eq!(backoff_delay(10, 1.0), BACKOFF_MAX);
+        assert_eq!(backoff_delay(u32::MAX, 1.0), BACKOFF_MAX);
+    }
+
+    #[test]
+    fn should_reconnect_and_log_in_again_when_asked_to() {
+        log::init();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (logins, received) = unbounded();
+
+        let server = thread::spawn(move || {
+            for (n, stream) in listener.incoming().take(2).enumerate() {
+                let mut socket = tungstenite::accept(stream.unwrap()).unwrap();
+                let login: Vec<String> = (0..4)
+                    .map(|_| socket.read_message().unwrap().into_text().unwrap())
+                    .collect();
+                logins.send(login).unwrap();
+                let line = if n == 0 {
+                    ":tmi.twitch.tv RECONNECT"
+                } else {
+                    ":foo!foo@foo.tmi.twitch.tv PRIVMSG #dallas :welcome back"
+                };
+                socket
+                    .write_message(SocketMessage::Text(line.into()))
+                    .unwrap();
+                while socket.read_message().is_ok() {}
+            }
+        });
+
+        let client =
+            TwitchClient::new(url, "token", vec!["dallas".to_string()], "foo", None).unwrap();
+        let mut events = Vec::new();
+        while let Ok(message) = client.receiver.recv_timeout(Duration::from_secs(5)) {
+            let done = matches!(message, Message::Text(_));
+            events.push(message);
+            if done {
+                break;
+            }
+        }
+        drop(client);
+        server.join().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Message::State(ConnectionState::Connected),
+                Message::State(ConnectionState::Disconnected),
+                Message::State(ConnectionState::Connected),
+                Message::Text(":foo!foo@foo.tmi.twitch.tv PRIVMSG #dallas :welcome back".into()),
+            ]
+        );
+        let expected = vec![
+            "PASS oauth:token".to_string(),
+            "NICK foo".to_string(),
+            "CAP REQ :twitch.tv/tags".to_string(),
+            "JOIN #dallas".to_string(),
+        ];
+        assert_eq!(
+            received.try_iter().collect::<Vec<_>>(),
+            vec![expected.clone(), expected]
+        );
+    }
+
+    #[test]
+    fn should_speak_irc_over_tcp() {
+        log::init();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("irc://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut read_line = || {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                line
+            };
+            let login: Vec<String> = (0..5).map(|_| read_line()).collect();
+            let ping = read_line();
+            writer
+                .write_all(b":tmi.twitch.tv PONG tmi.twitch.tv :tmi.twitch.tv\r\n")
+                .unwrap();
+
+            writer
+                .write_all(b"PING :tmi.twitch.tv\r\n:foo!foo@foo.tmi.twitch.tv PRIV")
+                .unwrap();
+            writer.flush().unwrap();
+            let pong = read_line();
+            writer.write_all(b"MSG #dallas :over tcp\r\n").unwrap();
+            (login, ping, pong)
+        });
+
+        let client = TwitchClient::new(
+            url,
+            "token",
+            vec!["dallas".to_string(), "forsen".to_string()],
+            "foo",
+            None,
+        )
+        .unwrap();
+        let mut text = None;
+        let mut latency = None;
+        while let Ok(message) = client.receiver.recv_timeout(Duration::from_secs(5)) {
+            match message {
+                Message::Text(line) => {
+                    text = Some(line);
+                    break;
+                }
+                Message::Latency(rtt) => latency = Some(rtt),
+                _ => (),
+            }
+        }
+        let (login, ping, pong) = server.join().unwrap();
+        drop(client);
+
+        assert_eq!(
+            login,
+            vec![
+                "PASS oauth:token\r\n",
+                "NICK foo\r\n",
+                "CAP REQ :twitch.tv/tags\r\n",
+                "JOIN #dallas\r\n",
+                "JOIN #forsen\r\n"
+            ]
+        );
+        assert_eq!(ping, "PING :tmi.twitch.tv\r\n");
+        assert!(latency.is_some_and(|rtt| rtt < Duration::from_secs(1)));
+        assert_eq!(pong, "PONG :tmi.twitch.tv\r\n");
+        assert_eq!(
+            text,
+            Some(":foo!foo@foo.tmi.twitch.tv PRIVMSG #dallas :over tcp".to_string())
+        );
+    }
+
+    #[test]
+    fn should_forward_frames_without_delay_and_stop_promptly() {
+        log::init();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (go, wait) = unbounded::<()>();
+
+        let server = thread::spawn(move || {
+            let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            for _ in 0..4 {
+                socket.read_message().unwrap();
+            }
+            wait.recv().unwrap();
+            let sent = Instant::now();
+            for n in 0..10 {
+                let line = format!(":foo!foo@foo.tmi.twitch.tv PRIVMSG #dallas :{}", n);
+                socket.write_message(SocketMessage::Text(line)).unwrap();
+            }
+            while socket.read_message().is_ok() {}
+            sent
+        });
+
+        let client =
+            TwitchClient::new(url, "token", vec!["dallas".to_string()], "foo", None).unwrap();
+        assert_eq!(
+            client.receiver.recv_timeout(Duration::from_secs(5)),
+            Ok(Message::State(ConnectionState::Connected))
+        );
+        go.send(()).unwrap();
+        let mut received = Vec::new();
+        for _ in 0..10 {
+            match client.receiver.recv_timeout(Duration::from_secs(5)) {
+                Ok(Message::Text(line)) => received.push(line),
+                other => panic!("expected line {}, got {:?}", received.len(), other),
+            }
+        }
+        let arrived = Instant::now();
+
+        let closing = Instant::now();
+        drop(client);
+        let closed = closing.elapsed();
+        let sent = server.join().unwrap();
+
+        let expected: Vec<String> = (0..10)
+            .map(|n| format!(":foo!foo@foo.tmi.twitch.tv PRIVMSG #dallas :{}", n))
+            .collect();
+        assert_eq!(received, expected);
+        // a sleep between reads would add up to seconds for ten lines, the
+        // margins are wide so that a busy machine does not fail the test
+        assert!(
+            arrived - sent < Duration::from_secs(1),
+            "took {:?}",
+            arrived - sent
+        );
+        // the server answers the close, the timeout is never waited for
+        assert!(closed < CLOSE_TIMEOUT, "took {:?}", closed);
+    }
+}